@@ -18,6 +18,17 @@ This will build a binary that can be run with the following command
 This will use a dataset from the `datasets` folder, the supported datasets can be seen in `test/tests.rs`
 `RUST_LOG` part can be removed to the users liking. This removes debugging logging when the algorithm will run.
 
+### Anonymizing other datasets
+Any CSV file can be anonymized without writing Rust code by adding it to `datasets` in the `application.conf`.
+The columns of the CSV are described by a HOCON or JSON schema, which tells which columns are
+`interval`, `ordinal` or `nominal` QIs, which columns are `sensitive` and which columns are a `passthrough`.
+Examples for `airlines.csv` and `sea.csv` can be found in the `schemas` folder.
+```hocon
+datasets = [
+    { path = "datasets/sea.csv", export = "exports/sea", schema = "schemas/sea.conf" }
+]
+```
+
 ## Where is the data exported
 When `main.rs` is run, the processed datasets can be seen in the `exports` directory.
 
@@ -38,6 +49,9 @@ Additional parameters can be easily added through the `config.rs` file by adding
 | delta     | The maximum time in seconds a cluster can exist without the addition of new data tuples                        |
 | buff_size | The maximum amount of tuples the buffers W_curr and W_prev can contain                                         |
 | noise_thr | categorical noise level                                         |
+| datasets  | CSV datasets with their schema, the built-in datasets are used when empty |
+| record_error_policy | `skip`, `dead_letter` or `abort` on records that can't be anonymized, defaults to `abort` |
+| dead_letter_topic   | Kafka topic the raw rejected records are sent to with the `dead_letter` policy |
 
 # Documentation
 {{readme}}
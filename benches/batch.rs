@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use diff_priv::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+use diff_priv::noise::laplace::laplace_noiser::LaplaceNoiser;
+use diff_priv::test::adult_large::AdultLarge;
+use diff_priv::test::dummy_publisher::DummyPublisher;
+
+const DATASET: &str = "datasets/Adult_2_numerical_categorical_class_50K_drift.csv";
+const ROWS: usize = 5000;
+
+fn load() -> Vec<AdultLarge> {
+    csv::Reader::from_path(DATASET)
+        .expect("AdultLarge dataset not found")
+        .deserialize()
+        .take(ROWS)
+        .map(|row| row.expect("bad AdultLarge row"))
+        .collect()
+}
+
+fn anonymizer() -> MicroaggAnonymizer<LaplaceNoiser, AdultLarge, DummyPublisher> {
+    MicroaggAnonymizer::builder(DummyPublisher::default(), LaplaceNoiser::new(0.1, 3, 0.1))
+        .k(5)
+        .l(2)
+        .diff_thres(0.05)
+        .delta(3600)
+        .seed(42)
+        .build()
+        .unwrap()
+}
+
+/// tuples anonymized one by one versus in batches, which look up their clusters in parallel
+fn throughput(c: &mut Criterion) {
+    let rows = load();
+    let mut group = c.benchmark_group("adult_large");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(rows.len() as u64));
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let mut anonymizer = anonymizer();
+            rows.iter()
+                .cloned()
+                .for_each(|row| anonymizer.anonymize(row));
+            anonymizer.cluster_set.len()
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| {
+            let mut anonymizer = anonymizer();
+            anonymizer.anonymize_batch(rows.iter().cloned());
+            anonymizer.cluster_set.len()
+        })
+    });
+    group.finish()
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use diff_priv::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+use diff_priv::data_manipulation::distance::{DistanceMetric, Gower, QiDifference};
+use diff_priv::noise::laplace::laplace_noiser::LaplaceNoiser;
+use diff_priv::test::adult_large::AdultLarge;
+use diff_priv::test::dummy_publisher::DummyPublisher;
+use std::sync::Arc;
+
+const DATASET: &str = "datasets/Adult_2_numerical_categorical_class_50K_drift.csv";
+const ROWS: usize = 5000;
+
+/// gower distance without a lower bound, so every cluster is compared with every tuple
+struct Scan;
+
+impl DistanceMetric for Scan {
+    fn distance(&self, differences: &[QiDifference]) -> f64 {
+        Gower.distance(differences)
+    }
+}
+
+fn load() -> Vec<AdultLarge> {
+    csv::Reader::from_path(DATASET)
+        .expect("AdultLarge dataset not found")
+        .deserialize()
+        .take(ROWS)
+        .map(|row| row.expect("bad AdultLarge row"))
+        .collect()
+}
+
+/// a small difference threshold and a long life time keep thousands of clusters alive
+fn anonymize(rows: &[AdultLarge], metric: Arc<dyn DistanceMetric>) -> usize {
+    let mut anonymizer: MicroaggAnonymizer<LaplaceNoiser, AdultLarge, DummyPublisher> =
+        MicroaggAnonymizer::builder(DummyPublisher::default(), LaplaceNoiser::new(0.1, 3, 0.1))
+            .k(5)
+            .l(2)
+            .diff_thres(0.05)
+            .delta(3600)
+            .distance_metric(metric)
+            .seed(42)
+            .build()
+            .unwrap();
+    rows.iter()
+        .cloned()
+        .for_each(|row| anonymizer.anonymize(row));
+    anonymizer.cluster_set.len()
+}
+
+fn best_cluster_lookup(c: &mut Criterion) {
+    let rows = load();
+    let mut group = c.benchmark_group("adult_large");
+    group.sample_size(10);
+    group.bench_function("cluster_index", |b| {
+        b.iter(|| anonymize(&rows, Arc::new(Gower)))
+    });
+    group.bench_function("scan", |b| b.iter(|| anonymize(&rows, Arc::new(Scan))));
+    group.finish()
+}
+
+criterion_group!(benches, best_cluster_lookup);
+criterion_main!(benches);
@@ -0,0 +1,1175 @@
+//! # DiffPriv derive
+//! Derive macro generating the implementation of the `Anonymizable` trait of
+//! [DiffPriv](https://github.com/redwhitemike/DiffPriv).
+//!
+//! The macro is re-exported by `diff_priv::data_manipulation::anonymizable`, it should not be
+//! needed to depend on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    bracketed, parse_macro_input, Data, DeriveInput, Error, Expr, Field, Fields, Ident, Lit,
+    LitInt, LitStr, Token, Type,
+};
+
+/// Derive the `Anonymizable` trait for a struct with named fields.
+///
+/// The quasi identifiers are extracted in the order the fields are declared, the generated
+/// `update_quasi_identifiers` consumes them in the same order.
+///
+/// # Field attributes
+/// * `#[qi(interval, min = 1, max = 100, weight = 1)]` on an `i32`, `f32` or `f64` field
+/// * `#[qi(ordinal, categories = ["low", "high"], weight = 1)]` on a `String` field, the
+///   rank of a category is its position in the list starting at 1
+/// * `#[qi(ordinal, max = 10)]` on an `i32` field containing the rank
+/// * `#[qi(nominal, categories = ["male", "female"], weight = 1)]` on a `String` field, the
+///   value of a category is its position in the list starting at 0
+/// * `#[qi(nominal, max = 10)]` on an `i32` field containing the value
+/// * `#[qi(nominal, hierarchy = COUNTRIES)]` on a `String` field, where `COUNTRIES` is an
+///   expression dereferencing to an `Arc<Hierarchy>` (e.g. a `lazy_static`). The labels of the
+///   hierarchy are used as categories
+/// * `#[qi(temporal, min = "2022-01-01", max = "2023-01-01 12:00:00", granularity = "minute")]`
+///   on a `String` datetime, `SystemTime` or `i64` (seconds since the unix epoch) field. The
+///   boundaries are datetimes or seconds since the unix epoch, `granularity` is one of `second`
+///   (default), `minute`, `hour`, `day` or `week`
+/// * `#[qi(geo, max_distance = 50, privacy_radius = 0.5)]` on a `GeoPoint` field. Distances in
+///   kilometers are normalized by `max_distance`, which defaults to half the circumference of the
+///   earth. Noise makes points inside `privacy_radius` (default 1 km) indistinguishable. Add
+///   `equirectangular` to approximate distances instead of using the haversine formula
+/// * `median` on an interval, ordinal or temporal QI publishes the median instead of the mean or
+///   mode of a cluster. `trimmed_mean = 0.1` on an interval or temporal QI leaves out that
+///   proportion of the lowest and highest values, `weighted_mean` weighs the values by the
+///   `#[record_weight]` field
+/// * `#[sensitive]` on the `String` or `i32` fields containing the sensitive attributes, the
+///   first one is returned by `sensitive_value` and l-diversity is enforced for all of them
+/// * `#[sensitive(bucket_width = 1000.0)]` on an `f32` or `f64` sensitive field, values inside
+///   the same bucket are treated as equal. `bucket_width` defaults to 1
+/// * `#[arrival_time]` on the `SystemTime` field containing the time the tuple entered the algorithm
+/// * `#[event_time]` on the `SystemTime`, `i64` (seconds since the unix epoch) or `String`
+///   (datetime) field containing the time the event happened, used with `TimeMode::EventTime`.
+///   Missing or unparsable values fall back to the arrival time
+/// * `#[record_weight]` on an `i32`, `f32` or `f64` field containing the weight of the tuple in
+///   `weighted_mean` QI's
+///
+/// `weight` is optional and defaults to 1. All fields except the arrival time are exported by
+/// `extract_string_values` in the order they are declared.
+///
+/// # Missing values
+/// QI fields can be wrapped in an `Option`, `None` is extracted as a missing QI. How missing
+/// values are handled is set on the struct with `#[missing_values(ignore)]`,
+/// `#[missing_values(impute)]` or `#[missing_values(own_category)]`, the default is `ignore`.
+///
+/// # Rounding
+/// Aggregated integer QI's are truncated, which can be changed on the struct with
+/// `#[rounding(nearest)]`, `#[rounding(half_even)]`, `#[rounding(floor)]` or `#[rounding(ceil)]`.
+#[proc_macro_derive(
+    Anonymizable,
+    attributes(
+        qi,
+        sensitive,
+        arrival_time,
+        event_time,
+        record_weight,
+        missing_values,
+        rounding
+    )
+)]
+pub fn derive_anonymizable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// data category of a quasi identifier
+#[derive(Clone, Copy, PartialEq)]
+enum QiKind {
+    Interval,
+    Ordinal,
+    Nominal,
+    Temporal,
+    Geo,
+}
+
+/// supported rust types of annotated fields
+#[derive(Clone, Copy, PartialEq)]
+enum FieldType {
+    Integer,
+    Float,
+    SmallFloat,
+    Long,
+    Text,
+    Time,
+    Point,
+    Other,
+}
+
+/// numerical literal given as domain boundary
+#[derive(Clone, Copy)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+/// parsed content of a `#[qi(...)]` attribute
+struct QiAttribute {
+    kind: QiKind,
+    min: Option<Number>,
+    max: Option<Number>,
+    weight: usize,
+    categories: Vec<LitStr>,
+    hierarchy: Option<Expr>,
+    min_datetime: Option<LitStr>, // boundaries of a temporal QI given as datetime
+    max_datetime: Option<LitStr>,
+    granularity: Option<LitStr>,
+    median: bool,
+    trimmed_mean: Option<f64>, // proportion of the lowest and highest values left out of the mean
+    weighted_mean: bool,
+    max_distance: Option<Number>, // domain of a geo point QI in kilometers
+    privacy_radius: Option<Number>,
+    equirectangular: bool,
+}
+
+/// field annotated with `#[qi(...)]`
+struct QiField<'a> {
+    ident: &'a Ident,
+    field_type: FieldType, // type of the field, or of its content if it is optional
+    optional: bool,
+    attribute: QiAttribute,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Anonymizable can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Anonymizable can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut qi_fields: Vec<QiField> = Vec::new();
+    let mut sensitive: Vec<(&Field, f64)> = Vec::new();
+    let mut arrival_time: Option<&Field> = None;
+    let mut event_time: Option<&Field> = None;
+    let mut record_weight: Option<&Field> = None;
+
+    for field in fields {
+        for attr in &field.attrs {
+            if attr.path().is_ident("qi") {
+                let attribute = parse_qi_attribute(attr)?;
+                let (field_type, optional) = match option_content(&field.ty) {
+                    Some(ty) => (field_type(ty), true),
+                    None => (field_type(&field.ty), false),
+                };
+                let qi_field = QiField {
+                    ident: field.ident.as_ref().unwrap(),
+                    field_type,
+                    optional,
+                    attribute,
+                };
+                validate(&qi_field, field)?;
+                qi_fields.push(qi_field);
+            } else if attr.path().is_ident("sensitive") {
+                sensitive.push((field, parse_sensitive_attribute(attr, field)?));
+            } else if attr.path().is_ident("arrival_time") && arrival_time.replace(field).is_some()
+            {
+                return Err(Error::new_spanned(attr, "duplicate #[arrival_time] field"));
+            } else if attr.path().is_ident("event_time") {
+                let ty = option_content(&field.ty).unwrap_or(&field.ty);
+                if !matches!(
+                    field_type(ty),
+                    FieldType::Time | FieldType::Long | FieldType::Text
+                ) {
+                    return Err(Error::new(
+                        field.span(),
+                        "#[event_time] needs a SystemTime, i64 or String field",
+                    ));
+                }
+                if event_time.replace(field).is_some() {
+                    return Err(Error::new_spanned(attr, "duplicate #[event_time] field"));
+                }
+            } else if attr.path().is_ident("record_weight") {
+                if !matches!(
+                    field_type(&field.ty),
+                    FieldType::Integer | FieldType::Float | FieldType::SmallFloat
+                ) {
+                    return Err(Error::new(
+                        field.span(),
+                        "#[record_weight] needs an i32, f32 or f64 field",
+                    ));
+                }
+                if record_weight.replace(field).is_some() {
+                    return Err(Error::new_spanned(attr, "duplicate #[record_weight] field"));
+                }
+            }
+        }
+    }
+
+    if qi_fields.is_empty() {
+        return Err(Error::new_spanned(
+            name,
+            "at least one field needs to be annotated with #[qi(...)]",
+        ));
+    }
+    if sensitive.is_empty() {
+        return Err(Error::new_spanned(
+            name,
+            "missing a field annotated with #[sensitive]",
+        ));
+    }
+    let arrival_time = arrival_time.ok_or_else(|| {
+        Error::new_spanned(name, "missing a field annotated with #[arrival_time]")
+    })?;
+
+    if record_weight.is_none() && qi_fields.iter().any(|qi| qi.attribute.weighted_mean) {
+        return Err(Error::new_spanned(
+            name,
+            "`weighted_mean` needs a field annotated with #[record_weight]",
+        ));
+    }
+
+    let missing_value_policy = parse_missing_values(&input.attrs)?;
+    let rounding = parse_rounding(&input.attrs)?;
+    let aggregators = aggregators(&qi_fields);
+    let record_weight = record_weight.map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        quote! {
+            fn record_weight(&self) -> f64 {
+                self.#ident as f64
+            }
+        }
+    });
+    let extract = qi_fields.iter().map(extract_qi);
+    let update = qi_fields.iter().map(|qi| update_qi(qi, name));
+
+    let sensitive_values: Vec<TokenStream2> = sensitive
+        .iter()
+        .map(|(field, bucket_width)| {
+            let ident = field.ident.as_ref().unwrap();
+            let path = quote!(::diff_priv::data_manipulation::anonymizable::SensitiveAttribute);
+            match field_type(&field.ty) {
+                FieldType::Integer => quote!(#path::Integer(self.#ident)),
+                FieldType::Float | FieldType::SmallFloat => {
+                    quote!(#path::Float(self.#ident as f64, #bucket_width))
+                }
+                _ => quote!(#path::from(self.#ident.clone())),
+            }
+        })
+        .collect();
+    let sensitive_value = &sensitive_values[0];
+
+    let arrival_time_ident = arrival_time.ident.as_ref().unwrap();
+    let event_time = event_time.map(|field| event_time_expression(field, arrival_time_ident));
+    let exported = fields
+        .iter()
+        .filter(|field| field.ident != arrival_time.ident)
+        .map(|field| {
+            let ident = field.ident.as_ref().unwrap();
+            let temporal = quote!(::diff_priv::data_manipulation::temporal);
+            match option_content(&field.ty) {
+                Some(ty) if field_type(ty) == FieldType::Time => quote! {
+                    self.#ident
+                        .map(|value| #temporal::format_datetime(#temporal::from_system_time(value)))
+                        .unwrap_or_default()
+                },
+                Some(_) => quote! {
+                    self.#ident.as_ref().map(|value| value.to_string()).unwrap_or_default()
+                },
+                None if field_type(&field.ty) == FieldType::Time => {
+                    quote!(#temporal::format_datetime(#temporal::from_system_time(self.#ident)))
+                }
+                None => quote!(self.#ident.to_string()),
+            }
+        });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::diff_priv::data_manipulation::anonymizable::Anonymizable for #name #ty_generics #where_clause {
+            fn quasi_identifiers(&self) -> ::std::vec::Vec<::diff_priv::data_manipulation::anonymizable::QuasiIdentifierTypes> {
+                self.try_quasi_identifiers()
+                    .unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            fn try_quasi_identifiers(
+                &self,
+            ) -> ::std::result::Result<
+                ::std::vec::Vec<::diff_priv::data_manipulation::anonymizable::QuasiIdentifierTypes>,
+                ::diff_priv::error::DiffPrivError,
+            > {
+                Ok(vec![#(#extract),*])
+            }
+
+            fn update_quasi_identifiers(
+                &self,
+                qi: ::std::vec::Vec<::diff_priv::data_manipulation::anonymizable::QuasiIdentifierTypes>,
+            ) -> Self {
+                self.try_update_quasi_identifiers(qi)
+                    .unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            fn try_update_quasi_identifiers(
+                &self,
+                qi: ::std::vec::Vec<::diff_priv::data_manipulation::anonymizable::QuasiIdentifierTypes>,
+            ) -> ::std::result::Result<Self, ::diff_priv::error::DiffPrivError> {
+                let mut update = self.clone();
+                let mut qi = qi.into_iter();
+                #(#update)*
+                Ok(update)
+            }
+
+            fn sensitive_value(&self) -> ::diff_priv::data_manipulation::anonymizable::SensitiveAttribute {
+                #sensitive_value
+            }
+
+            fn sensitive_values(&self) -> ::std::vec::Vec<::diff_priv::data_manipulation::anonymizable::SensitiveAttribute> {
+                vec![#(#sensitive_values),*]
+            }
+
+            fn extract_string_values(&self, uuid: ::diff_priv::uuid::Uuid, dr: f64) -> ::std::vec::Vec<::std::string::String> {
+                vec![uuid.to_string(), dr.to_string(), #(#exported),*]
+            }
+
+            fn get_timestamp(&self) -> ::std::time::SystemTime {
+                self.#arrival_time_ident
+            }
+
+            #event_time
+
+            #missing_value_policy
+
+            #aggregators
+
+            #rounding
+
+            #record_weight
+        }
+    })
+}
+
+/// implementation of `event_time` reading the annotated field
+fn event_time_expression(field: &Field, arrival_time: &Ident) -> TokenStream2 {
+    let temporal = quote!(::diff_priv::data_manipulation::temporal);
+    let ident = field.ident.as_ref().unwrap();
+    let value = match option_content(&field.ty) {
+        Some(ty) => match field_type(ty) {
+            FieldType::Time => quote!(self.#ident.unwrap_or(self.#arrival_time)),
+            FieldType::Long => quote! {
+                self.#ident
+                    .map(#temporal::to_system_time)
+                    .unwrap_or(self.#arrival_time)
+            },
+            _ => quote! {
+                self.#ident
+                    .as_deref()
+                    .and_then(|value| #temporal::parse_datetime(value).ok())
+                    .map(#temporal::to_system_time)
+                    .unwrap_or(self.#arrival_time)
+            },
+        },
+        None => match field_type(&field.ty) {
+            FieldType::Time => quote!(self.#ident),
+            FieldType::Long => quote!(#temporal::to_system_time(self.#ident)),
+            _ => quote! {
+                #temporal::parse_datetime(&self.#ident)
+                    .map(#temporal::to_system_time)
+                    .unwrap_or(self.#arrival_time)
+            },
+        },
+    };
+
+    quote! {
+        fn event_time(&self) -> ::std::time::SystemTime {
+            #value
+        }
+    }
+}
+
+/// implementation of `aggregators` when a QI doesn't use the default aggregation
+fn aggregators(qi_fields: &[QiField]) -> TokenStream2 {
+    let path = quote!(::diff_priv::data_manipulation::aggregation::Aggregator);
+    let aggregators: Vec<TokenStream2> = qi_fields
+        .iter()
+        .map(|qi| {
+            let attribute = &qi.attribute;
+            match (
+                attribute.median,
+                attribute.trimmed_mean,
+                attribute.weighted_mean,
+            ) {
+                (true, _, _) => quote!(#path::Median),
+                (_, Some(proportion), _) => quote!(#path::TrimmedMean(#proportion)),
+                (_, _, true) => quote!(#path::WeightedMean),
+                _ => quote!(#path::Default),
+            }
+        })
+        .collect();
+
+    match qi_fields.iter().any(|qi| {
+        qi.attribute.median || qi.attribute.trimmed_mean.is_some() || qi.attribute.weighted_mean
+    }) {
+        true => quote! {
+            fn aggregators(&self) -> ::std::vec::Vec<#path> {
+                vec![#(#aggregators),*]
+            }
+        },
+        false => quote!(),
+    }
+}
+
+/// parse `#[rounding(...)]` on the struct into the implementation of `rounding`
+fn parse_rounding(attrs: &[syn::Attribute]) -> Result<TokenStream2, Error> {
+    let mut rounding = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("rounding")) {
+        attr.parse_nested_meta(|meta| {
+            let variant = if meta.path.is_ident("truncate") {
+                quote!(Truncate)
+            } else if meta.path.is_ident("nearest") {
+                quote!(Nearest)
+            } else if meta.path.is_ident("half_even") {
+                quote!(HalfEven)
+            } else if meta.path.is_ident("floor") {
+                quote!(Floor)
+            } else if meta.path.is_ident("ceil") {
+                quote!(Ceil)
+            } else {
+                return Err(meta.error(
+                    "expected one of `truncate`, `nearest`, `half_even`, `floor` or `ceil`",
+                ));
+            };
+            rounding = Some(variant);
+            Ok(())
+        })?;
+    }
+
+    Ok(match rounding {
+        Some(variant) => {
+            let path = quote!(::diff_priv::data_manipulation::aggregation::Rounding);
+            quote! {
+                fn rounding(&self) -> #path {
+                    #path::#variant
+                }
+            }
+        }
+        None => quote!(),
+    })
+}
+
+/// parse `#[missing_values(...)]` on the struct into the implementation of `missing_value_policy`
+fn parse_missing_values(attrs: &[syn::Attribute]) -> Result<TokenStream2, Error> {
+    let mut policy = None;
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("missing_values"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let variant = if meta.path.is_ident("ignore") {
+                quote!(Ignore)
+            } else if meta.path.is_ident("impute") {
+                quote!(Impute)
+            } else if meta.path.is_ident("own_category") {
+                quote!(OwnCategory)
+            } else {
+                return Err(meta.error("expected one of `ignore`, `impute` or `own_category`"));
+            };
+            policy = Some(variant);
+            Ok(())
+        })?;
+    }
+
+    Ok(match policy {
+        Some(variant) => {
+            let path = quote!(::diff_priv::data_manipulation::anonymizable::MissingValuePolicy);
+            quote! {
+                fn missing_value_policy(&self) -> #path {
+                    #path::#variant
+                }
+            }
+        }
+        None => quote!(),
+    })
+}
+
+/// parse the content of `#[qi(...)]`
+fn parse_qi_attribute(attr: &syn::Attribute) -> Result<QiAttribute, Error> {
+    let mut kind = None;
+    let mut min = None;
+    let mut max = None;
+    let mut weight = 1;
+    let mut categories = Vec::new();
+    let mut hierarchy = None;
+    let mut min_datetime = None;
+    let mut max_datetime = None;
+    let mut granularity = None;
+    let mut median = false;
+    let mut trimmed_mean = None;
+    let mut weighted_mean = false;
+    let mut max_distance = None;
+    let mut privacy_radius = None;
+    let mut equirectangular = false;
+
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("interval") {
+            kind = Some(QiKind::Interval);
+        } else if meta.path.is_ident("ordinal") {
+            kind = Some(QiKind::Ordinal);
+        } else if meta.path.is_ident("nominal") {
+            kind = Some(QiKind::Nominal);
+        } else if meta.path.is_ident("temporal") {
+            kind = Some(QiKind::Temporal);
+        } else if meta.path.is_ident("geo") {
+            kind = Some(QiKind::Geo);
+        } else if meta.path.is_ident("max_distance") {
+            max_distance = Some(parse_number(meta.value()?)?);
+        } else if meta.path.is_ident("privacy_radius") {
+            privacy_radius = Some(parse_number(meta.value()?)?);
+        } else if meta.path.is_ident("equirectangular") {
+            equirectangular = true;
+        } else if meta.path.is_ident("min") {
+            let value = meta.value()?;
+            match value.peek(LitStr) {
+                true => min_datetime = Some(value.parse::<LitStr>()?),
+                false => min = Some(parse_number(value)?),
+            }
+        } else if meta.path.is_ident("max") {
+            let value = meta.value()?;
+            match value.peek(LitStr) {
+                true => max_datetime = Some(value.parse::<LitStr>()?),
+                false => max = Some(parse_number(value)?),
+            }
+        } else if meta.path.is_ident("granularity") {
+            granularity = Some(meta.value()?.parse::<LitStr>()?);
+        } else if meta.path.is_ident("median") {
+            median = true;
+        } else if meta.path.is_ident("trimmed_mean") {
+            trimmed_mean = Some(match parse_number(meta.value()?)? {
+                Number::Integer(value) => value as f64,
+                Number::Float(value) => value,
+            });
+        } else if meta.path.is_ident("weighted_mean") {
+            weighted_mean = true;
+        } else if meta.path.is_ident("weight") {
+            weight = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+        } else if meta.path.is_ident("categories") {
+            let value = meta.value()?;
+            let content;
+            bracketed!(content in value);
+            categories = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                .into_iter()
+                .collect();
+        } else if meta.path.is_ident("hierarchy") {
+            hierarchy = Some(meta.value()?.parse::<Expr>()?);
+        } else {
+            return Err(meta.error("unsupported qi attribute"));
+        }
+        Ok(())
+    })?;
+
+    let kind = kind.ok_or_else(|| {
+        Error::new_spanned(
+            attr,
+            "expected one of `interval`, `ordinal`, `nominal`, `temporal` or `geo`",
+        )
+    })?;
+
+    Ok(QiAttribute {
+        kind,
+        min,
+        max,
+        weight,
+        categories,
+        hierarchy,
+        min_datetime,
+        max_datetime,
+        granularity,
+        median,
+        trimmed_mean,
+        weighted_mean,
+        max_distance,
+        privacy_radius,
+        equirectangular,
+    })
+}
+
+/// parse the optional `bucket_width` of `#[sensitive(...)]`
+fn parse_sensitive_attribute(attr: &syn::Attribute, field: &Field) -> Result<f64, Error> {
+    let mut bucket_width = None;
+    if let syn::Meta::List(_) = attr.meta {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("bucket_width") {
+                bucket_width = Some(match parse_number(meta.value()?)? {
+                    Number::Integer(value) => value as f64,
+                    Number::Float(value) => value,
+                });
+                Ok(())
+            } else {
+                Err(meta.error("unsupported sensitive attribute"))
+            }
+        })?;
+    }
+
+    match (field_type(&field.ty), bucket_width) {
+        (FieldType::Float | FieldType::SmallFloat, Some(width)) if width > 0.0 => Ok(width),
+        (FieldType::Float | FieldType::SmallFloat, None) => Ok(1.0),
+        (FieldType::Float | FieldType::SmallFloat, Some(_)) => Err(Error::new_spanned(
+            attr,
+            "bucket_width needs to be positive",
+        )),
+        (FieldType::Integer | FieldType::Text, None) => Ok(1.0),
+        (FieldType::Integer | FieldType::Text, Some(_)) => Err(Error::new_spanned(
+            attr,
+            "bucket_width can only be used on f32 or f64 fields",
+        )),
+        (FieldType::Long | FieldType::Time | FieldType::Point | FieldType::Other, _) => {
+            Err(Error::new(
+                field.span(),
+                "#[sensitive] needs a String, i32, f32 or f64 field",
+            ))
+        }
+    }
+}
+
+/// parse a possibly negative integer or float literal
+fn parse_number(input: ParseStream) -> Result<Number, Error> {
+    let sign = match input.peek(Token![-]) {
+        true => {
+            input.parse::<Token![-]>()?;
+            -1
+        }
+        false => 1,
+    };
+
+    match input.parse::<Lit>()? {
+        Lit::Int(value) => Ok(Number::Integer(sign * value.base10_parse::<i64>()?)),
+        Lit::Float(value) => Ok(Number::Float(sign as f64 * value.base10_parse::<f64>()?)),
+        lit => Err(Error::new(lit.span(), "expected a numerical literal")),
+    }
+}
+
+/// the type inside of an `Option`
+fn option_content(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments) if segment.ident == "Option" => {
+            match arguments.args.first()? {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn field_type(ty: &Type) -> FieldType {
+    match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) if segment.ident == "i32" => FieldType::Integer,
+            Some(segment) if segment.ident == "f64" => FieldType::Float,
+            Some(segment) if segment.ident == "f32" => FieldType::SmallFloat,
+            Some(segment) if segment.ident == "i64" => FieldType::Long,
+            Some(segment) if segment.ident == "String" => FieldType::Text,
+            Some(segment) if segment.ident == "SystemTime" => FieldType::Time,
+            Some(segment) if segment.ident == "GeoPoint" => FieldType::Point,
+            _ => FieldType::Other,
+        },
+        _ => FieldType::Other,
+    }
+}
+
+/// check that the attribute can be applied on the type of the field
+fn validate(qi: &QiField, field: &Field) -> Result<(), Error> {
+    let attribute = &qi.attribute;
+    let error = |message: &str| Err(Error::new(field.span(), message));
+
+    let numerical = matches!(attribute.kind, QiKind::Interval | QiKind::Temporal);
+    match (
+        attribute.median,
+        attribute.trimmed_mean,
+        attribute.weighted_mean,
+    ) {
+        (true, Some(_), _) | (true, _, true) | (_, Some(_), true) => {
+            return error("only one of `median`, `trimmed_mean` and `weighted_mean` can be used")
+        }
+        (true, _, _) if !numerical && attribute.kind != QiKind::Ordinal => {
+            return error("`median` needs an interval, ordinal or temporal quasi identifier")
+        }
+        (_, Some(_), _) | (_, _, true) if !numerical => {
+            return error(
+                "`trimmed_mean` and `weighted_mean` need an interval or temporal quasi identifier",
+            )
+        }
+        (_, Some(proportion), _) if !(0.0..0.5).contains(&proportion) => {
+            return error("`trimmed_mean` needs a proportion in 0..0.5")
+        }
+        _ => {}
+    }
+
+    if attribute.hierarchy.is_some() {
+        return match (attribute.kind, qi.field_type) {
+            (QiKind::Nominal, FieldType::Text) if attribute.categories.is_empty() => Ok(()),
+            (QiKind::Nominal, FieldType::Text) => {
+                error("hierarchical quasi identifiers can't have categories")
+            }
+            _ => error("hierarchies are only supported for nominal quasi identifiers on a String"),
+        };
+    }
+
+    if attribute.kind != QiKind::Temporal
+        && (attribute.min_datetime.is_some()
+            || attribute.max_datetime.is_some()
+            || attribute.granularity.is_some())
+    {
+        return error("datetime boundaries and `granularity` need a temporal quasi identifier");
+    }
+
+    if attribute.kind != QiKind::Geo
+        && (attribute.max_distance.is_some()
+            || attribute.privacy_radius.is_some()
+            || attribute.equirectangular)
+    {
+        return error(
+            "`max_distance`, `privacy_radius` and `equirectangular` need a geo quasi identifier",
+        );
+    }
+
+    match (attribute.kind, qi.field_type) {
+        (QiKind::Geo, FieldType::Point) => {
+            if attribute.min.is_some() || attribute.max.is_some() {
+                return error(
+                    "geo quasi identifiers use `max_distance` instead of `min` and `max`",
+                );
+            }
+            if !attribute.categories.is_empty() {
+                return error("geo quasi identifiers can't have categories");
+            }
+            let positive = |number: Option<Number>| match number {
+                Some(Number::Integer(value)) => value > 0,
+                Some(Number::Float(value)) => value > 0.0,
+                None => true,
+            };
+            match positive(attribute.max_distance) && positive(attribute.privacy_radius) {
+                true => Ok(()),
+                false => error("`max_distance` and `privacy_radius` need to be positive"),
+            }
+        }
+        (QiKind::Geo, _) => error("geo quasi identifiers need a GeoPoint field"),
+        (QiKind::Temporal, FieldType::Text | FieldType::Time | FieldType::Long) => {
+            let bound = |number: Option<Number>, datetime: &Option<LitStr>| {
+                matches!(number, Some(Number::Integer(_))) || datetime.is_some()
+            };
+            if !bound(attribute.min, &attribute.min_datetime)
+                || !bound(attribute.max, &attribute.max_datetime)
+            {
+                return error(
+                    "temporal quasi identifiers need a `min` and `max` datetime or integer timestamp",
+                );
+            }
+            if !attribute.categories.is_empty() {
+                return error("temporal quasi identifiers can't have categories");
+            }
+            match &attribute.granularity {
+                Some(granularity) if granularity_variant(granularity).is_none() => Err(Error::new(
+                    granularity.span(),
+                    "expected one of `second`, `minute`, `hour`, `day` or `week`",
+                )),
+                _ => Ok(()),
+            }
+        }
+        (QiKind::Temporal, _) => {
+            error("temporal quasi identifiers need a String, SystemTime or i64 field")
+        }
+        (QiKind::Interval, FieldType::Integer | FieldType::Float | FieldType::SmallFloat) => {
+            if attribute.min.is_none() || attribute.max.is_none() {
+                return error("interval quasi identifiers need a `min` and `max`");
+            }
+            if qi.field_type == FieldType::Integer
+                && matches!(
+                    (attribute.min, attribute.max),
+                    (Some(Number::Float(_)), _) | (_, Some(Number::Float(_)))
+                )
+            {
+                return error("integer interval quasi identifiers need integer boundaries");
+            }
+            if !attribute.categories.is_empty() {
+                return error("interval quasi identifiers can't have categories");
+            }
+            Ok(())
+        }
+        (QiKind::Interval, _) => error("interval quasi identifiers need an i32, f32 or f64 field"),
+        (_, FieldType::Text) => match attribute.categories.is_empty() {
+            true => error("categorical quasi identifiers on a String field need `categories`"),
+            false => Ok(()),
+        },
+        (_, FieldType::Integer) => match attribute.max {
+            Some(Number::Integer(_)) if attribute.categories.is_empty() => Ok(()),
+            _ => error("categorical quasi identifiers on an i32 field need an integer `max`"),
+        },
+        _ => error("categorical quasi identifiers need a String or i32 field"),
+    }
+}
+
+/// variant of `Granularity` named by the `granularity` of a temporal QI
+fn granularity_variant(granularity: &LitStr) -> Option<Ident> {
+    let variant = match granularity.value().as_str() {
+        "second" => "Second",
+        "minute" => "Minute",
+        "hour" => "Hour",
+        "day" => "Day",
+        "week" => "Week",
+        _ => return None,
+    };
+    Some(Ident::new(variant, granularity.span()))
+}
+
+/// expression of the boundary of a temporal QI in seconds since the unix epoch
+fn temporal_bound(number: Option<Number>, datetime: &Option<LitStr>) -> TokenStream2 {
+    match (datetime, number) {
+        (Some(datetime), _) => {
+            quote!(::diff_priv::data_manipulation::temporal::parse_datetime(#datetime)?)
+        }
+        (None, Some(Number::Integer(value))) => Literal::i64_suffixed(value).into_token_stream(),
+        _ => unreachable!("temporal boundaries have been validated"),
+    }
+}
+
+fn integer_literal(number: Number) -> Literal {
+    match number {
+        Number::Integer(value) => Literal::i32_unsuffixed(value as i32),
+        Number::Float(value) => Literal::i32_unsuffixed(value as i32),
+    }
+}
+
+fn float_literal(number: Number) -> Literal {
+    match number {
+        Number::Integer(value) => Literal::f64_unsuffixed(value as f64),
+        Number::Float(value) => Literal::f64_unsuffixed(value),
+    }
+}
+
+/// value a category is mapped to inside the QI
+fn category_value(kind: QiKind, index: usize) -> Literal {
+    match kind {
+        QiKind::Ordinal => Literal::i32_unsuffixed(index as i32 + 1),
+        _ => Literal::i32_unsuffixed(index as i32),
+    }
+}
+
+/// generate the expression extracting the QI from the field,
+/// `None` of an optional field becomes a missing QI
+fn extract_qi(qi: &QiField) -> TokenStream2 {
+    let path = quote!(::diff_priv::data_manipulation::anonymizable);
+    let ident = qi.ident;
+
+    match (qi.optional, qi.field_type) {
+        (false, FieldType::Text) => qi_expression(qi, quote!(self.#ident.as_str())),
+        (false, _) => qi_expression(qi, quote!(self.#ident)),
+        (true, field_type) => {
+            let content = match field_type {
+                FieldType::Text => quote!(self.#ident.as_deref()),
+                _ => quote!(self.#ident),
+            };
+            let present = qi_expression(qi, quote!(value));
+            let template = qi_expression(qi, placeholder(qi));
+            quote! {
+                match #content {
+                    Some(value) => #present,
+                    None => #path::QuasiIdentifierTypes::Missing(::std::boxed::Box::new(#template)),
+                }
+            }
+        }
+    }
+}
+
+/// value of the field used to create the template of a missing QI
+fn placeholder(qi: &QiField) -> TokenStream2 {
+    let attribute = &qi.attribute;
+    match (
+        &attribute.hierarchy,
+        attribute.categories.first(),
+        attribute.min,
+    ) {
+        (Some(_), _, _) => quote!(::diff_priv::data_manipulation::hierarchy::ROOT_LABEL),
+        (None, Some(category), _) => quote!(#category),
+        _ if attribute.kind == QiKind::Temporal => match qi.field_type {
+            FieldType::Text => quote!("1970-01-01"),
+            FieldType::Time => quote!(::std::time::UNIX_EPOCH),
+            _ => quote!(0i64),
+        },
+        _ if attribute.kind == QiKind::Geo => {
+            quote!(::diff_priv::data_manipulation::geo::GeoPoint::default())
+        }
+        (None, None, Some(min)) if attribute.kind == QiKind::Interval => match qi.field_type {
+            FieldType::Integer => integer_literal(min).into_token_stream(),
+            _ => float_literal(min).into_token_stream(),
+        },
+        _ => quote!(0),
+    }
+}
+
+/// generate the QI out of an expression containing the value of the field,
+/// string values are given as `&str`
+fn qi_expression(qi: &QiField, value: TokenStream2) -> TokenStream2 {
+    let path = quote!(::diff_priv::data_manipulation::anonymizable);
+    let ident = qi.ident;
+    let attribute = &qi.attribute;
+    let weight = Literal::usize_unsuffixed(attribute.weight);
+
+    if let Some(hierarchy) = &attribute.hierarchy {
+        let message = format!("`{{}}` of `{}`", ident);
+        return quote! {{
+            let hierarchy: &::std::sync::Arc<::diff_priv::data_manipulation::hierarchy::Hierarchy> =
+                &#hierarchy;
+            #path::QuasiIdentifierTypes::Hierarchical((
+                hierarchy
+                    .value(#value)
+                    .ok_or_else(|| {
+                        ::diff_priv::error::DiffPrivError::UnknownCategory(format!(#message, #value))
+                    })?,
+                ::std::sync::Arc::clone(hierarchy),
+                #weight,
+            ))
+        }};
+    }
+
+    match attribute.kind {
+        QiKind::Geo => {
+            let geo = quote!(::diff_priv::data_manipulation::geo);
+            let domain = match attribute.max_distance {
+                Some(max_distance) => {
+                    let max_distance = float_literal(max_distance);
+                    quote!(#geo::GeoDomain::new(#max_distance))
+                }
+                None => quote!(#geo::GeoDomain::default()),
+            };
+            let privacy_radius = attribute.privacy_radius.map(|radius| {
+                let radius = float_literal(radius);
+                quote!(.with_privacy_radius(#radius))
+            });
+            let formula = match attribute.equirectangular {
+                true => quote!(.with_formula(#geo::GeoDistance::Equirectangular)),
+                false => quote!(),
+            };
+
+            quote! {
+                #path::QuasiIdentifierTypes::GeoPoint((
+                    #value,
+                    #domain #privacy_radius #formula,
+                    #weight,
+                ))
+            }
+        }
+        QiKind::Temporal => {
+            let temporal = quote!(::diff_priv::data_manipulation::temporal);
+            let timestamp = match qi.field_type {
+                FieldType::Text => quote!(#temporal::parse_datetime(#value)?),
+                FieldType::Time => quote!(#temporal::from_system_time(#value)),
+                _ => value,
+            };
+            let min = temporal_bound(attribute.min, &attribute.min_datetime);
+            let max = temporal_bound(attribute.max, &attribute.max_datetime);
+            let granularity = attribute
+                .granularity
+                .as_ref()
+                .and_then(granularity_variant)
+                .unwrap_or_else(|| Ident::new("Second", ident.span()));
+            let median = match attribute.median {
+                true => quote!(.with_median_centroid()),
+                false => quote!(),
+            };
+
+            quote! {{
+                let domain = #temporal::TemporalDomain::new(
+                    #min,
+                    #max,
+                    #temporal::Granularity::#granularity,
+                )#median;
+                #path::QuasiIdentifierTypes::Temporal((
+                    #timestamp,
+                    domain.granularity,
+                    domain,
+                    #weight,
+                ))
+            }}
+        }
+        QiKind::Interval => {
+            let (value, min, max) = match qi.field_type {
+                FieldType::Integer => (
+                    quote!(#path::QuasiIdentifierType::Integer(#value)),
+                    integer_literal(attribute.min.unwrap()),
+                    integer_literal(attribute.max.unwrap()),
+                ),
+                FieldType::SmallFloat => (
+                    quote!(#path::QuasiIdentifierType::Float(#value as f64)),
+                    float_literal(attribute.min.unwrap()),
+                    float_literal(attribute.max.unwrap()),
+                ),
+                _ => (
+                    quote!(#path::QuasiIdentifierType::Float(#value)),
+                    float_literal(attribute.min.unwrap()),
+                    float_literal(attribute.max.unwrap()),
+                ),
+            };
+            let domain_type = match qi.field_type {
+                FieldType::Integer => quote!(#path::QuasiIdentifierType::Integer),
+                _ => quote!(#path::QuasiIdentifierType::Float),
+            };
+
+            quote! {
+                #path::QuasiIdentifierTypes::Interval((
+                    #value,
+                    #domain_type(#min),
+                    #domain_type(#max),
+                    #weight,
+                ))
+            }
+        }
+        kind => {
+            let variant = match kind {
+                QiKind::Ordinal => quote!(Ordinal),
+                _ => quote!(Nominal),
+            };
+            let (value, max) = match attribute.categories.is_empty() {
+                true => (value, integer_literal(attribute.max.unwrap())),
+                false => {
+                    let categories = &attribute.categories;
+                    let values = (0..categories.len()).map(|index| category_value(kind, index));
+                    let message = format!("`{{}}` of `{}`", ident);
+                    let max = match kind {
+                        QiKind::Ordinal => Literal::i32_unsuffixed(categories.len() as i32),
+                        _ => Literal::i32_unsuffixed(categories.len() as i32 - 1),
+                    };
+                    (
+                        quote! {
+                            match #value {
+                                #(#categories => #values,)*
+                                other => {
+                                    return Err(::diff_priv::error::DiffPrivError::UnknownCategory(
+                                        format!(#message, other),
+                                    ))
+                                }
+                            }
+                        },
+                        max,
+                    )
+                }
+            };
+
+            quote!(#path::QuasiIdentifierTypes::#variant((#value, #max, #weight)))
+        }
+    }
+}
+
+/// generate the statement updating the field with the next QI,
+/// a missing QI sets an optional field to `None`
+fn update_qi(qi: &QiField, name: &Ident) -> TokenStream2 {
+    let path = quote!(::diff_priv::data_manipulation::anonymizable);
+    let ident = qi.ident;
+    let message = format!("Couldn't update `{}` of `{}` with QI's", ident, name);
+
+    // a geo point isn't a single value and is matched directly
+    if qi.attribute.kind == QiKind::Geo {
+        let arms = match qi.optional {
+            true => quote! {
+                Some(#path::QuasiIdentifierTypes::GeoPoint((value, _, _))) => Some(value),
+                Some(#path::QuasiIdentifierTypes::Missing(_)) => None,
+            },
+            false => quote!(Some(#path::QuasiIdentifierTypes::GeoPoint((value, _, _))) => value,),
+        };
+        return quote! {
+            update.#ident = match qi.next() {
+                #arms
+                _ => {
+                    return Err(::diff_priv::error::DiffPrivError::TypeMismatch(
+                        ::std::string::String::from(#message),
+                    ))
+                }
+            };
+        };
+    }
+
+    let (patterns, values): (Vec<TokenStream2>, Vec<TokenStream2>) =
+        match (qi.attribute.categories.is_empty(), qi.field_type) {
+            _ if qi.attribute.hierarchy.is_some() => {
+                let hierarchy = qi.attribute.hierarchy.as_ref().unwrap();
+                (
+                    vec![quote!(#path::QuasiIdentifierType::Integer(value))],
+                    vec![quote!(#hierarchy.label(value).to_string())],
+                )
+            }
+            _ if qi.attribute.kind == QiKind::Temporal => {
+                let temporal = quote!(::diff_priv::data_manipulation::temporal);
+                (
+                    vec![quote!(#path::QuasiIdentifierType::Float(value))],
+                    vec![match qi.field_type {
+                        FieldType::Text => quote!(#temporal::format_datetime(value as i64)),
+                        FieldType::Time => quote!(#temporal::to_system_time(value as i64)),
+                        _ => quote!(value as i64),
+                    }],
+                )
+            }
+            (false, _) => qi
+                .attribute
+                .categories
+                .iter()
+                .enumerate()
+                .map(|(index, category)| {
+                    let value = category_value(qi.attribute.kind, index);
+                    (
+                        quote!(#path::QuasiIdentifierType::Integer(#value)),
+                        quote!(::std::string::String::from(#category)),
+                    )
+                })
+                .unzip(),
+            (true, FieldType::Integer) => (
+                vec![quote!(#path::QuasiIdentifierType::Integer(value))],
+                vec![quote!(value)],
+            ),
+            (true, FieldType::SmallFloat) => (
+                vec![quote!(#path::QuasiIdentifierType::Float(value))],
+                vec![quote!(value as f32)],
+            ),
+            (true, _) => (
+                vec![quote!(#path::QuasiIdentifierType::Float(value))],
+                vec![quote!(value)],
+            ),
+        };
+
+    let arms = match qi.optional {
+        true => quote! {
+            #(Some(#patterns) => Some(#values),)*
+            None => None,
+        },
+        false => quote!(#(Some(#patterns) => #values,)*),
+    };
+
+    quote! {
+        update.#ident = match qi.next().and_then(#path::QuasiIdentifierTypes::try_extract_value) {
+            #arms
+            _ => {
+                return Err(::diff_priv::error::DiffPrivError::TypeMismatch(
+                    ::std::string::String::from(#message),
+                ))
+            }
+        };
+    }
+}
@@ -1,15 +1,59 @@
-use crate::analysis::cluster_analyser::ClusterAnalyser;
-use crate::analysis::disclosure_risk_analyser::DisclosureRiskAnalyser;
-use crate::analysis::mse_analyser::MseAnalyser;
-use crate::analysis::publishing_delay_analyser::PublishingDelayAnalyser;
-use crate::analysis::sse_analyser::SseAnalyser;
+use crate::anonymization::clock::Clock;
+use crate::anonymization::eviction::EvictionPolicy;
+use crate::anonymization::record::QiColumns;
+use crate::anonymization::suppression::SuppressionStrategy;
 use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::data_manipulation::distance::DistanceMetric;
+use uuid::Uuid;
 
-/// This enum encompasses all the different analysers used in the framework
-pub enum Analyser<A: Anonymizable> {
-    Mse(MseAnalyser),
-    Sse(SseAnalyser),
-    PublishingDelay(PublishingDelayAnalyser),
-    ClusterAnalyser(ClusterAnalyser),
-    DisclosureRiskAnalyser(DisclosureRiskAnalyser<A>),
+/// Information about a tuple that is about to be published
+pub struct PublishContext<'a> {
+    pub cluster: Uuid,                            // cluster the tuple belongs to
+    pub metric: &'a dyn DistanceMetric,           // distance used by the anonymizer
+    pub clock: &'a Clock,                         // time of the anonymizer
+    pub suppression: Option<SuppressionStrategy>, // `None` when published with the centroid
+    pub disclosure_risk: f64,                     // published together with the tuple
+    pub original_qi: &'a QiColumns,               // QI's of the original tuple
+    pub published_qi: &'a QiColumns,              // QI's of the published tuple
+}
+
+/// This trait lets you measure the behaviour of the anonymizer, every hook is called
+/// by the anonymizer or its clusters and does nothing by default.
+/// Register your own analysers with `MicroaggAnonymizer::with_analyser`
+pub trait Analyser<A: Anonymizable> {
+    /// key of the report of the analyser
+    fn name(&self) -> &str;
+
+    /// the measurements of the analyser
+    fn report(&self) -> serde_json::Value;
+
+    /// a tuple is published as `published`, which is either microaggregated or suppressed
+    fn on_publish(&mut self, _original: &A, _published: &A, _ctx: &mut PublishContext) {}
+
+    /// a tuple of a cluster that isn't diverse is suppressed, called before it is published.
+    /// Tuples that are held are not suppressed yet
+    fn on_suppress(&mut self, _original: &A, _strategy: SuppressionStrategy) {}
+
+    /// tuples are held by `SuppressionStrategy::Hold` for the first time
+    fn on_hold(&mut self, _count: usize) {}
+
+    fn on_cluster_created(&mut self, _cluster: Uuid) {}
+
+    fn on_cluster_removed(&mut self, _cluster: Uuid) {}
+
+    /// concept drift is detected inside the cluster
+    fn on_drift(&mut self, _cluster: Uuid) {}
+
+    /// the cluster is evicted to stay within the maximum cluster count or memory budget,
+    /// called before its tuples are flushed and it is removed
+    fn on_evict(&mut self, _cluster: Uuid, _policy: EvictionPolicy) {}
+
+    /// the measurements needed to merge the analyser with the same analyser of another shard of a
+    /// `ShardedAnonymizer`, see `merge`. `None` when it can't be merged, the reports of the shards are then listed
+    fn state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// add the `state` of the same analyser of another shard
+    fn merge(&mut self, _state: &serde_json::Value) {}
 }
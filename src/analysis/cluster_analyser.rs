@@ -1,9 +1,16 @@
+use crate::analysis::analyser::Analyser;
+use crate::anonymization::eviction::EvictionPolicy;
+use crate::data_manipulation::anonymizable::Anonymizable;
+use uuid::Uuid;
+
 /// Analyses the behaviour of creating and deleting
 /// the clusters throughout algorithms lifetime
 #[derive(Default)]
 pub struct ClusterAnalyser {
     pub delete_counter: i32,
     pub create_counter: i32,
+    pub drift_counter: i32,
+    pub evict_counter: i32,
 }
 
 impl ClusterAnalyser {
@@ -15,3 +22,46 @@ impl ClusterAnalyser {
         self.delete_counter += 1;
     }
 }
+
+impl<A: Anonymizable> Analyser<A> for ClusterAnalyser {
+    fn name(&self) -> &str {
+        "clusters"
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "created": self.create_counter,
+            "deleted": self.delete_counter,
+            "drifts": self.drift_counter,
+            "evicted": self.evict_counter,
+        })
+    }
+
+    fn on_cluster_created(&mut self, _cluster: Uuid) {
+        self.add_count()
+    }
+
+    fn on_cluster_removed(&mut self, _cluster: Uuid) {
+        self.remove_count()
+    }
+
+    fn on_drift(&mut self, _cluster: Uuid) {
+        self.drift_counter += 1
+    }
+
+    fn on_evict(&mut self, _cluster: Uuid, _policy: EvictionPolicy) {
+        self.evict_counter += 1
+    }
+
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(Analyser::<A>::report(self))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        let count = |key: &str| state[key].as_i64().unwrap_or_default() as i32;
+        self.create_counter += count("created");
+        self.delete_counter += count("deleted");
+        self.drift_counter += count("drifts");
+        self.evict_counter += count("evicted")
+    }
+}
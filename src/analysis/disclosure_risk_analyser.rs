@@ -1,6 +1,10 @@
+use crate::analysis::analyser::{Analyser, PublishContext};
+use crate::anonymization::record::QiColumns;
 use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::data_manipulation::distance::DistanceMetric;
 use std::cmp::Ordering;
 use std::collections::VecDeque;
+use std::marker::PhantomData;
 
 /// Analyses the risk of disclosure of the anonymized
 /// tuple
@@ -12,7 +16,8 @@ where
     pub current_linkage_probability: f64,
     sum_linkage_probability: f64,
     count: i32,
-    buffer: VecDeque<A>,
+    buffer: VecDeque<QiColumns>, // QI's of the recent tuples
+    data: PhantomData<A>,
 }
 
 impl<A> DisclosureRiskAnalyser<A>
@@ -26,21 +31,39 @@ where
             sum_linkage_probability: 0.0,
             count: 0,
             buffer: VecDeque::new(),
+            data: PhantomData,
         }
     }
 
-    pub fn add_data(&mut self, value: A, anonymized_value: &A) {
+    /// add a tuple and its anonymized version, which is linked to the closest
+    /// recent tuples according to the metric
+    pub fn add_data(&mut self, value: A, anonymized_value: &A, metric: &dyn DistanceMetric) {
+        let value = QiColumns::extract(&value);
+        let anonymized_value = QiColumns::new(
+            value.schema().clone(),
+            &anonymized_value.quasi_identifiers(),
+        );
+        self.add_columns(value, &anonymized_value, metric)
+    }
+
+    /// `add_data` with the QI's that are already extracted
+    fn add_columns(
+        &mut self,
+        value: QiColumns,
+        anonymized_value: &QiColumns,
+        metric: &dyn DistanceMetric,
+    ) {
         if self.buffer.len() >= self.buffer_size {
             self.buffer.pop_back();
         }
         self.buffer.push_front(value);
         self.count += 1;
-        self.update_estimation(anonymized_value)
+        self.update_estimation(anonymized_value, metric)
     }
 
-    fn update_estimation(&mut self, anonymized_value: &A) {
+    fn update_estimation(&mut self, anonymized_value: &QiColumns, metric: &dyn DistanceMetric) {
         let newest_tuple = self.buffer.front().unwrap();
-        let mut minimum_distance = newest_tuple.calculate_difference(anonymized_value);
+        let mut minimum_distance = metric.qi_difference(newest_tuple, anonymized_value);
         let mut indices: Vec<usize> = vec![0];
 
         self.buffer
@@ -48,7 +71,7 @@ where
             .skip(1)
             .enumerate()
             .for_each(|(index, unit)| {
-                let difference = unit.calculate_difference(anonymized_value);
+                let difference = metric.qi_difference(unit, anonymized_value);
                 match difference.partial_cmp(&minimum_distance) {
                     Some(Ordering::Less) => {
                         minimum_distance = difference;
@@ -59,12 +82,9 @@ where
                 }
             });
 
-        match indices.contains(&0) {
-            true => {
-                self.sum_linkage_probability += 1.0 / indices.len() as f64;
-                self.current_linkage_probability = self.sum_linkage_probability / self.count as f64
-            }
-            false => {}
+        if indices.contains(&0) {
+            self.sum_linkage_probability += 1.0 / indices.len() as f64;
+            self.current_linkage_probability = self.sum_linkage_probability / self.count as f64
         }
     }
 
@@ -72,3 +92,35 @@ where
         self.sum_linkage_probability / self.count as f64
     }
 }
+
+impl<A> Analyser<A> for DisclosureRiskAnalyser<A>
+where
+    A: Anonymizable,
+{
+    fn name(&self) -> &str {
+        "disclosure_risk"
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.calculate_disclosure_risk())
+    }
+
+    /// the linkage probability is published together with the tuple
+    fn on_publish(&mut self, _original: &A, _published: &A, ctx: &mut PublishContext) {
+        self.add_columns(ctx.original_qi.clone(), ctx.published_qi, ctx.metric);
+        ctx.disclosure_risk = self.current_linkage_probability
+    }
+
+    /// the risk of the merged analysers is the mean over the tuples of every shard
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!([
+            self.count,
+            self.sum_linkage_probability
+        ]))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        self.count += state[0].as_i64().unwrap_or_default() as i32;
+        self.sum_linkage_probability += state[1].as_f64().unwrap_or_default()
+    }
+}
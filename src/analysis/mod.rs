@@ -4,3 +4,4 @@ pub mod disclosure_risk_analyser;
 pub mod mse_analyser;
 pub mod publishing_delay_analyser;
 pub mod sse_analyser;
+pub mod suppression_analyser;
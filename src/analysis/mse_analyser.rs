@@ -1,3 +1,6 @@
+use crate::analysis::analyser::{Analyser, PublishContext};
+use crate::data_manipulation::anonymizable::Anonymizable;
+
 /// Analyses the mean of square errors between the
 /// original data tuple and its anonymized version throughout the
 /// algorithms lifetime
@@ -25,3 +28,26 @@ impl Default for MseAnalyser {
         }
     }
 }
+
+impl<A: Anonymizable> Analyser<A> for MseAnalyser {
+    fn name(&self) -> &str {
+        "mse"
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.calculate_mse())
+    }
+
+    fn on_publish(&mut self, _original: &A, _published: &A, ctx: &mut PublishContext) {
+        self.add_error(ctx.metric.qi_difference(ctx.original_qi, ctx.published_qi))
+    }
+
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!([self.count, self.sum_square_erros]))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        self.count += state[0].as_i64().unwrap_or_default() as i32;
+        self.sum_square_erros += state[1].as_f64().unwrap_or_default()
+    }
+}
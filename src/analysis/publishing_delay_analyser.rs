@@ -1,5 +1,6 @@
+use crate::analysis::analyser::{Analyser, PublishContext};
 use crate::data_manipulation::anonymizable::Anonymizable;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
 /// Analyses the average delay a tuple experiences between being put
 /// inside the algorithm and being published
@@ -10,14 +11,43 @@ pub struct PublishingDelayAnalyser {
 }
 
 impl PublishingDelayAnalyser {
-    pub fn add_delay<A: Anonymizable>(&mut self, value: &A) {
+    pub fn add_delay(&mut self, delay: Duration) {
         self.count += 1;
-        self.sum_delays += SystemTime::now()
-            .duration_since(value.get_timestamp())
-            .unwrap()
+        self.sum_delays += delay
     }
 
     pub fn calculate_average_delay(&self) -> Duration {
         self.sum_delays.div_f64(self.count as f64)
     }
 }
+
+impl<A: Anonymizable> Analyser<A> for PublishingDelayAnalyser {
+    fn name(&self) -> &str {
+        "publishing_delay"
+    }
+
+    /// average delay in nanoseconds
+    fn report(&self) -> serde_json::Value {
+        match self.count {
+            0 => serde_json::Value::Null,
+            _ => serde_json::json!(self.calculate_average_delay().as_nanos() as u64),
+        }
+    }
+
+    /// the delay is measured in the time of the anonymizer, event time or processing time
+    fn on_publish(&mut self, original: &A, _published: &A, ctx: &mut PublishContext) {
+        self.add_delay(ctx.clock.elapsed(original))
+    }
+
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!([
+            self.count,
+            self.sum_delays.as_nanos() as u64
+        ]))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        self.count += state[0].as_i64().unwrap_or_default() as i32;
+        self.sum_delays += Duration::from_nanos(state[1].as_u64().unwrap_or_default())
+    }
+}
@@ -1,3 +1,6 @@
+use crate::analysis::analyser::{Analyser, PublishContext};
+use crate::data_manipulation::anonymizable::Anonymizable;
+
 /// Analyses the overall data loss experienced throughout the
 /// algorithms lifetime
 #[derive(Default)]
@@ -14,3 +17,25 @@ impl SseAnalyser {
         self.sum_info_loss
     }
 }
+
+impl<A: Anonymizable> Analyser<A> for SseAnalyser {
+    fn name(&self) -> &str {
+        "sse"
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.total_info_loss())
+    }
+
+    fn on_publish(&mut self, _original: &A, _published: &A, ctx: &mut PublishContext) {
+        self.add_info_loss(ctx.metric.qi_difference(ctx.original_qi, ctx.published_qi))
+    }
+
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!(self.sum_info_loss))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        self.add_info_loss(state.as_f64().unwrap_or_default())
+    }
+}
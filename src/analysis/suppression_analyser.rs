@@ -0,0 +1,77 @@
+use crate::analysis::analyser::{Analyser, PublishContext};
+use crate::anonymization::suppression::SuppressionStrategy;
+use crate::data_manipulation::anonymizable::Anonymizable;
+use std::collections::HashMap;
+
+/// Analyses how many tuples were published with the centroid of their cluster
+/// and how many were suppressed by every `SuppressionStrategy`
+#[derive(Default)]
+pub struct SuppressionAnalyser {
+    pub published: usize,
+    pub suppressed: HashMap<SuppressionStrategy, usize>, // held tuples that are dropped count as `Drop`
+    pub held: usize, // tuples that have been held at least once by `SuppressionStrategy::Hold`
+}
+
+impl SuppressionAnalyser {
+    pub fn add_published(&mut self) {
+        self.published += 1
+    }
+
+    pub fn add_suppressed(&mut self, strategy: SuppressionStrategy) {
+        *self.suppressed.entry(strategy).or_insert(0) += 1
+    }
+
+    pub fn add_held(&mut self, count: usize) {
+        self.held += count
+    }
+
+    /// the amount of tuples suppressed by any strategy
+    pub fn total_suppressed(&self) -> usize {
+        self.suppressed.values().sum()
+    }
+}
+
+impl<A: Anonymizable> Analyser<A> for SuppressionAnalyser {
+    fn name(&self) -> &str {
+        "suppression"
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "published": self.published,
+            "suppressed": self.suppressed,
+            "held": self.held,
+        })
+    }
+
+    fn on_publish(&mut self, _original: &A, _published: &A, ctx: &mut PublishContext) {
+        if ctx.suppression.is_none() {
+            self.add_published()
+        }
+    }
+
+    fn on_suppress(&mut self, _original: &A, strategy: SuppressionStrategy) {
+        self.add_suppressed(strategy)
+    }
+
+    fn on_hold(&mut self, count: usize) {
+        self.add_held(count)
+    }
+
+    fn state(&self) -> Option<serde_json::Value> {
+        Some(Analyser::<A>::report(self))
+    }
+
+    fn merge(&mut self, state: &serde_json::Value) {
+        let count = |value: &serde_json::Value| value.as_u64().unwrap_or_default() as usize;
+        self.published += count(&state["published"]);
+        self.held += count(&state["held"]);
+        if let Some(suppressed) = state["suppressed"].as_object() {
+            suppressed.iter().for_each(|(strategy, amount)| {
+                if let Ok(strategy) = serde_json::from_value(serde_json::json!(strategy)) {
+                    *self.suppressed.entry(strategy).or_insert(0) += count(amount)
+                }
+            })
+        }
+    }
+}
@@ -1,22 +1,34 @@
-use crate::data_manipulation::aggregation::AggregateType;
-use crate::data_manipulation::aggregation::AggregateType::{Mean, Mode};
-use crate::data_manipulation::anonymizable::{Anonymizable, QuasiIdentifierTypes};
+use crate::anonymization::record::{QiColumns, Record};
+use crate::data_manipulation::aggregation::AggregateType::{
+    LowestCommonAncestor, Mean, Median, Mode, SphericalCentroid,
+};
+use crate::data_manipulation::aggregation::{AggregateType, Aggregator, RunningAggregate};
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, MissingValuePolicy, QuasiIdentifierTypes,
+};
+use std::borrow::Cow;
 use std::collections::VecDeque;
 
 /// used to keep note which data tuple has already been published or not
-pub type DataContainer<M> = (bool, M);
+pub type DataContainer<M> = (bool, Record<M>);
 
 /// Buffer used for containing individual data tuples and generalized centroid.
 /// Also used for checking concept drift in a cluster by comparing 2 buffers with
 /// each other.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Buffer<M>
 where
     M: Anonymizable,
 {
     pub max_buffer_size: usize,
-    pub centroid: M,
+    pub centroid: M, // changed by `add_tuple`, otherwise `centroid_qi` has to be updated as well
     pub buffer: VecDeque<DataContainer<M>>,
+    #[serde(skip)]
+    pub(crate) centroid_qi: Option<QiColumns>, // QI's of the centroid, extracted when it changes
+    #[serde(skip)]
+    pub(crate) aggregates: Vec<RunningAggregate>, // running aggregation of every QI of the buffered tuples
+    #[serde(skip)]
+    pub(crate) aggregated: usize, // amount of buffered tuples in the running aggregates
 }
 
 impl<M> Buffer<M>
@@ -25,54 +37,194 @@ where
 {
     /// add data tuple, updated centroid and return copy of
     /// centroid
-    pub fn add_tuple(&mut self, value: M) -> M {
+    pub fn add_tuple(&mut self, value: impl Into<Record<M>>) -> M {
+        let value = value.into();
+        // the centroid is based on the first tuple of the buffer, a default
+        // `Anonymizable` does not always know how to represent its QI's
+        if self.buffer.is_empty() {
+            self.centroid = value.value.clone();
+            self.centroid_qi = Some(value.qi.clone());
+        }
         self.buffer.push_back((false, value));
 
-        self.update_centroid();
+        match self.aggregate_running() {
+            true => self.update_running_centroid(),
+            false => self.update_centroid(),
+        }
 
         self.centroid.clone()
     }
 
+    /// the QI's of the centroid, extracted again when they are not known
+    pub(crate) fn centroid_qi(&self) -> Cow<'_, QiColumns> {
+        match &self.centroid_qi {
+            Some(centroid_qi) => Cow::Borrowed(centroid_qi),
+            None => Cow::Owned(QiColumns::extract(&self.centroid)),
+        }
+    }
+
+    /// replace the centroid by one with the QI's and extract them
+    /// in the schema of the buffered tuples
+    fn update_centroid_qi(&mut self, qi: Vec<QuasiIdentifierTypes>) {
+        self.centroid = self.centroid.update_quasi_identifiers(qi);
+        self.centroid_qi = Some(match self.buffer.back() {
+            Some((_, record)) => QiColumns::new(
+                record.qi.schema().clone(),
+                &self.centroid.quasi_identifiers(),
+            ),
+            None => QiColumns::extract(&self.centroid),
+        });
+    }
+
+    /// whether the centroid can be kept up to date with running aggregates,
+    /// imputed values change with the centroid and need all the values again
+    fn aggregate_running(&self) -> bool {
+        self.centroid.missing_value_policy() != MissingValuePolicy::Impute
+            && self
+                .centroid
+                .aggregators()
+                .into_iter()
+                .all(RunningAggregate::supports)
+    }
+
+    /// add the newest tuple to the running aggregates and update the centroid with them.
+    /// The aggregates are built again when the buffer has been changed in another way
+    fn update_running_centroid(&mut self) {
+        let policy = self.centroid.missing_value_policy();
+        let rounding = self.centroid.rounding();
+        let centroid_qi = self.centroid_qi().quasi_identifiers();
+
+        let start = match self.aggregated + 1 == self.buffer.len()
+            && self.aggregates.len() == centroid_qi.len()
+        {
+            true => self.aggregated,
+            false => {
+                let aggregators = self.centroid.aggregators();
+                self.aggregates = (0..centroid_qi.len())
+                    .map(|index| {
+                        RunningAggregate::new(aggregators.get(index).copied().unwrap_or_default())
+                    })
+                    .collect();
+                0
+            }
+        };
+
+        self.buffer.range(start..).for_each(|(_, x)| {
+            let weight = x.weight;
+            x.qi.quasi_identifiers()
+                .into_iter()
+                .zip(self.aggregates.iter_mut())
+                .filter(|(qi, _)| !(qi.is_missing() && policy == MissingValuePolicy::Ignore))
+                .for_each(|(qi, aggregate)| aggregate.add(qi, weight))
+        });
+        self.aggregated = self.buffer.len();
+
+        // keep the current centroid value if there is nothing to aggregate
+        let new_qi: Vec<QuasiIdentifierTypes> = centroid_qi
+            .into_iter()
+            .zip(self.aggregates.iter())
+            .map(|(centroid, aggregate)| aggregate.aggregate(rounding).unwrap_or(centroid))
+            .collect();
+
+        self.update_centroid_qi(new_qi);
+    }
+
     /// aggregate all the QI's in the buffer
     /// into a centroid
     /// TODO: maybe use parallel iterator at some point?
     pub fn update_centroid(&mut self) {
-        let mut qi_list: Vec<AggregateType> = Vec::new();
+        self.aggregates = Vec::new();
+        self.aggregated = 0;
+        let policy = self.centroid.missing_value_policy();
+        let aggregators = self.centroid.aggregators();
+        let rounding = self.centroid.rounding();
+        let centroid_qi = self.centroid_qi().quasi_identifiers();
+        let mut qi_list: Vec<Vec<(QuasiIdentifierTypes, f64)>> = Vec::new();
         self.buffer.iter().for_each(|(_, x)| {
-            x.quasi_identifiers()
+            let weight = x.weight;
+            x.qi.quasi_identifiers()
                 .into_iter()
                 .enumerate()
                 .for_each(|(index, qi)| match qi_list.get_mut(index) {
-                    None => {
-                        let aggregate_type = match qi {
-                            QuasiIdentifierTypes::Interval(qi) => {
-                                Mean(vec![QuasiIdentifierTypes::Interval(qi)])
-                            }
-                            QuasiIdentifierTypes::Ordinal(qi) => {
-                                Mode(vec![QuasiIdentifierTypes::Ordinal(qi)])
-                            }
-                            QuasiIdentifierTypes::Nominal(qi) => {
-                                Mode(vec![QuasiIdentifierTypes::Nominal(qi)])
-                            }
-                        };
-                        qi_list.insert(index, aggregate_type)
-                    }
-                    Some(aggregate_type) => match aggregate_type {
-                        Mean(list) => list.push(qi),
-                        Mode(list) => list.push(qi),
-                    },
+                    None => qi_list.insert(index, vec![(qi, weight)]),
+                    Some(list) => list.push((qi, weight)),
                 })
         });
 
-        let new_qi: Vec<QuasiIdentifierTypes> =
-            qi_list.into_iter().map(|x| x.aggregate()).collect();
+        let new_qi: Vec<QuasiIdentifierTypes> = qi_list
+            .into_iter()
+            .zip(centroid_qi)
+            .enumerate()
+            .map(|(index, (list, centroid))| {
+                let (list, weights): (Vec<QuasiIdentifierTypes>, Vec<f64>) = list
+                    .into_iter()
+                    .filter_map(|(qi, weight)| match (qi, policy) {
+                        (QuasiIdentifierTypes::Missing(_), MissingValuePolicy::Ignore) => None,
+                        (QuasiIdentifierTypes::Missing(_), MissingValuePolicy::Impute) => {
+                            match centroid.is_missing() {
+                                true => None,
+                                false => Some((centroid.clone(), weight)),
+                            }
+                        }
+                        (qi, _) => Some((qi, weight)),
+                    })
+                    .unzip();
+
+                // keep the current centroid value if there is nothing left to aggregate
+                let aggregator = aggregators.get(index).copied().unwrap_or_default();
+                match Self::aggregate_type(list, weights, aggregator) {
+                    Some(aggregate_type) => aggregate_type.aggregate_with(rounding),
+                    None => centroid,
+                }
+            })
+            .collect();
+
+        self.update_centroid_qi(new_qi);
+    }
+
+    /// use the aggregator of the QI, or choose the aggregation based on
+    /// the data category of the QI's for `Aggregator::Default`
+    fn aggregate_type(
+        list: Vec<QuasiIdentifierTypes>,
+        weights: Vec<f64>,
+        aggregator: Aggregator,
+    ) -> Option<AggregateType> {
+        let qi = list.iter().find(|qi| !qi.is_missing()).or(list.first())?;
+        let qi = match qi {
+            QuasiIdentifierTypes::Missing(template) => template.as_ref(),
+            qi => qi,
+        };
+
+        if aggregator != Aggregator::Default {
+            return aggregator.aggregate_type(list, weights);
+        }
+
+        let aggregate_type = match qi {
+            QuasiIdentifierTypes::Interval(_) => Mean(list),
+            QuasiIdentifierTypes::Temporal((_, _, domain, _)) => match domain.median_centroid() {
+                true => Median(list),
+                false => Mean(list),
+            },
+            QuasiIdentifierTypes::GeoPoint(_) => SphericalCentroid(list),
+            QuasiIdentifierTypes::Ordinal(_) => Mode(list),
+            QuasiIdentifierTypes::Nominal(_) => Mode(list),
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, _)) => {
+                match hierarchy.ancestor_centroid() {
+                    true => LowestCommonAncestor(list),
+                    false => Mode(list),
+                }
+            }
+            QuasiIdentifierTypes::Missing(_) => panic!("Nested missing QI found"),
+        };
 
-        self.centroid = self.centroid.update_quasi_identifiers(new_qi);
+        Some(aggregate_type)
     }
 
     /// empty buffer after null hypothesis
     pub fn reset(&mut self) {
         self.buffer = VecDeque::new();
+        self.aggregates = Vec::new();
+        self.aggregated = 0;
     }
 
     /// used for checking if the buffer needs to be emptied
@@ -90,6 +242,9 @@ where
             max_buffer_size: 0,
             centroid: Default::default(),
             buffer: VecDeque::new(),
+            centroid_qi: None,
+            aggregates: Vec::new(),
+            aggregated: 0,
         }
     }
 }
@@ -97,7 +252,12 @@ where
 #[cfg(test)]
 mod tests {
     use crate::anonymization::buffer::Buffer;
+    use crate::data_manipulation::aggregation::Aggregator;
+    use crate::data_manipulation::anonymizable::Anonymizable;
+    use crate::data_manipulation::geo::GeoPoint;
     use crate::data_manipulation::mueller::MuellerStream;
+    use serde::Serialize;
+    use std::time::{Duration, SystemTime};
 
     #[test]
     fn update_centroid() {
@@ -119,13 +279,16 @@ mod tests {
             ..MuellerStream::default()
         };
 
+        // the centroid is based on the first tuple added to the buffer
         let centroid = MuellerStream {
             age: Some(40),
             gender: Some("female".to_string()),
+            time_generated: mueller1.time_generated,
             ..MuellerStream::default()
         };
 
         let mut buffer: Buffer<MuellerStream> = Buffer::default();
+
         buffer.add_tuple(mueller1);
         buffer.add_tuple(mueller2);
         buffer.add_tuple(mueller3);
@@ -133,6 +296,42 @@ mod tests {
         assert_eq!(buffer.centroid, centroid)
     }
 
+    #[test]
+    fn update_centroid_missing_values() {
+        let mueller1 = MuellerStream {
+            age: None,
+            gender: None,
+            ..MuellerStream::default()
+        };
+
+        let mueller2 = MuellerStream {
+            age: Some(40),
+            gender: None,
+            ..MuellerStream::default()
+        };
+
+        let mueller3 = MuellerStream {
+            age: Some(50),
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        };
+
+        let mut buffer: Buffer<MuellerStream> = Buffer::default();
+
+        buffer.add_tuple(mueller1);
+        assert_eq!(
+            (buffer.centroid.age, buffer.centroid.gender.clone()),
+            (None, None)
+        );
+
+        buffer.add_tuple(mueller2);
+        buffer.add_tuple(mueller3);
+
+        // missing values are left out of the aggregation
+        assert_eq!(buffer.centroid.age, Some(45));
+        assert_eq!(buffer.centroid.gender, Some("male".to_string()))
+    }
+
     #[test]
     fn is_full() {
         let mut buffer: Buffer<MuellerStream> = Buffer {
@@ -164,4 +363,154 @@ mod tests {
 
         assert!(buffer.is_full())
     }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    #[rounding(nearest)]
+    struct Household {
+        #[qi(interval, min = 0, max = 1000000, median)]
+        capital_gain: i32,
+        #[qi(interval, min = 0, max = 100, trimmed_mean = 0.25)]
+        age: i32,
+        #[qi(interval, min = 0, max = 100, weighted_mean)]
+        hours_per_week: i32,
+        #[qi(ordinal, categories = ["low", "middle", "high"], median)]
+        income: String,
+        #[record_weight]
+        survey_weight: f64,
+        #[sensitive]
+        occupation: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Household {
+        fn default() -> Self {
+            Self {
+                capital_gain: 0,
+                age: 0,
+                hours_per_week: 0,
+                income: "low".to_string(),
+                survey_weight: 1.0,
+                occupation: "sales".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn derive_aggregators() {
+        let household =
+            |capital_gain, age, hours_per_week, income: &str, survey_weight| Household {
+                capital_gain,
+                age,
+                hours_per_week,
+                income: income.to_string(),
+                survey_weight,
+                occupation: "sales".to_string(),
+                time_generated: SystemTime::now(),
+            };
+        let households = vec![
+            household(0, 20, 40, "low", 1.0),
+            household(0, 30, 40, "low", 1.0),
+            household(10, 33, 40, "high", 1.0),
+            household(999999, 90, 15, "high", 3.0),
+        ];
+
+        assert_eq!(
+            households[0].aggregators(),
+            vec![
+                Aggregator::Median,
+                Aggregator::TrimmedMean(0.25),
+                Aggregator::WeightedMean,
+                Aggregator::Median
+            ]
+        );
+        assert_eq!(households[3].record_weight(), 3.0);
+
+        let mut buffer: Buffer<Household> = Buffer::default();
+        households
+            .into_iter()
+            .for_each(|household| drop(buffer.add_tuple(household)));
+
+        assert_eq!(buffer.centroid.capital_gain, 0);
+        // 30 and 33 are left, their mean of 31.5 is rounded to the nearest
+        assert_eq!(buffer.centroid.age, 32);
+        // (3 * 40 + 3 * 15) / 6
+        assert_eq!(buffer.centroid.hours_per_week, 28);
+        assert_eq!(buffer.centroid.income, "low")
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    #[missing_values(own_category)]
+    struct Reading {
+        #[qi(interval, min = 0, max = 100)]
+        temperature: Option<f64>,
+        #[qi(interval, min = 0, max = 1000, median)]
+        pressure: Option<i32>,
+        #[qi(interval, min = 0, max = 100, weighted_mean)]
+        humidity: i32,
+        #[qi(interval, min = 0, max = 200, trimmed_mean = 0.2)]
+        wind_speed: Option<i32>,
+        #[qi(ordinal, categories = ["low", "middle", "high"], median)]
+        level: String,
+        #[qi(nominal, categories = ["north", "east", "south", "west"])]
+        wind: Option<String>,
+        #[qi(temporal, min = 1655683200, max = 1656288000)]
+        measured: SystemTime,
+        #[qi(geo)]
+        station: GeoPoint,
+        #[record_weight]
+        weight: f64,
+        #[sensitive]
+        alarm: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Reading {
+        fn default() -> Self {
+            Self {
+                temperature: None,
+                pressure: None,
+                humidity: 0,
+                wind_speed: None,
+                level: "low".to_string(),
+                wind: None,
+                measured: SystemTime::UNIX_EPOCH + Duration::from_secs(1655683200),
+                station: GeoPoint::default(),
+                weight: 1.0,
+                alarm: "none".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn running_aggregates() {
+        let levels = ["low", "middle", "high"];
+        let winds = ["north", "east", "south", "west"];
+        let mut buffer: Buffer<Reading> = Buffer::default();
+
+        (0..40).for_each(|i: i32| {
+            let reading = Reading {
+                temperature: (i % 5 != 0).then_some((i * 37 % 100) as f64 / 3.0),
+                pressure: (i % 3 != 0).then_some(i * 91 % 1000),
+                humidity: i * 13 % 100,
+                wind_speed: (i % 7 != 0).then_some(i * 53 % 200),
+                level: levels[(i * 7 % 3) as usize].to_string(),
+                wind: (i % 2 == 0).then(|| winds[(i * 5 % 4) as usize].to_string()),
+                measured: SystemTime::UNIX_EPOCH
+                    + Duration::from_secs(1655683200 + (i * 7919 % 86400) as u64),
+                station: GeoPoint::new((i * 17 % 90) as f64, (i * 29 % 180) as f64),
+                weight: (i % 4) as f64,
+                ..Reading::default()
+            };
+            buffer.add_tuple(reading);
+
+            // the running aggregates give the same centroid as aggregating all tuples again
+            let mut aggregated = buffer.clone();
+            aggregated.update_centroid();
+            assert_eq!(buffer.centroid, aggregated.centroid);
+        });
+    }
 }
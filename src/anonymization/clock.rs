@@ -0,0 +1,95 @@
+use crate::data_manipulation::anonymizable::Anonymizable;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Which time the cluster life time delta and the publishing delay are measured in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeMode {
+    /// the wall clock and the time tuples entered the algorithm
+    #[default]
+    ProcessingTime,
+    /// the `event_time` of the tuples, replays of historical data behave like the original stream
+    EventTime,
+}
+
+/// The current time of the anonymizer, shared with its clusters. In `TimeMode::EventTime`
+/// this is the latest event time seen, so out of order tuples don't move the clock back
+#[derive(Debug, Default)]
+pub struct Clock {
+    mode: TimeMode,
+    watermark: AtomicU64, // latest event time in nanoseconds since the unix epoch
+}
+
+impl Clock {
+    pub fn new(mode: TimeMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> TimeMode {
+        self.mode
+    }
+
+    /// move the clock forward to the event time of the tuple
+    pub fn observe<A: Anonymizable>(&self, value: &A) {
+        if self.mode == TimeMode::EventTime {
+            self.watermark
+                .fetch_max(to_nanos(value.event_time()) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// latest event time seen in nanoseconds since the unix epoch
+    pub fn watermark(&self) -> u64 {
+        self.watermark.load(Ordering::Relaxed)
+    }
+
+    /// move the watermark forward, used when the anonymizer is restored from a snapshot
+    pub fn advance(&self, watermark: u64) {
+        self.watermark.fetch_max(watermark, Ordering::Relaxed);
+    }
+
+    /// move the clock to the given time without a tuple, e.g. on a quiet stream.
+    /// The wall clock of processing time moves by itself
+    pub fn tick(&self, now: SystemTime) {
+        if self.mode == TimeMode::EventTime {
+            self.advance(to_nanos(now) as u64)
+        }
+    }
+
+    /// nanoseconds since the unix epoch
+    pub fn now(&self) -> u128 {
+        match self.mode {
+            TimeMode::ProcessingTime => to_nanos(SystemTime::now()),
+            TimeMode::EventTime => self.watermark.load(Ordering::Relaxed) as u128,
+        }
+    }
+
+    pub fn system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.now() as u64)
+    }
+
+    /// the time the tuple is measured from, its arrival time or its event time
+    pub fn timestamp<A: Anonymizable>(&self, value: &A) -> SystemTime {
+        match self.mode {
+            TimeMode::ProcessingTime => value.get_timestamp(),
+            TimeMode::EventTime => value.event_time(),
+        }
+    }
+
+    /// time between the timestamp of the tuple and now, zero for tuples from the future
+    pub fn elapsed<A: Anonymizable>(&self, value: &A) -> Duration {
+        self.system_time()
+            .duration_since(self.timestamp(value))
+            .unwrap_or_default()
+    }
+}
+
+/// nanoseconds since the unix epoch, zero for times before it
+fn to_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
@@ -1,15 +1,25 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use itertools::Itertools;
-use uuid::Uuid;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use uuid::{Builder, Uuid};
 
-use crate::analysis::analyser::Analyser;
+use crate::analysis::analyser::{Analyser, PublishContext};
 use crate::anonymization::buffer::Buffer;
+use crate::anonymization::clock::Clock;
+use crate::anonymization::record::{QiColumns, Record};
+use crate::anonymization::snapshot::sensitive_frequencies;
+use crate::anonymization::suppression::SuppressionStrategy;
 use crate::data_manipulation::anonymizable::{
-    Anonymizable, QuasiIdentifierType, QuasiIdentifierTypes, SensitiveAttribute,
+    Anonymizable, MissingValuePolicy, QuasiIdentifierType, QuasiIdentifierTypes, SensitiveAttribute,
 };
+use crate::data_manipulation::distance::{DistanceMetric, Gower};
+use crate::data_manipulation::geo::GeoPoint;
 use crate::noise::noiser::Noiser;
+use crate::publishing::generalized::{GeneralizedQi, GeneralizedRecord, OutputMode};
 use crate::publishing::publisher::Publisher;
 
 /// Critical Values of Two-Sample Kolmogorov-Smirnorv Test Statistic
@@ -24,6 +34,91 @@ const KS_CONFIDENDE_TABLE: &[f64] = &[
 ];
 const KS_CRITICAL_VALUE: f64 = KS_CONFIDENDE_TABLE[0];
 
+/// frequencies of the values of every sensitive attribute,
+/// by the position of the attribute in `sensitive_values()`
+pub type SensitiveFrequencies = HashMap<usize, HashMap<SensitiveAttribute, i32>>;
+
+/// count the sensitive values of a tuple
+pub fn update_sensitive_frequencies<A: Anonymizable>(
+    frequencies: &mut SensitiveFrequencies,
+    value: &A,
+) {
+    value
+        .sensitive_values()
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, sensitive_attribute)| {
+            *frequencies
+                .entry(index)
+                .or_default()
+                .entry(sensitive_attribute)
+                .or_insert(0) += 1
+        })
+}
+
+/// Earth Mover's Distance between the distribution of a sensitive attribute inside a cluster
+/// and its global distribution. Integers and floats use the ordered distance, where moving
+/// mass between neighbouring values costs 1/(m-1) for m values. Strings use the equal distance,
+/// where all different values are 1 apart
+pub fn earth_movers_distance(
+    cluster: &HashMap<SensitiveAttribute, i32>,
+    global: &HashMap<SensitiveAttribute, i32>,
+) -> f64 {
+    let cluster_total: i32 = cluster.values().sum();
+    let global_total: i32 = global.values().sum();
+    if cluster_total == 0 || global_total == 0 {
+        return 0.0;
+    }
+
+    let mut values: Vec<&SensitiveAttribute> = global
+        .keys()
+        .chain(cluster.keys().filter(|value| !global.contains_key(value)))
+        .collect();
+    let difference = |value: &SensitiveAttribute| {
+        *cluster.get(value).unwrap_or(&0) as f64 / cluster_total as f64
+            - *global.get(value).unwrap_or(&0) as f64 / global_total as f64
+    };
+
+    match values.iter().all(|value| value.rank().is_some()) {
+        true => {
+            if values.len() < 2 {
+                return 0.0;
+            }
+            values.sort_by_key(|value| value.rank());
+            let mut cumulative = 0.0;
+            values
+                .iter()
+                .map(|value| {
+                    cumulative += difference(value);
+                    f64::abs(cumulative)
+                })
+                .sum::<f64>()
+                / (values.len() - 1) as f64
+        }
+        false => {
+            values
+                .iter()
+                .map(|value| f64::abs(difference(value)))
+                .sum::<f64>()
+                / 2.0
+        }
+    }
+}
+
+/// How the unpublished tuples of a cluster are handled
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Flush {
+    /// publish the tuples while the cluster keeps growing
+    Publish,
+    /// the cluster is removed, held tuples are dropped
+    Release,
+    /// the cluster is evicted before it is k-anonymous, every tuple is suppressed
+    Suppress,
+}
+
+/// The clusters are part of a snapshot of the anonymizer, the metric and the clock
+/// are shared with the anonymizer and set again when it is restored
+#[derive(Serialize, Deserialize)]
 pub struct Cluster<A, N>
 where
     A: Anonymizable,
@@ -33,16 +128,27 @@ where
     pub k: usize,             // desired k level of cluster
     pub l: usize,             // desired l diversity level of cluster
     pub c: i32,               // desired recursive (l,c)-diversity
+    pub t: Option<f64>,       // desired t-closeness, not checked when None
     pub centroid: A,          // centroid of the cluster
     pub w_current: Buffer<A>, // current buffer of cluster
     pub w_prev: Buffer<A>,    // previous current buffer of cluster
     pub exit_time: f64,       // used to check what the cluster activity is
     pub sse: f64,             // sum of squared error of the cluster
     pub categorical_freq: HashMap<usize, HashMap<i32, i32>>, // used for checking categorical frequency for l-diversity
-    pub sensitive_freq: HashMap<SensitiveAttribute, i32>, // used for checking sensitive attribute frequency for l-diversity
+    #[serde(with = "sensitive_frequencies")]
+    pub sensitive_freq: SensitiveFrequencies, // used for checking the frequency of every sensitive attribute for l-diversity
     pub complete_buffer_amount: usize, // the count of all added tuples to the cluster, used for max_k calculations
     pub last_arrival: u128,            // last arrival of tuple into the cluster
+    pub created: u128, // time the cluster was created, used by `EvictionPolicy::Oldest`
     pub noiser: N,
+    #[serde(skip, default = "default_metric")]
+    pub metric: Arc<dyn DistanceMetric>, // distance used for concept drift and the analysers
+    pub output_mode: OutputMode,
+    pub suppression_strategy: SuppressionStrategy, // used when l-diversity or t-closeness is not met
+    pub held: usize, // amount of unpublished tuples held by `SuppressionStrategy::Hold`
+    #[serde(skip)]
+    pub clock: Arc<Clock>, // time of the anonymizer, used for the life time of the cluster
+    pub rng: ChaCha12Rng, // samples the suppressed tuples
 }
 
 impl<A, N> Cluster<A, N>
@@ -68,158 +174,339 @@ where
         }
     }
 
+    /// derive the uuid, the suppression samples and the noise of the
+    /// cluster from the seed so that they can be reproduced
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.noiser.reseed(self.rng.next_u64());
+        self.uuid = Builder::from_random_bytes(self.rng.gen()).into_uuid();
+        self
+    }
+
     // add tuple to cluster
     // 4 possible outcomes
     // 1. update inner state with tuple
     // 2. update inner state and publishing tuple(s)
     // 3. concept drift detected create new cluster
     // 4. close cluster if |C| > KMax
-    /// add tuple to cluster, replace w_prev with w_current
+    /// add tuple to cluster, move w_prev to the state of w_current
     /// and update centroid
-    pub fn add_tuple(&mut self, value: A) {
+    pub fn add_tuple(&mut self, value: impl Into<Record<A>>) {
+        let value = value.into();
         self.update_frequencies(&value);
-        let temp_buffer = self.w_current.clone();
+        self.advance_previous();
         let new_centroid = self.w_current.add_tuple(value);
         self.centroid = new_centroid;
-        self.w_prev = temp_buffer;
         self.complete_buffer_amount += 1;
-        self.last_arrival = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
+        self.last_arrival = self.clock.now()
+    }
+
+    /// bring w_prev to the state of w_current before the next tuple is added. Only the
+    /// latest tuple of w_current is copied when w_prev is one tuple behind, the buffer is
+    /// copied as a whole after it has been emptied or changed in another way
+    fn advance_previous(&mut self) {
+        match self.w_prev.buffer.len() + 1 == self.w_current.buffer.len() {
+            true => {
+                let latest = self.w_current.buffer.back().cloned().unwrap();
+                self.w_prev.buffer.push_back(latest)
+            }
+            false => self.w_prev.buffer = self.w_current.buffer.clone(),
+        }
+        self.w_prev.centroid = self.w_current.centroid.clone();
+        self.w_prev.centroid_qi = self.w_current.centroid_qi.clone();
+        self.w_prev.max_buffer_size = self.w_current.max_buffer_size;
     }
 
     /// update all the hashmaps containing frequencies of values
-    fn update_frequencies(&mut self, value: &A) {
-        self.update_categorical_frequency(value);
-        self.update_sensitive_frequency(value);
+    fn update_frequencies(&mut self, value: &Record<A>) {
+        self.update_categorical_frequency(&value.qi);
+        self.update_sensitive_frequency(&value.value);
     }
 
-    /// check if the cluster satisfies (c,l)-diversity
-    /// return false or true depending on if the most common value appears to often
-    /// or if the least common value appears to infrequent
+    /// the QI's of the centroid, which is the centroid of the current buffer
+    pub(crate) fn centroid_qi(&self) -> std::borrow::Cow<'_, QiColumns> {
+        self.w_current.centroid_qi()
+    }
+
+    /// check if the cluster satisfies (c,l)-diversity for every sensitive attribute
     fn check_l_recursive_diversity(&self) -> bool {
         match self.k <= self.w_current.buffer.len() {
-            true => {
-                let sorted_sensitive_frequency: Vec<i32> =
-                    self.sensitive_freq.values().copied().sorted().collect();
-                let r1 = sorted_sensitive_frequency[0];
+            true => self
+                .sensitive_freq
+                .values()
+                .all(|frequencies| self.is_diverse(frequencies)),
+            false => false,
+        }
+    }
 
-                let rl_to_rm: i32 = sorted_sensitive_frequency[1..].iter().sum();
+    /// return false or true depending on if the sensitive attribute has less than l
+    /// distinct values or if the most common value r1 appears to often compared to
+    /// the l-th up to the least common value, r1 < c * (r_l + ... + r_m)
+    fn is_diverse(&self, frequencies: &HashMap<SensitiveAttribute, i32>) -> bool {
+        if frequencies.len() < self.l {
+            return false;
+        }
 
-                r1 < self.c * rl_to_rm
-            }
-            false => false,
+        let sorted_sensitive_frequency: Vec<i32> = frequencies
+            .values()
+            .copied()
+            .sorted_by(|a, b| b.cmp(a))
+            .collect();
+        let r1 = sorted_sensitive_frequency[0];
+
+        let rl_to_rm: i32 = sorted_sensitive_frequency[self.l.saturating_sub(1)..]
+            .iter()
+            .sum();
+
+        r1 < self.c * rl_to_rm
+    }
+
+    /// check if the distribution of every sensitive attribute in the cluster is at most
+    /// t away from its global distribution
+    fn check_t_closeness(&self, global_sensitive_freq: &SensitiveFrequencies) -> bool {
+        match self.t {
+            None => true,
+            Some(t) => self.sensitive_freq.iter().all(|(index, frequencies)| {
+                match global_sensitive_freq.get(index) {
+                    Some(global) => earth_movers_distance(frequencies, global) <= t,
+                    None => true,
+                }
+            }),
         }
     }
 
-    /// publish last added data tuple to the publisher and update published status
-    pub fn publish<P: Publisher>(&mut self, publisher: &mut P, analysers: &mut [Analyser<A>]) {
+    /// publish last added data tuple to the publisher and update published status.
+    /// With `SuppressionStrategy::Hold` the held tuples are published as well
+    pub fn publish<P: Publisher>(
+        &mut self,
+        publisher: &mut P,
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+    ) {
+        if self.suppression_strategy == SuppressionStrategy::Hold {
+            return self.publish_all(publisher, analysers, global_sensitive_freq);
+        }
+
         // we can use unwrap here because there is always a value in the cluster when
         // data in a cluster is published
         let (_, original) = self.w_current.buffer.back().cloned().unwrap();
 
-        self.publish_data(&original, publisher, analysers);
-        let (published, _) = self.w_current.buffer.back_mut().unwrap();
-        *published = true
+        let published = self.publish_data(
+            &original,
+            publisher,
+            analysers,
+            global_sensitive_freq,
+            Flush::Publish,
+        );
+        let (status, _) = self.w_current.buffer.back_mut().unwrap();
+        *status = published
+    }
+
+    /// publishing all the tuples in the buffer that have still not been published,
+    /// tuples held by `SuppressionStrategy::Hold` stay in the buffer
+    pub fn publish_all<P: Publisher>(
+        &mut self,
+        publisher: &mut P,
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+    ) {
+        self.publish_unpublished(publisher, analysers, global_sensitive_freq, Flush::Publish)
     }
 
     /// publishing all the tuples in the buffer that have still not been published
-    pub fn publish_all<P: Publisher>(&mut self, publisher: &mut P, analysers: &mut [Analyser<A>]) {
-        let publish: Vec<A> = self
+    /// before the cluster is removed, held tuples that still can't be published are dropped
+    pub fn release_all<P: Publisher>(
+        &mut self,
+        publisher: &mut P,
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+    ) {
+        self.publish_unpublished(publisher, analysers, global_sensitive_freq, Flush::Release)
+    }
+
+    /// suppress all the tuples in the buffer that have still not been published before
+    /// the cluster is evicted, the centroid of a cluster with less than k tuples can't be published
+    pub fn suppress_all<P: Publisher>(
+        &mut self,
+        publisher: &mut P,
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+    ) {
+        self.publish_unpublished(publisher, analysers, global_sensitive_freq, Flush::Suppress)
+    }
+
+    /// remove the tuples that have still not been published from the buffer,
+    /// so they can be merged into another cluster
+    pub fn take_unpublished(&mut self) -> Vec<Record<A>> {
+        let (unpublished, published) = self
             .w_current
             .buffer
-            .iter_mut()
-            .filter_map(|(published, original)| match published {
-                true => None,
-                false => {
-                    *published = true;
-                    Some(original.clone())
-                }
-            })
+            .drain(..)
+            .partition(|(published, _)| !published);
+        self.w_current.buffer = published;
+        self.held = 0;
+        unpublished.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// rough estimate of the memory used by the cluster in bytes,
+    /// every tuple of its buffers and centroids is assumed to take `tuple_size` bytes
+    pub fn estimated_size(&self, tuple_size: usize) -> usize {
+        let tuples = self.w_current.buffer.len() + self.w_prev.buffer.len() + 3;
+        std::mem::size_of::<Self>() + tuples * tuple_size
+    }
+
+    fn publish_unpublished<P: Publisher>(
+        &mut self,
+        publisher: &mut P,
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+        flush: Flush,
+    ) {
+        let unpublished: Vec<usize> = self
+            .w_current
+            .buffer
+            .iter()
+            .positions(|(published, _)| !published)
             .collect();
 
-        publish
-            .into_iter()
-            .for_each(|original| self.publish_data(&original, publisher, analysers))
+        unpublished.iter().for_each(|index| {
+            let original = self.w_current.buffer[*index].1.clone();
+            let published = self.publish_data(
+                &original,
+                publisher,
+                analysers,
+                global_sensitive_freq,
+                flush,
+            );
+            self.w_current.buffer[*index].0 = published
+        });
+
+        let held = self
+            .w_current
+            .buffer
+            .iter()
+            .filter(|(published, _)| !published)
+            .count();
+        if held > self.held {
+            analysers
+                .iter_mut()
+                .for_each(|analyser| analyser.on_hold(held - self.held))
+        }
+        self.held = held
     }
 
-    /// publish a given data tuple looking at (c,l)-diversity
+    /// publish a given data tuple looking at (c,l)-diversity and t-closeness.
+    /// Returns false when the tuple is held by `SuppressionStrategy::Hold`,
+    /// a cluster that is removed drops it instead
     fn publish_data<P: Publisher>(
         &mut self,
-        value: &A,
+        record: &Record<A>,
         publisher: &mut P,
-        analysers: &mut [Analyser<A>],
-    ) {
-        let publish = match self.check_l_recursive_diversity() {
-            true => {
+        analysers: &mut [Box<dyn Analyser<A>>],
+        global_sensitive_freq: &SensitiveFrequencies,
+        flush: Flush,
+    ) -> bool {
+        let value = &record.value;
+        let diverse = flush != Flush::Suppress
+            && self.check_l_recursive_diversity()
+            && self.check_t_closeness(global_sensitive_freq);
+        let strategy = match (diverse, self.suppression_strategy) {
+            (true, _) => None,
+            (false, SuppressionStrategy::Hold) if flush != Flush::Publish => {
+                Some(SuppressionStrategy::Drop)
+            }
+            (false, strategy) => Some(strategy),
+        };
+
+        if let Some(strategy) = strategy.filter(|strategy| *strategy != SuppressionStrategy::Hold) {
+            analysers
+                .iter_mut()
+                .for_each(|analyser| analyser.on_suppress(value, strategy))
+        }
+
+        let publish = match strategy {
+            None => {
                 debug!("l-diversity met");
                 let centroid_qi = self.noiser.add_noise(&self.centroid);
                 value.update_quasi_identifiers(centroid_qi)
             }
-            false => {
-                debug!("l-diversity not met, suppressing data");
-                value.suppress()
-            }
-        };
-
-        let mut dr = 0.0;
-        analysers.iter_mut().for_each(|analyser| match analyser {
-            Analyser::Mse(mse) => {
-                let error = value.calculate_difference(&publish);
-                mse.add_error(error)
+            Some(SuppressionStrategy::Hold) => {
+                debug!("l-diversity or t-closeness not met, holding data");
+                return false;
             }
-            Analyser::Sse(sse) => {
-                let error = value.calculate_info_loss(&publish);
-                sse.add_info_loss(error)
+            Some(SuppressionStrategy::Drop) => {
+                debug!("l-diversity or t-closeness not met, dropping data");
+                return true;
             }
-            Analyser::PublishingDelay(publishing_delay) => publishing_delay.add_delay(value),
-            Analyser::DisclosureRiskAnalyser(disclosure_risk) => {
-                disclosure_risk.add_data(value.clone(), &publish);
-                dr = disclosure_risk.current_linkage_probability;
+            Some(strategy) => {
+                debug!("l-diversity or t-closeness not met, suppressing data");
+                value.suppress_with(strategy, &mut self.rng)
             }
-            _ => {}
-        });
-        publisher.publish(publish, self.uuid, dr)
+        };
+
+        let published_qi = QiColumns::new(record.qi.schema().clone(), &publish.quasi_identifiers());
+        let mut ctx = PublishContext {
+            cluster: self.uuid,
+            metric: self.metric.as_ref(),
+            clock: self.clock.as_ref(),
+            suppression: strategy,
+            disclosure_risk: 0.0,
+            original_qi: &record.qi,
+            published_qi: &published_qi,
+        };
+        analysers
+            .iter_mut()
+            .for_each(|analyser| analyser.on_publish(value, &publish, &mut ctx));
+        let dr = ctx.disclosure_risk;
+
+        if self.output_mode.generalization() {
+            let quasi_identifiers = match strategy {
+                None => self.generalize(),
+                Some(SuppressionStrategy::FullDomain) => record
+                    .qi
+                    .quasi_identifiers()
+                    .iter()
+                    .map(GeneralizedQi::full_domain)
+                    .collect(),
+                Some(_) => vec![GeneralizedQi::Suppressed; published_qi.len()],
+            };
+            publisher.publish_generalized(GeneralizedRecord {
+                cluster: self.uuid,
+                quasi_identifiers,
+                sensitive_values: value.sensitive_values(),
+                disclosure_risk: dr,
+            })
+        }
+        if self.output_mode.microaggregation() {
+            publisher.publish(publish, self.uuid, dr)
+        }
+        true
     }
 
     /// calculate the duration since last arrival
     pub fn check_cluster_life_time(&self) -> u128 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
-            - self.last_arrival
+        self.clock.now().saturating_sub(self.last_arrival)
     }
 
-    /// update the frequency of sensitive values that have been added to the cluster
+    /// update the frequency of every sensitive value that has been added to the cluster
     fn update_sensitive_frequency(&mut self, value: &A) {
-        let sensitive_attribute = value.sensitive_value();
-
-        match self.sensitive_freq.get_mut(&sensitive_attribute) {
-            None => {
-                self.sensitive_freq.insert(sensitive_attribute, 1);
-            }
-            Some(sensitive_counter) => *sensitive_counter += 1,
-        }
+        update_sensitive_frequencies(&mut self.sensitive_freq, value)
     }
 
     /// keep up to date which and how many times categorical data has been present
     /// iniside the cluster
-    fn update_categorical_frequency(&mut self, value: &A) {
-        let qi_list = value.quasi_identifiers();
-        qi_list
-            .into_iter()
-            .enumerate()
-            .for_each(|(index, qi)| match qi {
-                QuasiIdentifierTypes::Interval(_) => {}
-                QuasiIdentifierTypes::Ordinal((value, _, _)) => {
-                    self.update_categorical_map_frequency(index, value)
-                }
-                QuasiIdentifierTypes::Nominal((value, _, _)) => {
-                    self.update_categorical_map_frequency(index, value)
+    fn update_categorical_frequency(&mut self, qi: &QiColumns) {
+        (0..qi.len()).for_each(|index| match qi.schema().template(index) {
+            QuasiIdentifierTypes::Interval(_)
+            | QuasiIdentifierTypes::Temporal(_)
+            | QuasiIdentifierTypes::GeoPoint(_) => {}
+            _ => match qi.category(index) {
+                Some(value) => self.update_categorical_map_frequency(index, value),
+                None => {
+                    self.categorical_freq.entry(index).or_default();
                 }
-            });
+            },
+        })
     }
 
     fn update_categorical_map_frequency(&mut self, index: usize, value: i32) {
@@ -239,8 +526,8 @@ where
     }
 
     /// detect if the cluster is experiencing concept drift after
-    /// the max buffer size has been achieved
-    pub fn detect_concept_drift(&mut self) {
+    /// the max buffer size has been achieved, returns whether drift is detected
+    pub fn detect_concept_drift(&mut self) -> bool {
         // if the categorical frequency hashmap is empty only numerical QI's are present
         // use the k-s test if that is the case, otherwise calculate the difference between
         // centroids of w_curr and w_prev
@@ -258,9 +545,8 @@ where
             }
             false => {
                 // check if the difference is > then 1.0 - confidence threshold
-                self.w_current
-                    .centroid
-                    .calculate_difference(&self.w_prev.centroid)
+                self.metric
+                    .difference(&self.w_current.centroid, &self.w_prev.centroid)
                     > self.calculate_threshold()
             }
         };
@@ -268,10 +554,12 @@ where
         // if concept drift is detected reset the current centroid to the previous buffer
         if rejected {
             self.w_current.centroid = self.w_prev.centroid.clone();
+            self.w_current.centroid_qi = self.w_prev.centroid_qi.clone();
             self.centroid = self.w_current.centroid.clone();
         }
 
-        self.w_current.reset()
+        self.w_current.reset();
+        rejected
     }
 
     /// calculate the needed threshold for KS test for mixed types data tuples
@@ -284,31 +572,51 @@ where
     }
 
     /// return the qi values that are present inside the buffer
-    /// to be used in the Kolmogorov Smirnov test
+    /// to be used in the Kolmogorov Smirnov test. Missing values are imputed with the
+    /// centroid of the buffer or left out depending on the `MissingValuePolicy`
     fn flatten_buffer_qi(buffer: &Buffer<A>) -> Vec<f64> {
+        let policy = buffer.centroid.missing_value_policy();
+        let centroid_qi = QiColumns::extract(&buffer.centroid);
         buffer
             .buffer
             .iter()
-            .flat_map(|(_, buffer)| {
-                buffer
-                    .quasi_identifiers()
-                    .into_iter()
-                    .map(|qi| match qi.extract_value() {
-                        QuasiIdentifierType::Float(value) => value,
-                        QuasiIdentifierType::Integer(value) => value as f64,
+            .flat_map(|(_, record)| {
+                (0..record.qi.len())
+                    .filter_map(|index| match (record.qi.numbers(index), policy) {
+                        (None, MissingValuePolicy::Impute) => centroid_qi.numbers(index),
+                        (numbers, _) => numbers,
                     })
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<f64>>()
             })
             .collect()
     }
 
+    /// the largest or smallest of 2 QI values
+    fn bound(x: QuasiIdentifierType, y: QuasiIdentifierType, max: bool) -> QuasiIdentifierType {
+        match (x, y) {
+            (QuasiIdentifierType::Integer(a), QuasiIdentifierType::Integer(b)) => match max {
+                true => QuasiIdentifierType::Integer(a.max(b)),
+                false => QuasiIdentifierType::Integer(a.min(b)),
+            },
+            (QuasiIdentifierType::Float(a), QuasiIdentifierType::Float(b)) => match max {
+                true => QuasiIdentifierType::Float(a.max(b)),
+                false => QuasiIdentifierType::Float(a.min(b)),
+            },
+            _ => panic!("wrong type"),
+        }
+    }
+
     pub fn is_full(&self) -> bool {
         self.w_current.is_full()
     }
 
-    pub fn print_domain_qis(&self) -> Vec<(QuasiIdentifierType, QuasiIdentifierType)> {
+    /// the QI's of the tuples inside the current buffer, grouped by their position
+    fn buffer_qi_lists(&self) -> Vec<Vec<QuasiIdentifierTypes>> {
         let mut qi_list: Vec<Vec<QuasiIdentifierTypes>> = Vec::new();
         self.w_current.buffer.iter().for_each(|(_, x)| {
-            x.quasi_identifiers()
+            x.qi.quasi_identifiers()
                 .into_iter()
                 .enumerate()
                 .for_each(|(index, qi)| match qi_list.get_mut(index) {
@@ -318,47 +626,116 @@ where
                     Some(list) => list.push(qi),
                 })
         });
-
         qi_list
+    }
+
+    /// generalize every QI to the range of its values inside the current buffer,
+    /// or to the set of values for nominal and hierarchical QI's
+    pub fn generalize(&self) -> Vec<GeneralizedQi> {
+        self.print_domain_qis()
+            .into_iter()
+            .zip(self.buffer_qi_lists())
+            .map(
+                |(domain, list)| match (domain, list.iter().find(|qi| !qi.is_missing())) {
+                    (_, Some(QuasiIdentifierTypes::GeoPoint(_))) => Self::bounding_box(&list),
+                    (None, _) | (_, None) => GeneralizedQi::Missing,
+                    (
+                        Some(_),
+                        Some(
+                            QuasiIdentifierTypes::Nominal(_)
+                            | QuasiIdentifierTypes::Hierarchical(_),
+                        ),
+                    ) => GeneralizedQi::Categories {
+                        values: list
+                            .into_iter()
+                            .filter_map(|qi| match qi.try_extract_value() {
+                                Some(QuasiIdentifierType::Integer(value)) => Some(value),
+                                _ => None,
+                            })
+                            .sorted()
+                            .dedup()
+                            .collect(),
+                    },
+                    (
+                        Some((QuasiIdentifierType::Float(min), QuasiIdentifierType::Float(max))),
+                        Some(QuasiIdentifierTypes::Temporal(_)),
+                    ) => GeneralizedQi::TimeRange {
+                        min: min as i64,
+                        max: max as i64,
+                    },
+                    (
+                        Some((
+                            QuasiIdentifierType::Integer(min),
+                            QuasiIdentifierType::Integer(max),
+                        )),
+                        _,
+                    ) => GeneralizedQi::IntegerRange { min, max },
+                    (
+                        Some((QuasiIdentifierType::Float(min), QuasiIdentifierType::Float(max))),
+                        _,
+                    ) => GeneralizedQi::FloatRange { min, max },
+                    _ => panic!("wrong QI"),
+                },
+            )
+            .collect()
+    }
+
+    /// the smallest box containing the present geo points of the list
+    fn bounding_box(list: &[QuasiIdentifierTypes]) -> GeneralizedQi {
+        let points: Vec<&GeoPoint> = list
+            .iter()
+            .filter_map(|qi| match qi {
+                QuasiIdentifierTypes::GeoPoint((point, _, _)) => Some(point),
+                _ => None,
+            })
+            .collect();
+        let bound = |coordinate: fn(&GeoPoint) -> f64, max: bool| {
+            let values = points.iter().map(|point| coordinate(point));
+            match max {
+                true => values.fold(f64::NEG_INFINITY, f64::max),
+                false => values.fold(f64::INFINITY, f64::min),
+            }
+        };
+        GeneralizedQi::BoundingBox {
+            min: GeoPoint {
+                latitude: bound(|point| point.latitude, false),
+                longitude: bound(|point| point.longitude, false),
+            },
+            max: GeoPoint {
+                latitude: bound(|point| point.latitude, true),
+                longitude: bound(|point| point.longitude, true),
+            },
+        }
+    }
+
+    /// the min and max of every QI inside the current buffer, see `generalize` for geo points.
+    /// `None` if all the values of a QI are missing or geo points
+    pub fn print_domain_qis(&self) -> Vec<Option<(QuasiIdentifierType, QuasiIdentifierType)>> {
+        self.buffer_qi_lists()
             .into_iter()
             .map(|x| {
                 let domain = x
                     .into_iter()
-                    .map(|x| x.extract_value())
+                    .filter_map(|x| x.try_extract_value())
                     .collect::<Vec<QuasiIdentifierType>>();
 
                 let max = domain
                     .iter()
                     .copied()
-                    .max_by(|unit1, unit2| match (unit1, unit2) {
-                        (QuasiIdentifierType::Integer(a), QuasiIdentifierType::Integer(b)) => {
-                            a.cmp(b)
-                        }
-                        (QuasiIdentifierType::Float(a), QuasiIdentifierType::Float(b)) => {
-                            a.partial_cmp(b).unwrap()
-                        }
-                        _ => panic!("wrong type"),
-                    })
-                    .unwrap();
-
+                    .reduce(|unit1, unit2| Self::bound(unit1, unit2, true))?;
                 let min = domain
                     .into_iter()
-                    .min_by(|unit1, unit2| match (unit1, unit2) {
-                        (QuasiIdentifierType::Integer(a), QuasiIdentifierType::Integer(b)) => {
-                            a.cmp(b)
-                        }
-                        (QuasiIdentifierType::Float(a), QuasiIdentifierType::Float(b)) => {
-                            a.partial_cmp(b).unwrap()
-                        }
-                        _ => panic!("wrong type"),
-                    })
-                    .unwrap();
-                (min, max)
+                    .reduce(|unit1, unit2| Self::bound(unit1, unit2, false))?;
+                Some((min, max))
             })
             .collect()
     }
 }
 
+fn default_metric() -> Arc<dyn DistanceMetric> {
+    Arc::new(Gower)
+}
+
 impl<A: Anonymizable, N: Noiser> Default for Cluster<A, N> {
     fn default() -> Self {
         Self {
@@ -366,6 +743,7 @@ impl<A: Anonymizable, N: Noiser> Default for Cluster<A, N> {
             k: 0,
             l: 0,
             c: 0,
+            t: None,
             centroid: Default::default(),
             w_current: Default::default(),
             w_prev: Default::default(),
@@ -378,7 +756,17 @@ impl<A: Anonymizable, N: Noiser> Default for Cluster<A, N> {
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_nanos(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
             noiser: Default::default(),
+            metric: default_metric(),
+            output_mode: OutputMode::default(),
+            suppression_strategy: SuppressionStrategy::default(),
+            held: 0,
+            clock: Default::default(),
+            rng: ChaCha12Rng::from_entropy(),
         }
         // set exit_time to 0
     }
@@ -386,12 +774,46 @@ impl<A: Anonymizable, N: Noiser> Default for Cluster<A, N> {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::VecDeque;
+    use std::collections::{HashMap, VecDeque};
+    use std::time::SystemTime;
 
+    use crate::analysis::analyser::Analyser;
+    use crate::analysis::suppression_analyser::SuppressionAnalyser;
     use crate::anonymization::buffer::{Buffer, DataContainer};
-    use crate::anonymization::cluster::Cluster;
+    use crate::anonymization::cluster::{
+        earth_movers_distance, update_sensitive_frequencies, Cluster, SensitiveFrequencies,
+    };
+    use crate::anonymization::suppression::SuppressionStrategy;
+    use crate::data_manipulation::anonymizable::{Anonymizable, SensitiveAttribute};
+    use crate::data_manipulation::geo::GeoPoint;
     use crate::data_manipulation::mueller::MuellerStream;
     use crate::noise::laplace::laplace_noiser::LaplaceNoiser;
+    use crate::publishing::generalized::{GeneralizedQi, GeneralizedRecord, OutputMode};
+    use crate::publishing::publisher::Publisher;
+    use uuid::Uuid;
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Prescription {
+        #[qi(interval, min = 0, max = 120)]
+        age: i32,
+        #[sensitive]
+        diagnosis: String,
+        #[sensitive]
+        medication: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Prescription {
+        fn default() -> Self {
+            Self {
+                age: 40,
+                diagnosis: "flu".to_string(),
+                medication: "aspirin".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
 
     fn create_test_buffer(qi_list: Vec<(i32, &str)>) -> VecDeque<DataContainer<MuellerStream>> {
         qi_list
@@ -403,7 +825,8 @@ mod tests {
                         age: Some(age),
                         gender: Some(gender.to_string()),
                         ..Default::default()
-                    },
+                    }
+                    .into(),
                 )
             })
             .collect()
@@ -600,4 +1023,309 @@ mod tests {
         let critical_value = cluster.calculate_threshold();
         assert!((critical_value - 0.592_813_442_642_605_5) <= f64::EPSILON)
     }
+
+    #[test]
+    fn l_diversity_multiple_sensitive_attributes() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster::new(2, 2, 3, 10, noiser);
+
+        cluster.add_tuple(Prescription::default());
+        cluster.add_tuple(Prescription {
+            age: 42,
+            diagnosis: "cold".to_string(),
+            ..Prescription::default()
+        });
+
+        // the diagnosis is diverse but everyone got the same medication
+        assert_eq!(cluster.sensitive_freq.len(), 2);
+        assert!(!cluster.check_l_recursive_diversity());
+
+        cluster.add_tuple(Prescription {
+            age: 44,
+            medication: "ibuprofen".to_string(),
+            ..Prescription::default()
+        });
+
+        assert!(cluster.check_l_recursive_diversity())
+    }
+
+    #[test]
+    fn recursive_diversity_dominant_value() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let cluster: Cluster<Prescription, LaplaceNoiser> = Cluster::new(2, 2, 7, 10, noiser);
+        let flu = SensitiveAttribute::String("flu".to_string());
+        let cold = SensitiveAttribute::String("cold".to_string());
+        let fever = SensitiveAttribute::String("fever".to_string());
+
+        // the most common value appears more than c times as often as the rest
+        let dominant = HashMap::from([(flu.clone(), 1), (cold.clone(), 100)]);
+        assert!(!cluster.is_diverse(&dominant));
+
+        let balanced = HashMap::from([(flu.clone(), 10), (cold.clone(), 20)]);
+        assert!(cluster.is_diverse(&balanced));
+
+        // with l = 3 the second most common value doesn't count towards r_l..r_m
+        let cluster: Cluster<Prescription, LaplaceNoiser> =
+            Cluster::new(2, 3, 2, 10, LaplaceNoiser::new(0.1, 3, 0.1));
+        let diverse = HashMap::from([(flu.clone(), 10), (cold.clone(), 9), (fever.clone(), 6)]);
+        assert!(cluster.is_diverse(&diverse));
+        let dominant = HashMap::from([(flu, 10), (cold, 9), (fever, 4)]);
+        assert!(!cluster.is_diverse(&dominant))
+    }
+
+    #[test]
+    fn earth_movers_distance_ordered() {
+        let global: HashMap<SensitiveAttribute, i32> = (1..=3)
+            .map(|value| (SensitiveAttribute::Integer(value), 1))
+            .collect();
+        let cluster = HashMap::from([(SensitiveAttribute::Integer(1), 2)]);
+
+        assert!((earth_movers_distance(&cluster, &global) - 0.5).abs() <= f64::EPSILON);
+        assert_eq!(earth_movers_distance(&global, &global), 0.0)
+    }
+
+    #[test]
+    fn earth_movers_distance_equal() {
+        let flu = SensitiveAttribute::String("flu".to_string());
+        let cold = SensitiveAttribute::String("cold".to_string());
+        let covid = SensitiveAttribute::String("covid".to_string());
+        let global = HashMap::from([(flu.clone(), 1), (cold.clone(), 1), (covid, 2)]);
+        let cluster = HashMap::from([(flu, 1), (cold, 1)]);
+
+        assert!((earth_movers_distance(&cluster, &global) - 0.5).abs() <= f64::EPSILON)
+    }
+
+    #[test]
+    fn t_closeness() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster::new(2, 2, 2, 10, noiser);
+        let records = [
+            Prescription::default(),
+            Prescription {
+                diagnosis: "cold".to_string(),
+                medication: "ibuprofen".to_string(),
+                ..Prescription::default()
+            },
+        ];
+        let mut global_sensitive_freq = SensitiveFrequencies::new();
+        records.iter().for_each(|record| {
+            update_sensitive_frequencies(&mut global_sensitive_freq, record);
+            cluster.add_tuple(record.clone())
+        });
+
+        cluster.t = Some(0.2);
+        assert!(cluster.check_t_closeness(&global_sensitive_freq));
+
+        // half of the global population has covid while nobody in the cluster has
+        (0..2).for_each(|_| {
+            update_sensitive_frequencies(
+                &mut global_sensitive_freq,
+                &Prescription {
+                    diagnosis: "covid".to_string(),
+                    ..Prescription::default()
+                },
+            )
+        });
+        assert!(!cluster.check_t_closeness(&global_sensitive_freq));
+
+        cluster.t = None;
+        assert!(cluster.check_t_closeness(&global_sensitive_freq))
+    }
+
+    #[test]
+    fn generalize() {
+        let cluster = setup_cluster(
+            vec![(30, "male"), (40, "female")],
+            vec![(50, "female")],
+            10,
+            MuellerStream {
+                age: Some(35),
+                gender: Some("male".to_string()),
+                ..MuellerStream::default()
+            },
+        );
+
+        assert_eq!(
+            cluster.generalize(),
+            vec![
+                GeneralizedQi::IntegerRange { min: 30, max: 40 },
+                GeneralizedQi::Categories { values: vec![0, 1] }
+            ]
+        )
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Delivery {
+        #[qi(geo)]
+        address: Option<GeoPoint>,
+        #[sensitive]
+        parcel: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Delivery {
+        fn default() -> Self {
+            Self {
+                address: None,
+                parcel: "letter".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn generalize_geo_points() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Delivery, LaplaceNoiser> = Cluster::new(2, 2, 2, 10, noiser);
+        [
+            Some((52.37, 4.9)),
+            None,
+            Some((50.85, 4.35)),
+            Some((51.22, 6.78)),
+        ]
+        .into_iter()
+        .for_each(|address| {
+            cluster.add_tuple(Delivery {
+                address: address.map(|(latitude, longitude)| GeoPoint::new(latitude, longitude)),
+                ..Delivery::default()
+            })
+        });
+
+        // missing points are left out of the bounding box
+        assert_eq!(
+            cluster.generalize(),
+            vec![GeneralizedQi::BoundingBox {
+                min: GeoPoint::new(50.85, 4.35),
+                max: GeoPoint::new(52.37, 6.78),
+            }]
+        )
+    }
+
+    /// keeps count of the microaggregated tuples and the generalized records
+    #[derive(Default)]
+    struct RecordingPublisher {
+        published: usize,
+        generalized: Vec<GeneralizedRecord>,
+    }
+
+    impl Publisher for RecordingPublisher {
+        fn publish<M: Anonymizable>(&mut self, _value: M, _uuid: Uuid, _dr: f64) {
+            self.published += 1
+        }
+
+        fn publish_generalized(&mut self, record: GeneralizedRecord) {
+            self.generalized.push(record)
+        }
+    }
+
+    #[test]
+    fn publish_output_mode() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster {
+            output_mode: OutputMode::Both,
+            ..Cluster::new(2, 2, 2, 10, noiser)
+        };
+        let mut publisher = RecordingPublisher::default();
+
+        cluster.add_tuple(Prescription::default());
+        cluster.publish(&mut publisher, &mut [], &HashMap::new());
+
+        // a single tuple isn't diverse, so all of its QI's are suppressed
+        assert_eq!(
+            publisher.generalized[0].quasi_identifiers,
+            vec![GeneralizedQi::Suppressed]
+        );
+
+        cluster.add_tuple(Prescription {
+            age: 42,
+            diagnosis: "cold".to_string(),
+            medication: "ibuprofen".to_string(),
+            ..Prescription::default()
+        });
+        cluster.publish(&mut publisher, &mut [], &HashMap::new());
+
+        assert_eq!(publisher.published, 2);
+        assert_eq!(
+            publisher.generalized[1].quasi_identifiers,
+            vec![GeneralizedQi::IntegerRange { min: 40, max: 42 }]
+        );
+        assert_eq!(
+            publisher.generalized[1].sensitive_values,
+            vec![
+                SensitiveAttribute::String("cold".to_string()),
+                SensitiveAttribute::String("ibuprofen".to_string())
+            ]
+        )
+    }
+
+    #[test]
+    fn publish_hold_and_release() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster {
+            suppression_strategy: SuppressionStrategy::Hold,
+            ..Cluster::new(2, 2, 2, 10, noiser)
+        };
+        let mut publisher = RecordingPublisher::default();
+        let mut analysers: [Box<dyn Analyser<Prescription>>; 1] =
+            [Box::new(SuppressionAnalyser::default())];
+
+        cluster.add_tuple(Prescription::default());
+        cluster.publish(&mut publisher, &mut analysers, &HashMap::new());
+
+        // a single tuple isn't diverse, so it is held
+        assert_eq!((publisher.published, cluster.held), (0, 1));
+
+        cluster.add_tuple(Prescription {
+            age: 42,
+            diagnosis: "cold".to_string(),
+            medication: "ibuprofen".to_string(),
+            ..Prescription::default()
+        });
+        cluster.publish(&mut publisher, &mut analysers, &HashMap::new());
+
+        // the held tuple is published with the centroid once the cluster is diverse
+        assert_eq!((publisher.published, cluster.held), (2, 0));
+
+        // tuples that are still held when the cluster is removed are dropped
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster {
+            suppression_strategy: SuppressionStrategy::Hold,
+            ..Cluster::new(2, 2, 2, 10, LaplaceNoiser::new(0.1, 3, 0.1))
+        };
+        cluster.add_tuple(Prescription::default());
+        cluster.publish(&mut publisher, &mut analysers, &HashMap::new());
+        cluster.release_all(&mut publisher, &mut analysers, &HashMap::new());
+
+        assert_eq!((publisher.published, cluster.held), (2, 0));
+        assert_eq!(
+            analysers[0].report(),
+            serde_json::json!({ "published": 2, "suppressed": { "drop": 1 }, "held": 2 })
+        )
+    }
+
+    #[test]
+    fn publish_full_domain() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut cluster: Cluster<Prescription, LaplaceNoiser> = Cluster {
+            output_mode: OutputMode::Both,
+            suppression_strategy: SuppressionStrategy::FullDomain,
+            ..Cluster::new(2, 2, 2, 10, noiser)
+        };
+        let mut publisher = RecordingPublisher::default();
+        let mut analysers: [Box<dyn Analyser<Prescription>>; 1] =
+            [Box::new(SuppressionAnalyser::default())];
+
+        cluster.add_tuple(Prescription::default());
+        cluster.publish(&mut publisher, &mut analysers, &HashMap::new());
+
+        assert_eq!(publisher.published, 1);
+        assert_eq!(
+            publisher.generalized[0].quasi_identifiers,
+            vec![GeneralizedQi::IntegerRange { min: 0, max: 120 }]
+        );
+        assert_eq!(
+            analysers[0].report(),
+            serde_json::json!({ "published": 0, "suppressed": { "full_domain": 1 }, "held": 0 })
+        )
+    }
 }
@@ -0,0 +1,428 @@
+use crate::data_manipulation::anonymizable::QuasiIdentifierTypes;
+use crate::data_manipulation::distance::{
+    category_distance, normalized_coordinate, DistanceMetric, QiDifference,
+};
+use std::collections::HashMap;
+
+/// maximum amount of clusters in a leaf before it is split
+const LEAF_SIZE: usize = 16;
+
+/// the distances of the clusters are calculated differently from their bounds,
+/// rounding errors shouldn't skip the nearest cluster
+const TOLERANCE: f64 = 1e-9;
+
+/// cluster key with the normalized coordinates of its centroid
+type Entry = (u128, Vec<f64>);
+
+/// how far apart the values of a QI are on its axis of the index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axis {
+    weight: usize,
+    category: Option<f64>, // smallest distance between 2 categories, `None` for QI's on a line
+}
+
+impl Axis {
+    pub fn new(qi: &QuasiIdentifierTypes) -> Self {
+        Self {
+            weight: qi.weight(),
+            category: category_distance(qi),
+        }
+    }
+
+    /// lower bound of the distance between the coordinate and any coordinate between lower and upper
+    fn distance(&self, coordinate: f64, lower: f64, upper: f64) -> f64 {
+        if coordinate.is_nan() || lower.is_nan() || upper.is_nan() {
+            return 0.0;
+        }
+        match self.category {
+            // only a range of a single category can have no other categories
+            Some(distance) => match coordinate < lower || coordinate > upper {
+                true => distance,
+                false => 0.0,
+            },
+            None => (lower - coordinate).max(coordinate - upper).max(0.0),
+        }
+    }
+}
+
+/// coordinates of the QI's of a tuple or centroid in the index
+pub fn point(qi: &[QuasiIdentifierTypes]) -> Vec<f64> {
+    qi.iter().map(normalized_coordinate).collect()
+}
+
+/// k-d tree over the normalized coordinates of the cluster centroids, see `normalized_coordinate`.
+/// A cluster is removed before a tuple moves its centroid and inserted again afterwards.
+/// The boxes of the nodes only grow when clusters are removed, so the tree is rebuilt
+/// once as many clusters have been removed as it contains
+#[derive(Default)]
+pub struct ClusterIndex {
+    nodes: Vec<Node>,             // the root is the first node
+    leaves: HashMap<u128, usize>, // leaf node of every cluster key
+    removed: usize,               // clusters removed since the tree was built
+}
+
+struct Node {
+    lower: Vec<f64>, // lower corner of the box around the coordinates of the node
+    upper: Vec<f64>, // upper corner, a missing coordinate stretches the box over the whole axis
+    kind: Kind,
+}
+
+enum Kind {
+    Leaf(Vec<Entry>),
+    Split {
+        dimension: usize,
+        value: f64, // smaller coordinates are on the left
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn new(dimensions: usize) -> Self {
+        Self {
+            lower: vec![f64::INFINITY; dimensions],
+            upper: vec![f64::NEG_INFINITY; dimensions],
+            kind: Kind::Leaf(Vec::new()),
+        }
+    }
+
+    fn expand(&mut self, point: &[f64]) {
+        self.lower
+            .iter_mut()
+            .zip(self.upper.iter_mut())
+            .zip(point)
+            .for_each(|((lower, upper), coordinate)| match coordinate.is_nan() {
+                true => {
+                    *lower = f64::NEG_INFINITY;
+                    *upper = f64::INFINITY
+                }
+                false => {
+                    *lower = lower.min(*coordinate);
+                    *upper = upper.max(*coordinate)
+                }
+            })
+    }
+}
+
+impl ClusterIndex {
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default()
+    }
+
+    pub fn insert(&mut self, key: u128, point: Vec<f64>) {
+        if self.nodes.is_empty() {
+            self.nodes.push(Node::new(point.len()))
+        }
+
+        let mut index = 0;
+        loop {
+            self.nodes[index].expand(&point);
+            match &self.nodes[index].kind {
+                Kind::Split {
+                    dimension,
+                    value,
+                    left,
+                    right,
+                } => {
+                    index = match point[*dimension] < *value {
+                        true => *left,
+                        false => *right,
+                    }
+                }
+                Kind::Leaf(_) => break,
+            }
+        }
+
+        self.leaves.insert(key, index);
+        if let Kind::Leaf(entries) = &mut self.nodes[index].kind {
+            entries.push((key, point));
+            if entries.len() > LEAF_SIZE {
+                let entries = std::mem::take(entries);
+                self.fill(index, entries)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: u128) {
+        let index = match self.leaves.remove(&key) {
+            Some(index) => index,
+            None => return,
+        };
+        if let Kind::Leaf(entries) = &mut self.nodes[index].kind {
+            if let Some(position) = entries.iter().position(|(entry, _)| *entry == key) {
+                entries.swap_remove(position);
+            }
+        }
+
+        self.removed += 1;
+        if self.removed > self.len().max(LEAF_SIZE) {
+            self.rebuild()
+        }
+    }
+
+    /// key of the cluster with the smallest distance within the maximal distance, ties go to
+    /// the smallest key like a scan over all clusters. Only the clusters whose coordinates can be
+    /// within the distance by the lower bound of the metric are measured
+    pub fn nearest(
+        &self,
+        query: &[f64],
+        axes: &[Axis],
+        max_distance: f64,
+        metric: &dyn DistanceMetric,
+        measure: impl Fn(u128) -> f64,
+    ) -> Option<u128> {
+        let mut best: Option<(f64, u128)> = None;
+        let mut differences = Vec::with_capacity(axes.len());
+        let mut stack = match self.nodes.is_empty() {
+            true => vec![],
+            false => vec![0],
+        };
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = best.map_or(max_distance, |(distance, _)| distance) + TOLERANCE;
+            let bound = lower_bound(
+                metric,
+                query,
+                axes,
+                &node.lower,
+                &node.upper,
+                &mut differences,
+            );
+            if bound > limit {
+                continue;
+            }
+
+            match &node.kind {
+                Kind::Leaf(entries) => {
+                    for (key, point) in entries {
+                        let limit = best.map_or(max_distance, |(distance, _)| distance) + TOLERANCE;
+                        if lower_bound(metric, query, axes, point, point, &mut differences) > limit
+                        {
+                            continue;
+                        }
+                        let distance = measure(*key);
+                        let closer = match best {
+                            None => true,
+                            Some((best_distance, best_key)) => {
+                                distance < best_distance
+                                    || (distance == best_distance && *key < best_key)
+                            }
+                        };
+                        if distance <= max_distance && closer {
+                            best = Some((distance, *key))
+                        }
+                    }
+                }
+                Kind::Split {
+                    dimension,
+                    value,
+                    left,
+                    right,
+                } => {
+                    // the side of the query is visited first, it most likely has the nearest cluster
+                    match query[*dimension] < *value {
+                        true => stack.extend([*right, *left]),
+                        false => stack.extend([*left, *right]),
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, key)| key)
+    }
+
+    fn rebuild(&mut self) {
+        let entries: Vec<Entry> = self
+            .nodes
+            .drain(..)
+            .flat_map(|node| match node.kind {
+                Kind::Leaf(entries) => entries,
+                Kind::Split { .. } => vec![],
+            })
+            .collect();
+        self.clear();
+        if !entries.is_empty() {
+            self.build(entries);
+        }
+    }
+
+    /// add a node with the entries and return its index
+    fn build(&mut self, entries: Vec<Entry>) -> usize {
+        let mut node = Node::new(entries[0].1.len());
+        entries.iter().for_each(|(_, point)| node.expand(point));
+        self.nodes.push(node);
+        let index = self.nodes.len() - 1;
+        self.fill(index, entries);
+        index
+    }
+
+    /// store the entries in the node, a node with too many entries is split
+    /// at the median of the axis on which the entries are spread the most
+    fn fill(&mut self, index: usize, entries: Vec<Entry>) {
+        match split_plane(&self.nodes[index], &entries) {
+            Some((dimension, value)) if entries.len() > LEAF_SIZE => {
+                let (left, right): (Vec<Entry>, Vec<Entry>) = entries
+                    .into_iter()
+                    .partition(|(_, point)| point[dimension] < value);
+                let left = self.build(left);
+                let right = self.build(right);
+                self.nodes[index].kind = Kind::Split {
+                    dimension,
+                    value,
+                    left,
+                    right,
+                }
+            }
+            _ => {
+                entries.iter().for_each(|(key, _)| {
+                    self.leaves.insert(*key, index);
+                });
+                self.nodes[index].kind = Kind::Leaf(entries)
+            }
+        }
+    }
+}
+
+/// axis and value that split the entries into 2 non empty halves,
+/// `None` when all the coordinates are the same
+fn split_plane(node: &Node, entries: &[Entry]) -> Option<(usize, f64)> {
+    let spread = |dimension: &usize| {
+        let (lower, upper) = (node.lower[*dimension], node.upper[*dimension]);
+        match lower.is_finite() && upper.is_finite() {
+            true => upper - lower,
+            false => 0.0,
+        }
+    };
+    let dimension = (0..node.lower.len()).max_by(|x, y| spread(x).total_cmp(&spread(y)))?;
+    if spread(&dimension) <= 0.0 {
+        return None;
+    }
+
+    let mut values: Vec<f64> = entries
+        .iter()
+        .map(|(_, point)| point[dimension])
+        .filter(|value| !value.is_nan())
+        .collect();
+    values.sort_by(f64::total_cmp);
+    // the median can be the smallest value, then the next larger value keeps the left side non empty
+    let median = values[values.len() / 2];
+    match median > values[0] {
+        true => Some((dimension, median)),
+        false => values
+            .into_iter()
+            .find(|value| *value > median)
+            .map(|value| (dimension, value)),
+    }
+}
+
+/// lower bound of the distance between the query and any point in the box
+fn lower_bound(
+    metric: &dyn DistanceMetric,
+    query: &[f64],
+    axes: &[Axis],
+    lower: &[f64],
+    upper: &[f64],
+    differences: &mut Vec<QiDifference>,
+) -> f64 {
+    differences.clear();
+    differences.extend(query.iter().zip(axes).zip(lower.iter().zip(upper)).map(
+        |((coordinate, axis), (lower, upper))| QiDifference {
+            distance: axis.distance(*coordinate, *lower, *upper),
+            weight: axis.weight,
+            deltas: vec![],
+        },
+    ));
+    metric.lower_bound(differences).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Axis, ClusterIndex};
+    use crate::data_manipulation::distance::Gower;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    /// 2 interval QI's and a nominal QI
+    const AXES: [Axis; 3] = [
+        Axis {
+            weight: 1,
+            category: None,
+        },
+        Axis {
+            weight: 1,
+            category: None,
+        },
+        Axis {
+            weight: 1,
+            category: Some(1.0),
+        },
+    ];
+
+    /// gower distance where a missing value has the maximum distance like `MissingValuePolicy::OwnCategory`
+    fn distance(x: &[f64], y: &[f64]) -> f64 {
+        let differences = x.iter().zip(y).zip(AXES).map(|((x, y), axis)| {
+            match (x.is_nan() || y.is_nan(), axis.category) {
+                (true, _) => 1.0,
+                (false, Some(_)) => (x != y) as i32 as f64,
+                (false, None) => (x - y).abs(),
+            }
+        });
+        differences.sum::<f64>() / AXES.len() as f64
+    }
+
+    fn point(rng: &mut StdRng) -> Vec<f64> {
+        let mut point: Vec<f64> = vec![
+            rng.gen_range(0..20) as f64 / 20.0,
+            rng.gen_range(0..20) as f64 / 20.0,
+            rng.gen_range(0..4) as f64,
+        ];
+        if rng.gen_bool(0.1) {
+            point[rng.gen_range(0..3)] = f64::NAN
+        }
+        point
+    }
+
+    #[test]
+    fn nearest_matches_scan() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut index = ClusterIndex::default();
+        let mut points: BTreeMap<u128, Vec<f64>> = BTreeMap::new();
+        (0..500_u128).for_each(|key| {
+            let point = point(&mut rng);
+            index.insert(key, point.clone());
+            points.insert(key, point);
+        });
+        // move the clusters around like tuples do, which rebuilds the tree
+        (0..1000_u128).for_each(|key| {
+            let old = rng.gen_range(0..500 + key);
+            if points.remove(&old).is_some() {
+                index.remove(old);
+                let point = point(&mut rng);
+                index.insert(500 + key, point.clone());
+                points.insert(500 + key, point);
+            }
+        });
+        assert_eq!(index.len(), points.len());
+
+        (0..200).for_each(|_| {
+            let query = point(&mut rng);
+            let max_distance = rng.gen_range(0.0..0.6);
+            let expected = points
+                .iter()
+                .map(|(key, point)| (distance(point, &query), *key))
+                .filter(|(distance, _)| *distance <= max_distance)
+                .min_by(|x, y| x.0.total_cmp(&y.0).then(x.1.cmp(&y.1)))
+                .map(|(_, key)| key);
+            let nearest = index.nearest(&query, &AXES, max_distance, &Gower, |key| {
+                distance(&points[&key], &query)
+            });
+            assert_eq!(nearest, expected)
+        })
+    }
+}
@@ -0,0 +1,14 @@
+/// Which cluster is evicted when the anonymizer exceeds its maximum cluster count or memory budget.
+/// An evicted cluster with at least k tuples is released, the tuples of a smaller cluster are
+/// suppressed or merged into the nearest cluster
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// the cluster with the earliest last arrival of a tuple
+    #[default]
+    LeastRecentlyUsed,
+    /// the cluster with the least tuples, like the singleton clusters of outliers
+    Smallest,
+    /// the cluster that was created first
+    Oldest,
+}
@@ -4,14 +4,35 @@ use crate::analysis::disclosure_risk_analyser::DisclosureRiskAnalyser;
 use crate::analysis::mse_analyser::MseAnalyser;
 use crate::analysis::publishing_delay_analyser::PublishingDelayAnalyser;
 use crate::analysis::sse_analyser::SseAnalyser;
-use crate::anonymization::cluster::Cluster;
-use crate::data_manipulation::anonymizable::{Anonymizable, QuasiIdentifierType};
+use crate::analysis::suppression_analyser::SuppressionAnalyser;
+use crate::anonymization::clock::{Clock, TimeMode};
+use crate::anonymization::cluster::{update_sensitive_frequencies, Cluster, SensitiveFrequencies};
+use crate::anonymization::cluster_index::{self, Axis, ClusterIndex};
+use crate::anonymization::eviction::EvictionPolicy;
+use crate::anonymization::microagg_anonymizer_builder::MicroaggAnonymizerBuilder;
+use crate::anonymization::record::{QiColumns, QiSchema, Record};
+use crate::anonymization::snapshot::{self, SnapshotRef, SNAPSHOT_VERSION};
+use crate::anonymization::suppression::SuppressionStrategy;
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, QuasiIdentifierType, QuasiIdentifierTypes,
+};
+use crate::data_manipulation::distance::{DistanceMetric, Gower};
+use crate::error::{DiffPrivError, RecordErrorPolicy};
 use crate::noise::noiser::Noiser;
+use crate::publishing::generalized::OutputMode;
 use crate::publishing::publisher::Publisher;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use rayon::prelude::*;
-use std::collections::BTreeMap;
-use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// amount of tuples of a batch whose nearest clusters are looked up together
+const BATCH_SIZE: usize = 1024;
 
 // the micro aggregation differential privacy anonymization
 pub struct MicroaggAnonymizer<N, A, P>
@@ -24,13 +45,38 @@ where
     k_max: usize,       // maximum k-anonymity level before cluster is removed
     l: usize,           // l-diversity level
     c: i32,             // recursive (l,c)-diversity
+    t: Option<f64>,     // t-closeness, not checked when None
     delta: u128,        // life time delta in seconds
     diff_thres: f64,    // difference threshold between data points
     buffer_size: usize, // batch of data used to detect concept drift
     pub publisher: P,
     pub cluster_set: BTreeMap<u128, Cluster<A, N>>,
     pub noiser: N,
-    pub analysers: Vec<Analyser<A>>,
+    pub analysers: Vec<Box<dyn Analyser<A>>>,
+    pub sensitive_freq: SensitiveFrequencies, // running global distribution of the sensitive attributes
+    record_error_policy: RecordErrorPolicy,   // what to do with records that can't be anonymized
+    schema: Option<Arc<QiSchema>>, // QI's of the first record, used to validate and extract the others
+    pub dead_letters: Vec<(A, DiffPrivError)>, // rejected records with `RecordErrorPolicy::DeadLetter`
+    metric: Arc<dyn DistanceMetric>, // distance between tuples and centroids, shared with the clusters
+    output_mode: OutputMode,
+    suppression_strategy: SuppressionStrategy, // what happens with tuples of clusters that aren't diverse
+    clock: Arc<Clock>,                         // processing or event time, shared with the clusters
+    rng: Box<dyn RngCore + Send>, // seeds the noise and the suppression of every new cluster
+    max_clusters: Option<usize>,  // clusters are evicted above this count, unbounded when None
+    memory_budget: Option<usize>, // estimated bytes of the clusters before they are evicted
+    memory: usize, // estimated bytes of the clusters in the cluster set, updated on insert and remove
+    tuple_size: Option<usize>, // estimated bytes of a tuple, taken from the first inserted centroid
+    eviction_policy: EvictionPolicy, // which cluster is evicted first
+    merge_evicted: bool, // merge the tuples of evicted clusters below k instead of suppressing them
+    index: ClusterIndex, // nearest centroids of the cluster set for metrics with a lower bound
+    changes: Option<BatchChanges>, // clusters changed since the lookups of a batch
+}
+
+/// clusters removed or inserted since the nearest clusters of a batch were looked up
+#[derive(Default)]
+struct BatchChanges {
+    keys: HashSet<u128>,
+    index: ClusterIndex, // the changed clusters that are in the cluster set
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -40,6 +86,8 @@ where
     A: Anonymizable,
     P: Publisher,
 {
+    /// create the anonymizer with positional parameters that are not validated,
+    /// see `builder` for named parameters that are
     pub fn new(
         k: usize,
         k_max: usize,
@@ -51,18 +99,20 @@ where
         publisher: P,
         noiser: N,
     ) -> Self {
-        let analysers = vec![
-            Analyser::Mse(MseAnalyser::default()),
-            Analyser::Sse(SseAnalyser::default()),
-            Analyser::PublishingDelay(PublishingDelayAnalyser::default()),
-            Analyser::ClusterAnalyser(ClusterAnalyser::default()),
-            Analyser::DisclosureRiskAnalyser(DisclosureRiskAnalyser::initialize(100)),
+        let analysers: Vec<Box<dyn Analyser<A>>> = vec![
+            Box::new(MseAnalyser::default()),
+            Box::new(SseAnalyser::default()),
+            Box::new(PublishingDelayAnalyser::default()),
+            Box::new(ClusterAnalyser::default()),
+            Box::new(DisclosureRiskAnalyser::initialize(100)),
+            Box::new(SuppressionAnalyser::default()),
         ];
         Self {
             k,
             k_max,
             l,
             c,
+            t: None,
             diff_thres,
             delta: delta * 1000000000,
             buffer_size,
@@ -70,106 +120,582 @@ where
             cluster_set: Default::default(),
             noiser,
             analysers,
+            sensitive_freq: Default::default(),
+            record_error_policy: RecordErrorPolicy::default(),
+            schema: None,
+            dead_letters: Vec::new(),
+            metric: Arc::new(Gower),
+            output_mode: OutputMode::default(),
+            suppression_strategy: SuppressionStrategy::default(),
+            clock: Default::default(),
+            rng: Box::new(ChaCha12Rng::from_entropy()),
+            max_clusters: None,
+            memory_budget: None,
+            memory: 0,
+            tuple_size: None,
+            eviction_policy: EvictionPolicy::default(),
+            merge_evicted: false,
+            index: ClusterIndex::default(),
+            changes: None,
         }
     }
 
-    /// feed the data tuple through the differential privacy algorithm
+    /// create a `MicroaggAnonymizerBuilder` with named parameters and defaults
+    pub fn builder(publisher: P, noiser: N) -> MicroaggAnonymizerBuilder<N, A, P> {
+        MicroaggAnonymizerBuilder::new(publisher, noiser)
+    }
+
+    /// only publish the centroid of a cluster when the distribution of every sensitive
+    /// attribute in the cluster has an Earth Mover's Distance of at most t to the global distribution
+    pub fn with_t_closeness(mut self, t: f64) -> Self {
+        self.t = Some(t);
+        self
+    }
+
+    /// set what happens with records that can't be anonymized
+    pub fn with_record_error_policy(mut self, record_error_policy: RecordErrorPolicy) -> Self {
+        self.record_error_policy = record_error_policy;
+        self
+    }
+
+    /// set the metric used to find the best cluster of a tuple, detect concept drift
+    /// and measure the error of published tuples, the default is `Gower`
+    pub fn with_distance_metric(mut self, metric: Arc<dyn DistanceMetric>) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// measure the cluster life time delta and the publishing delay in processing time
+    /// or in the event time of the tuples, the default is `TimeMode::ProcessingTime`
+    pub fn with_time_mode(mut self, time_mode: TimeMode) -> Self {
+        self.clock = Arc::new(Clock::new(time_mode));
+        self
+    }
+
+    /// evict clusters when there are more than `max_clusters`
+    pub fn with_max_clusters(mut self, max_clusters: usize) -> Self {
+        self.max_clusters = Some(max_clusters);
+        self
+    }
+
+    /// evict clusters when their estimated memory exceeds the budget in bytes
+    pub fn with_memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// set which cluster is evicted first, the default is `EvictionPolicy::LeastRecentlyUsed`
+    pub fn with_eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// merge the tuples of an evicted cluster with less than k tuples into the nearest
+    /// cluster instead of suppressing them
+    pub fn with_merge_evicted(mut self, merge_evicted: bool) -> Self {
+        self.merge_evicted = merge_evicted;
+        self
+    }
+
+    /// draw the seeds of the clusters from the given random number generator,
+    /// the default is the `ChaCha12Rng` CSPRNG seeded from the OS
+    pub fn with_rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// seed the anonymizer so that runs over the same tuples in the same order publish
+    /// the same noise, suppressed values and cluster uuids
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_rng(ChaCha12Rng::seed_from_u64(seed))
+    }
+
+    /// register an analyser next to the default ones, its hooks are called
+    /// while tuples are anonymized
+    pub fn with_analyser(mut self, analyser: Box<dyn Analyser<A>>) -> Self {
+        self.analysers.push(analyser);
+        self
+    }
+
+    /// the reports of all the analysers by their name
+    pub fn report(&self) -> serde_json::Map<String, serde_json::Value> {
+        self.analysers
+            .iter()
+            .map(|analyser| (analyser.name().to_string(), analyser.report()))
+            .collect()
+    }
+
+    /// set whether tuples are published microaggregated, generalized to the
+    /// ranges of their cluster or both
+    pub fn with_output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    /// set what happens with tuples of clusters that don't satisfy l-diversity
+    /// or t-closeness, the default is `SuppressionStrategy::UniformSample`
+    pub fn with_suppression_strategy(mut self, suppression_strategy: SuppressionStrategy) -> Self {
+        self.suppression_strategy = suppression_strategy;
+        self
+    }
+
+    /// write the clusters with their buffers, the sensitive frequencies, the state of the noiser
+    /// and the clock to a file with bincode. The analysers, the publisher and the distance
+    /// metric are not part of the snapshot. The tuples are written with `Serialize` and read
+    /// with `Deserialize`, fields skipped by only one of them can't be restored
+    pub fn snapshot(&mut self, path: impl AsRef<Path>) -> Result<(), DiffPrivError>
+    where
+        N: Serialize,
+    {
+        // continuing after a snapshot and restoring it draw the same seeds
+        let seed = self.rng.next_u64();
+        self.rng = Box::new(ChaCha12Rng::seed_from_u64(seed));
+
+        snapshot::write(
+            path.as_ref(),
+            &SnapshotRef {
+                version: SNAPSHOT_VERSION,
+                cluster_set: &self.cluster_set,
+                noiser: &self.noiser,
+                sensitive_freq: &self.sensitive_freq,
+                watermark: self.clock.watermark(),
+                seed,
+            },
+        )
+    }
+
+    /// replace the state of the anonymizer with a snapshot, the anonymizer should
+    /// be created with the same parameters as the one that wrote the snapshot
+    pub fn restore(&mut self, path: impl AsRef<Path>) -> Result<(), DiffPrivError>
+    where
+        A: DeserializeOwned,
+        N: DeserializeOwned,
+    {
+        let snapshot = snapshot::read::<A, N>(path.as_ref())?;
+
+        self.cluster_set = snapshot
+            .cluster_set
+            .into_iter()
+            .map(|(key, mut cluster)| {
+                cluster.metric = Arc::clone(&self.metric);
+                cluster.clock = Arc::clone(&self.clock);
+                (key, cluster)
+            })
+            .collect();
+        self.rebuild_index();
+        self.noiser = snapshot.noiser;
+        self.sensitive_freq = snapshot.sensitive_freq;
+        self.clock.advance(snapshot.watermark);
+        self.rng = Box::new(ChaCha12Rng::seed_from_u64(snapshot.seed));
+        Ok(())
+    }
+
+    /// feed the data tuple through the differential privacy algorithm.
+    /// Panics on a bad record with `RecordErrorPolicy::Abort`, see `try_anonymize`
     pub fn anonymize(&mut self, value: A) {
+        if let Err(e) = self.try_anonymize(value) {
+            panic!("{}", e)
+        }
+    }
+
+    /// feed the data tuple through the differential privacy algorithm.
+    /// A record that can't be anonymized is handled according to the `RecordErrorPolicy`,
+    /// only with `RecordErrorPolicy::Abort` the error is returned
+    pub fn try_anonymize(&mut self, value: A) -> Result<(), DiffPrivError> {
+        match self.validate(&value) {
+            Ok(qi) => {
+                self.anonymize_valid(Record::new(value, qi));
+                Ok(())
+            }
+            Err(e) => self.reject(value, e),
+        }
+    }
+
+    /// feed the data tuples through the differential privacy algorithm in their order.
+    /// Panics on a bad record with `RecordErrorPolicy::Abort`, see `try_anonymize_batch`
+    pub fn anonymize_batch(&mut self, values: impl IntoIterator<Item = A>) {
+        if let Err(e) = self.try_anonymize_batch(values) {
+            panic!("{}", e)
+        }
+    }
+
+    /// feed the data tuples through the differential privacy algorithm with the same result as
+    /// `try_anonymize` for every tuple in their order. The tuples are validated and their nearest
+    /// clusters looked up in parallel, after which they are added to the clusters one by one.
+    /// With `RecordErrorPolicy::Abort` the tuples after the bad record aren't anonymized
+    pub fn try_anonymize_batch(
+        &mut self,
+        values: impl IntoIterator<Item = A>,
+    ) -> Result<(), DiffPrivError> {
+        let mut values = values.into_iter();
+        // the first valid record decides the QI's of the others
+        while self.schema.is_none() {
+            match values.next() {
+                Some(value) => self.try_anonymize(value)?,
+                None => return Ok(()),
+            }
+        }
+
+        loop {
+            let batch: Vec<A> = values.by_ref().take(BATCH_SIZE).collect();
+            if batch.is_empty() {
+                return Ok(());
+            }
+            self.changes = Some(BatchChanges::default());
+            let result = self.anonymize_lookups(batch);
+            self.changes = None;
+            result?
+        }
+    }
+
+    /// validate the tuples and look up their nearest clusters in parallel,
+    /// then anonymize them in their order
+    fn anonymize_lookups(&mut self, batch: Vec<A>) -> Result<(), DiffPrivError> {
+        if self.index.len() != self.cluster_set.len() {
+            self.rebuild_index()
+        }
+        // metrics that learn from every tuple can only find the cluster after the tuples before
+        let adaptive = self.metric.is_adaptive();
+        let schema = self.schema.as_ref().unwrap();
+        let (cluster_set, index, metric) = (&self.cluster_set, &self.index, self.metric.as_ref());
+        let diff_thres = self.diff_thres;
+
+        let lookups: Vec<Result<(QiColumns, Option<u128>), DiffPrivError>> = batch
+            .par_iter()
+            .map(|value| {
+                let qi =
+                    QiColumns::new(Arc::clone(schema), &Self::checked_qi(Some(schema), value)?);
+                let candidate = match adaptive {
+                    true => None,
+                    false => closest_key(cluster_set, index, metric, &qi, diff_thres),
+                };
+                Ok((qi, candidate))
+            })
+            .collect();
+
+        batch
+            .into_iter()
+            .zip(lookups)
+            .try_for_each(|(value, lookup)| match lookup {
+                Ok((qi, candidate)) => {
+                    let value = Record::new(value, qi);
+                    self.observe(&value);
+                    let cluster = match adaptive {
+                        true => self.find_best_cluster(&value.qi),
+                        false => self.find_looked_up_cluster(&value.qi, candidate),
+                    };
+                    self.assign(value, cluster);
+                    Ok(())
+                }
+                Err(e) => self.reject(value, e),
+            })
+    }
+
+    /// handle a record that can't be anonymized according to the `RecordErrorPolicy`
+    fn reject(&mut self, value: A, e: DiffPrivError) -> Result<(), DiffPrivError> {
+        match self.record_error_policy {
+            RecordErrorPolicy::Skip => {
+                warn!("skipping record: {}", e);
+                Ok(())
+            }
+            RecordErrorPolicy::DeadLetter => {
+                warn!("dead lettering record: {}", e);
+                self.dead_letters.push((value, e));
+                Ok(())
+            }
+            RecordErrorPolicy::Abort => Err(e),
+        }
+    }
+
+    /// check that the QI's of the record can be extracted, updated and
+    /// have the same types as the QI's of the records before, returns the extracted QI's
+    fn validate(&mut self, value: &A) -> Result<QiColumns, DiffPrivError> {
+        let qi = Self::checked_qi(self.schema.as_ref(), value)?;
+        let schema = self
+            .schema
+            .get_or_insert_with(|| Arc::new(QiSchema::new(value, &qi)));
+        Ok(QiColumns::new(Arc::clone(schema), &qi))
+    }
+
+    /// the QI's of the record when they are valid and fit the schema of the records before.
+    /// Only the first record is updated with its QI's to check that they can be written back,
+    /// the QI's of the later records have the types of the schema and come from the records
+    fn checked_qi(
+        schema: Option<&Arc<QiSchema>>,
+        value: &A,
+    ) -> Result<Vec<QuasiIdentifierTypes>, DiffPrivError> {
+        let qi = value.try_quasi_identifiers()?;
+        qi.iter().try_for_each(QuasiIdentifierTypes::validate)?;
+        match schema {
+            Some(schema) => schema.check(&qi)?,
+            None => drop(value.try_update_quasi_identifiers(qi.clone())?),
+        }
+        Ok(qi)
+    }
+
+    /// move the clock of the anonymizer to `now` and flush the clusters that expired,
+    /// returns the amount of flushed clusters. See `flush_expired`
+    pub fn tick(&mut self, now: SystemTime) -> usize {
+        self.clock.tick(now);
+        self.flush_expired()
+    }
+
+    /// publish or suppress the tuples of every cluster without new tuples for `delta`
+    /// and remove it, otherwise a cluster that stops receiving tuples keeps them forever.
+    /// Returns the amount of flushed clusters
+    pub fn flush_expired(&mut self) -> usize {
+        let mut flushed = 0;
+        // the clusters are ordered by their last arrival, the expired ones come first
+        while let Some((&key, cluster)) = self.cluster_set.first_key_value() {
+            if cluster.check_cluster_life_time() < self.delta {
+                break;
+            }
+            let cluster = self.remove_cluster(key).unwrap();
+            self.release_cluster(cluster);
+            flushed += 1
+        }
+        if flushed > 0 {
+            info!("{} expired clusters flushed", flushed)
+        }
+        flushed
+    }
+
+    /// publish or suppress the tuples of every cluster and remove it, e.g. when the stream ends.
+    /// Returns the amount of flushed clusters
+    pub fn flush_all(&mut self) -> usize {
+        let mut flushed = 0;
+        while let Some((&key, _)) = self.cluster_set.first_key_value() {
+            let cluster = self.remove_cluster(key).unwrap();
+            self.release_cluster(cluster);
+            flushed += 1
+        }
+        if flushed > 0 {
+            info!("{} clusters flushed", flushed)
+        }
+        flushed
+    }
+
+    /// evict clusters until the maximum cluster count and the memory budget are met
+    fn enforce_limits(&mut self) {
+        while self.cluster_set.len() > self.max_clusters.unwrap_or(usize::MAX)
+            || matches!(self.memory_budget, Some(budget) if self.estimated_memory() > budget)
+        {
+            match self.eviction_candidate() {
+                Some(key) => self.evict(key),
+                None => break,
+            }
+        }
+    }
+
+    /// rough estimate of the memory used by the clusters in bytes,
+    /// every tuple is assumed to be as large as the centroid of the first inserted cluster
+    pub fn estimated_memory(&self) -> usize {
+        self.memory
+    }
+
+    /// key of the cluster that is evicted next by the eviction policy
+    fn eviction_candidate(&self) -> Option<u128> {
+        // the clusters are keyed by their last arrival, so ties go to the least recently used
+        let candidate = match self.eviction_policy {
+            EvictionPolicy::LeastRecentlyUsed => self.cluster_set.iter().next(),
+            EvictionPolicy::Smallest => self
+                .cluster_set
+                .iter()
+                .min_by_key(|(_, cluster)| cluster.w_current.buffer.len()),
+            EvictionPolicy::Oldest => self
+                .cluster_set
+                .iter()
+                .min_by_key(|(_, cluster)| cluster.created),
+        };
+        candidate.map(|(key, _)| *key)
+    }
+
+    /// remove the cluster and flush its tuples. A k-anonymous cluster is released, the tuples of
+    /// a smaller cluster are merged into the nearest cluster with `merge_evicted` or suppressed
+    fn evict(&mut self, key: u128) {
+        let mut cluster = self.remove_cluster(key).unwrap();
+        info!("cluster evicted by {:?}", self.eviction_policy);
+        let policy = self.eviction_policy;
+        self.analysers
+            .iter_mut()
+            .for_each(|analyser| analyser.on_evict(cluster.uuid, policy));
+
+        let unpublished = match cluster.w_current.buffer.len() >= self.k {
+            true => Vec::new(),
+            false if self.merge_evicted && !self.cluster_set.is_empty() => {
+                cluster.take_unpublished()
+            }
+            false => {
+                cluster.suppress_all(
+                    &mut self.publisher,
+                    &mut self.analysers,
+                    &self.sensitive_freq,
+                );
+                Vec::new()
+            }
+        };
+        self.release_cluster(cluster);
+
+        unpublished.into_iter().for_each(|value| {
+            match self.find_closest_cluster(&value.qi, f64::INFINITY) {
+                Some(nearest) => self.add_to_cluster(nearest, value),
+                // the nearest cluster was published and removed while merging the previous tuples
+                None => {
+                    let mut cluster = self.create_new_cluster();
+                    cluster.add_tuple(value);
+                    self.analysers
+                        .iter_mut()
+                        .for_each(|analyser| analyser.on_cluster_created(cluster.uuid));
+                    self.insert_cluster(cluster)
+                }
+            }
+        });
+    }
+
+    /// release all the tuples of a cluster that is removed
+    fn release_cluster(&mut self, mut cluster: Cluster<A, N>) {
+        cluster.release_all(
+            &mut self.publisher,
+            &mut self.analysers,
+            &self.sensitive_freq,
+        );
+        self.analysers
+            .iter_mut()
+            .for_each(|analyser| analyser.on_cluster_removed(cluster.uuid));
+    }
+
+    /// anonymize a record whose QI's have been validated
+    fn anonymize_valid(&mut self, value: Record<A>) {
+        self.observe(&value);
+        let cluster = self.find_best_cluster(&value.qi);
+        self.assign(value, cluster)
+    }
+
+    /// update the statistics of the anonymizer with a record that enters it
+    fn observe(&mut self, value: &Record<A>) {
+        debug!("cluster count: {}", self.cluster_set.len());
+        update_sensitive_frequencies(&mut self.sensitive_freq, &value.value);
+        self.clock.observe(&value.value);
+        self.metric.observe(&value.qi.quasi_identifiers());
+    }
+
+    /// add the record to the cluster that was found for it, or to a new cluster when there is none
+    fn assign(&mut self, value: Record<A>, cluster: Option<Cluster<A, N>>) {
         // Borrowing the right cluster caused multiple ownership problems as we borrow
         // self mutable and immutable.
-        debug!("cluster count: {}", self.cluster_set.len());
-        match self.find_best_cluster(&value) {
+        match cluster {
             // create new cluster
             None => {
                 info!("new cluster created");
                 let mut cluster = self.create_new_cluster();
 
                 cluster.add_tuple(value);
-                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-                self.cluster_set.insert(now.as_nanos(), cluster);
-                self.analysers.iter_mut().for_each(|analyser| {
-                    if let Analyser::ClusterAnalyser(cluster_analyser) = analyser {
-                        cluster_analyser.add_count()
-                    }
-                })
+                self.analysers
+                    .iter_mut()
+                    .for_each(|analyser| analyser.on_cluster_created(cluster.uuid));
+                self.insert_cluster(cluster);
             }
-            Some(mut cluster) => {
+            Some(cluster) => {
                 info!("cluster found");
-                // check life time and change cluster
-                if cluster.check_cluster_life_time() >= self.delta {
-                    cluster.publish_all(&mut self.publisher, &mut self.analysers);
-                    cluster = self.create_new_cluster();
-                    info!("cluster life time delta exceeded")
-                }
+                self.add_to_cluster(cluster, value)
+            }
+        }
+        self.enforce_limits();
+    }
 
-                cluster.add_tuple(value);
+    /// add the tuple to a cluster that was removed from the cluster set, publish its tuples
+    /// and insert it again when it isn't complete
+    fn add_to_cluster(&mut self, mut cluster: Cluster<A, N>, value: Record<A>) {
+        // check life time and change cluster
+        if cluster.check_cluster_life_time() >= self.delta {
+            self.release_cluster(cluster);
+            cluster = self.create_new_cluster();
+            self.analysers
+                .iter_mut()
+                .for_each(|analyser| analyser.on_cluster_created(cluster.uuid));
+            info!("cluster life time delta exceeded")
+        }
 
-                // publishing value when k-anon level is met
-                if cluster.w_current.buffer.len() == self.k {
-                    cluster.publish_all(&mut self.publisher, &mut self.analysers);
-                    info!("k-level is met published all")
-                } else if self.k < cluster.w_current.buffer.len()
-                    && cluster.w_current.buffer.len() <= self.k_max + 1
-                {
-                    // if the cluster contains at least k records
-                    cluster.publish(&mut self.publisher, &mut self.analysers);
-                    info!("publishing")
-                }
+        cluster.add_tuple(value);
 
-                // check if the w_current is full (max size of buffer)
-                // and reuse the buffer if concept drift is not detected
-                if cluster.is_full() {
-                    info!("cluster is full, checking concept drift");
-                    cluster.detect_concept_drift()
-                }
+        // publishing value when k-anon level is met
+        if cluster.w_current.buffer.len() == self.k {
+            cluster.publish_all(
+                &mut self.publisher,
+                &mut self.analysers,
+                &self.sensitive_freq,
+            );
+            info!("k-level is met published all")
+        } else if self.k < cluster.w_current.buffer.len()
+            && cluster.w_current.buffer.len() <= self.k_max + 1
+        {
+            // if the cluster contains at least k records
+            cluster.publish(
+                &mut self.publisher,
+                &mut self.analysers,
+                &self.sensitive_freq,
+            );
+            info!("publishing")
+        }
 
-                // we removed the cluster in the find best cluster method
-                // we need to insert it again
-                match cluster.complete_buffer_amount > self.k_max {
-                    true => {
-                        cluster.publish_all(&mut self.publisher, &mut self.analysers);
-                        info!("cluster is full removing..");
-                        cluster.print_domain_qis().into_iter().enumerate().for_each(
-                            |(index, domain)| match domain {
-                                (
-                                    QuasiIdentifierType::Integer(min),
-                                    QuasiIdentifierType::Integer(max),
-                                ) => {
-                                    debug!("QI {}| min: {:?}| max: {:?}", index + 1, min, max)
-                                }
-                                (
-                                    QuasiIdentifierType::Float(min),
-                                    QuasiIdentifierType::Float(max),
-                                ) => {
-                                    debug!("QI {}| min: {:?}| max: {:?}", index + 1, min, max)
-                                }
-                                _ => panic!("wrong QI"),
-                            },
-                        );
-                        // cluster has already been removed from cluster_set
-                        // and it does not need to be added again
-                        self.analysers.iter_mut().for_each(|analyser| {
-                            if let Analyser::ClusterAnalyser(cluster_analyser) = analyser {
-                                cluster_analyser.remove_count()
-                            }
-                        })
-                    }
-                    false => {
-                        // add the cluster again at its arrival time into the cluster,
-                        // if there already is another cluster there change the arrival time
-                        // and try again
-                        while self.cluster_set.contains_key(&cluster.last_arrival) {
-                            let now = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_nanos();
-                            cluster.last_arrival = now
-                        }
-                        self.cluster_set.insert(cluster.last_arrival, cluster);
-                    }
-                };
+        // check if the w_current is full (max size of buffer)
+        // and reuse the buffer if concept drift is not detected
+        if cluster.is_full() {
+            info!("cluster is full, checking concept drift");
+            // held tuples would be lost when the buffer is reset
+            if cluster.held > 0 {
+                cluster.release_all(
+                    &mut self.publisher,
+                    &mut self.analysers,
+                    &self.sensitive_freq,
+                );
+            }
+            if cluster.detect_concept_drift() {
+                info!("concept drift detected");
+                self.analysers
+                    .iter_mut()
+                    .for_each(|analyser| analyser.on_drift(cluster.uuid))
             }
         }
+
+        // we removed the cluster in the find best cluster method
+        // we need to insert it again
+        match cluster.complete_buffer_amount > self.k_max {
+            true => {
+                cluster.release_all(
+                    &mut self.publisher,
+                    &mut self.analysers,
+                    &self.sensitive_freq,
+                );
+                info!("cluster is full removing..");
+                cluster
+                    .print_domain_qis()
+                    .into_iter()
+                    .enumerate()
+                    .for_each(|(index, domain)| match domain {
+                        Some((
+                            QuasiIdentifierType::Integer(min),
+                            QuasiIdentifierType::Integer(max),
+                        )) => {
+                            debug!("QI {}| min: {:?}| max: {:?}", index + 1, min, max)
+                        }
+                        Some((
+                            QuasiIdentifierType::Float(min),
+                            QuasiIdentifierType::Float(max),
+                        )) => {
+                            debug!("QI {}| min: {:?}| max: {:?}", index + 1, min, max)
+                        }
+                        None => debug!("QI {}| missing", index + 1),
+                        _ => panic!("wrong QI"),
+                    });
+                // cluster has already been removed from cluster_set
+                // and it does not need to be added again
+                self.analysers
+                    .iter_mut()
+                    .for_each(|analyser| analyser.on_cluster_removed(cluster.uuid))
+            }
+            false => self.insert_cluster(cluster),
+        };
     }
 
     /// finding best cluster looking at the threshold
@@ -178,58 +704,595 @@ where
     /// TODO: create a check here for old cluster so that the looping through the cluster_set
     /// is only done once, here we can improve massively on speed to use async to publishing the cluster set concurrently while
     /// looping further maybe?
-    fn find_best_cluster(&mut self, value: &A) -> Option<Cluster<A, N>> {
-        // remove the cluster from self and return it
-        let mut best_cluster: Option<Cluster<A, N>> = None;
-        let best_cluster_key: Mutex<Option<u128>> = Mutex::new(None);
-        let least_info_loss: Mutex<Option<f64>> = Mutex::new(None);
-
-        self.cluster_set.par_iter().for_each(|(key, a)| {
-            if a.centroid.calculate_difference(value) <= self.diff_thres {
-                let info_loss = value.calculate_info_loss(&a.centroid);
-                let mut least_info = least_info_loss.lock().unwrap();
-                match *least_info {
-                    None => {
-                        *least_info = Some(info_loss);
-                        *best_cluster_key.lock().unwrap() = Some(*key)
-                    }
-                    Some(current_info_loss) => {
-                        if info_loss < current_info_loss {
-                            *least_info = Some(info_loss);
-                            *best_cluster_key.lock().unwrap() = Some(*key)
-                        }
-                    }
-                }
-            }
-        });
+    fn find_best_cluster(&mut self, value: &QiColumns) -> Option<Cluster<A, N>> {
+        self.find_closest_cluster(value, self.diff_thres)
+    }
 
-        // remove the key from the hashmap and set the best_cluster
-        // variable
-        if let Some(key) = *best_cluster_key.lock().unwrap() {
-            best_cluster = self.cluster_set.remove(&key);
+    /// remove the cluster closest to the tuple within the maximal distance from the cluster set
+    fn find_closest_cluster(
+        &mut self,
+        value: &QiColumns,
+        max_distance: f64,
+    ) -> Option<Cluster<A, N>> {
+        // the cluster set is public and can be changed without the index
+        if self.index.len() != self.cluster_set.len() {
+            self.rebuild_index()
         }
 
-        best_cluster
+        let key = closest_key(
+            &self.cluster_set,
+            &self.index,
+            self.metric.as_ref(),
+            value,
+            max_distance,
+        );
+        key.and_then(|key| self.remove_cluster(key))
+    }
+
+    /// remove the best cluster of a tuple of a batch from the cluster set, with the cluster
+    /// that was looked up before the batch was anonymized. Only the clusters that were removed
+    /// or inserted since then are compared with the tuple again
+    fn find_looked_up_cluster(
+        &mut self,
+        value: &QiColumns,
+        candidate: Option<u128>,
+    ) -> Option<Cluster<A, N>> {
+        let changes = self.changes.as_ref().unwrap();
+        if matches!(candidate, Some(key) if changes.keys.contains(&key)) {
+            // the candidate changed, the nearest unchanged cluster is unknown
+            return self.find_best_cluster(value);
+        }
+
+        let metric = self.metric.as_ref();
+        let distance = |key: u128| {
+            let centroid_qi = self.cluster_set[&key].centroid_qi();
+            (metric.qi_difference(&centroid_qi, value), key)
+        };
+        // only the changed clusters can be closer than the candidate
+        let changed: Vec<u128> = match metric.supports_lower_bound() {
+            true => nearest_key(
+                &self.cluster_set,
+                &changes.index,
+                metric,
+                value,
+                self.diff_thres,
+            )
+            .into_iter()
+            .collect(),
+            false => changes
+                .keys
+                .iter()
+                .copied()
+                .filter(|key| self.cluster_set.contains_key(key))
+                .collect(),
+        };
+
+        // ties go to the oldest cluster like in `closest_key`
+        let key = candidate
+            .into_iter()
+            .chain(changed)
+            .map(distance)
+            .filter(|(distance, _)| *distance <= self.diff_thres)
+            .min_by(|(x, x_key), (y, y_key)| x.total_cmp(y).then(x_key.cmp(y_key)))
+            .map(|(_, key)| key);
+        key.and_then(|key| self.remove_cluster(key))
+    }
+
+    fn remove_cluster(&mut self, key: u128) -> Option<Cluster<A, N>> {
+        if let Some(changes) = &mut self.changes {
+            changes.keys.insert(key);
+            changes.index.remove(key);
+        }
+        self.index.remove(key);
+        let cluster = self.cluster_set.remove(&key)?;
+        if let Some(tuple_size) = self.tuple_size {
+            self.memory = self
+                .memory
+                .saturating_sub(cluster.estimated_size(tuple_size))
+        }
+        Some(cluster)
+    }
+
+    /// rebuild the index and the memory estimate after the cluster set was replaced
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        self.cluster_set.iter().for_each(|(key, cluster)| {
+            self.index.insert(
+                *key,
+                cluster_index::point(&cluster.centroid_qi().quasi_identifiers()),
+            )
+        });
+        self.tuple_size = self.cluster_set.values().next().map(tuple_size);
+        self.memory = match self.tuple_size {
+            Some(tuple_size) => self
+                .cluster_set
+                .values()
+                .map(|cluster| cluster.estimated_size(tuple_size))
+                .sum(),
+            None => 0,
+        }
+    }
+
+    /// add the cluster at its last arrival time into the cluster set,
+    /// if there already is another cluster there move the arrival time and try again
+    fn insert_cluster(&mut self, mut cluster: Cluster<A, N>) {
+        while self.cluster_set.contains_key(&cluster.last_arrival) {
+            cluster.last_arrival += 1
+        }
+        self.index.insert(
+            cluster.last_arrival,
+            cluster_index::point(&cluster.centroid_qi().quasi_identifiers()),
+        );
+        if let Some(changes) = &mut self.changes {
+            changes.keys.insert(cluster.last_arrival);
+            changes.index.insert(
+                cluster.last_arrival,
+                cluster_index::point(&cluster.centroid_qi().quasi_identifiers()),
+            );
+        }
+        let tuple_size = *self
+            .tuple_size
+            .get_or_insert_with(|| tuple_size::<A, N>(&cluster));
+        self.memory += cluster.estimated_size(tuple_size);
+        self.cluster_set.insert(cluster.last_arrival, cluster);
     }
 
     /// create new cluster
-    fn create_new_cluster(&self) -> Cluster<A, N> {
-        Cluster::new(
-            self.k,
-            self.l,
-            self.c,
-            self.buffer_size,
-            self.noiser.clone(),
-        )
+    fn create_new_cluster(&mut self) -> Cluster<A, N> {
+        let seed = self.rng.next_u64();
+        Cluster {
+            created: self.clock.now(),
+            t: self.t,
+            metric: Arc::clone(&self.metric),
+            output_mode: self.output_mode,
+            suppression_strategy: self.suppression_strategy,
+            clock: Arc::clone(&self.clock),
+            ..Cluster::new(
+                self.k,
+                self.l,
+                self.c,
+                self.buffer_size,
+                self.noiser.clone(),
+            )
+        }
+        .with_seed(seed)
     }
 }
 
+/// key of the cluster closest to the tuple within the maximal distance
+fn closest_key<A: Anonymizable, N: Noiser>(
+    cluster_set: &BTreeMap<u128, Cluster<A, N>>,
+    index: &ClusterIndex,
+    metric: &dyn DistanceMetric,
+    value: &QiColumns,
+    max_distance: f64,
+) -> Option<u128> {
+    // the index can only skip clusters when the metric has a lower bound
+    match metric.supports_lower_bound() {
+        true => nearest_key(cluster_set, index, metric, value, max_distance),
+        false => scan_closest_key(cluster_set, metric, value, max_distance),
+    }
+}
+
+/// key of the cluster in the index closest to the tuple within the maximal distance,
+/// for metrics with a lower bound
+fn nearest_key<A: Anonymizable, N: Noiser>(
+    cluster_set: &BTreeMap<u128, Cluster<A, N>>,
+    index: &ClusterIndex,
+    metric: &dyn DistanceMetric,
+    value: &QiColumns,
+    max_distance: f64,
+) -> Option<u128> {
+    let qi = value.quasi_identifiers();
+    let axes: Vec<Axis> = qi.iter().map(Axis::new).collect();
+    index.nearest(
+        &cluster_index::point(&qi),
+        &axes,
+        max_distance,
+        metric,
+        |key| metric.qi_difference(&cluster_set[&key].centroid_qi(), value),
+    )
+}
+
+/// compare the tuple with every cluster, for metrics without a lower bound
+fn scan_closest_key<A: Anonymizable, N: Noiser>(
+    cluster_set: &BTreeMap<u128, Cluster<A, N>>,
+    metric: &dyn DistanceMetric,
+    value: &QiColumns,
+    max_distance: f64,
+) -> Option<u128> {
+    let best_cluster_key: Mutex<Option<u128>> = Mutex::new(None);
+    let least_distance: Mutex<Option<f64>> = Mutex::new(None);
+
+    cluster_set.par_iter().for_each(|(key, a)| {
+        let distance = metric.qi_difference(&a.centroid_qi(), value);
+        if distance <= max_distance {
+            let mut least = least_distance.lock().unwrap();
+            match *least {
+                None => {
+                    *least = Some(distance);
+                    *best_cluster_key.lock().unwrap() = Some(*key)
+                }
+                Some(current_distance) => {
+                    // ties go to the oldest cluster, so the choice doesn't depend on the threads
+                    let mut best_key = best_cluster_key.lock().unwrap();
+                    if distance < current_distance
+                        || (distance == current_distance && Some(*key) < *best_key)
+                    {
+                        *least = Some(distance);
+                        *best_key = Some(*key)
+                    }
+                }
+            }
+        }
+    });
+
+    best_cluster_key.into_inner().unwrap()
+}
+
+/// rough estimate of the bytes of a tuple, assuming it is as large as the centroid of the cluster
+fn tuple_size<A: Anonymizable, N: Noiser>(cluster: &Cluster<A, N>) -> usize {
+    std::mem::size_of::<A>()
+        + bincode::serialized_size(&cluster.centroid).unwrap_or_default() as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::MicroaggAnonymizer;
+    use crate::analysis::analyser::{Analyser, PublishContext};
+    use crate::anonymization::clock::TimeMode;
+    use crate::anonymization::eviction::EvictionPolicy;
+    use crate::anonymization::record::QiColumns;
+    use crate::data_manipulation::anonymizable::Anonymizable;
+    use crate::data_manipulation::distance::DistanceMetricType;
     use crate::data_manipulation::mueller::MuellerStream;
+    use crate::data_manipulation::mueller::StrmMeta;
+    use crate::error::{DiffPrivError, RecordErrorPolicy};
     use crate::noise::laplace::laplace_noiser::LaplaceNoiser;
+    use crate::publishing::publisher::Publisher;
     use crate::test::dummy_publisher::DummyPublisher;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn builder_defaults() {
+        let anonymizer: MicroaggAnonymizer<LaplaceNoiser, MuellerStream, DummyPublisher> =
+            MicroaggAnonymizer::builder(DummyPublisher::default(), LaplaceNoiser::new(0.1, 3, 0.1))
+                .k(3)
+                .l(3)
+                .c(2)
+                .delta(10)
+                .build()
+                .unwrap();
+
+        assert_eq!((anonymizer.k, anonymizer.k_max), (3, 12));
+        assert_eq!((anonymizer.l, anonymizer.c), (3, 2));
+        assert_eq!(anonymizer.buffer_size, 9);
+        assert_eq!(anonymizer.delta, 10 * 1000000000)
+    }
+
+    /// counts the published tuples per cluster
+    #[derive(Default)]
+    struct TuplesPerCluster {
+        published: std::collections::HashMap<uuid::Uuid, usize>,
+    }
+
+    impl Analyser<MuellerStream> for TuplesPerCluster {
+        fn name(&self) -> &str {
+            "tuples_per_cluster"
+        }
+
+        fn report(&self) -> serde_json::Value {
+            serde_json::json!(self.published.values().sum::<usize>())
+        }
+
+        fn on_publish(
+            &mut self,
+            _original: &MuellerStream,
+            _published: &MuellerStream,
+            ctx: &mut PublishContext,
+        ) {
+            *self.published.entry(ctx.cluster).or_insert(0) += 1
+        }
+
+        fn on_cluster_created(&mut self, cluster: uuid::Uuid) {
+            self.published.insert(cluster, 0);
+        }
+    }
+
+    #[test]
+    fn user_defined_analyser() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut anonymizer: MicroaggAnonymizer<LaplaceNoiser, MuellerStream, DummyPublisher> =
+            MicroaggAnonymizer::new(2, 10, 2, 2, 0.65, 10, 5, DummyPublisher::default(), noiser)
+                .with_analyser(Box::new(TuplesPerCluster::default()));
+
+        (0..2).for_each(|_| {
+            anonymizer.anonymize(MuellerStream {
+                age: Some(30),
+                gender: Some("male".to_string()),
+                ..MuellerStream::default()
+            })
+        });
+
+        let report = anonymizer.report();
+        assert_eq!(report["tuples_per_cluster"], serde_json::json!(2));
+        assert_eq!(
+            report["clusters"],
+            serde_json::json!({ "created": 1, "deleted": 0, "drifts": 0, "evicted": 0 })
+        );
+        assert_eq!(report.len(), 7)
+    }
+
+    /// replay two tuples where the second one happens 20 seconds after the first one
+    fn replay(
+        time_mode: TimeMode,
+    ) -> MicroaggAnonymizer<LaplaceNoiser, MuellerStream, DummyPublisher> {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let publisher = DummyPublisher::default();
+        let mut anonymizer = MicroaggAnonymizer::new(2, 10, 2, 2, 0.65, 10, 5, publisher, noiser)
+            .with_time_mode(time_mode);
+
+        [0, 20000].into_iter().for_each(|timestamp| {
+            anonymizer.anonymize(MuellerStream {
+                age: Some(30),
+                gender: Some("male".to_string()),
+                strm_meta: StrmMeta {
+                    timestamp: Some(timestamp),
+                    ..StrmMeta::default()
+                },
+                ..MuellerStream::default()
+            })
+        });
+        anonymizer
+    }
+
+    #[test]
+    fn event_time_life_time() {
+        // the life time delta of 10 seconds is only exceeded in event time
+        let anonymizer = replay(TimeMode::ProcessingTime);
+        assert_eq!(anonymizer.report()["clusters"]["created"], 1);
+
+        let anonymizer = replay(TimeMode::EventTime);
+        assert_eq!(anonymizer.report()["clusters"]["created"], 2);
+        assert_eq!(anonymizer.report()["clusters"]["deleted"], 1);
+        assert_eq!(anonymizer.clock.now(), 20 * 1000000000)
+    }
+
+    /// records the serialized tuples and their cluster
+    #[derive(Default)]
+    struct RecordingPublisher {
+        published: Vec<String>,
+    }
+
+    impl Publisher for RecordingPublisher {
+        fn publish<M: Anonymizable>(&mut self, value: M, uuid: uuid::Uuid, _dr: f64) {
+            self.published.push(format!(
+                "{} {}",
+                uuid,
+                serde_json::to_string(&value).unwrap()
+            ))
+        }
+    }
+
+    fn seeded_anonymizer(
+        seed: u64,
+    ) -> MicroaggAnonymizer<LaplaceNoiser, MuellerStream, RecordingPublisher> {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        MicroaggAnonymizer::builder(RecordingPublisher::default(), noiser)
+            .k(2)
+            .diff_thres(0.65)
+            .seed(seed)
+            .build()
+            .unwrap()
+    }
+
+    fn tuple(i: i32) -> MuellerStream {
+        MuellerStream {
+            age: Some(20 + i % 7),
+            gender: Some(["male", "female"][i as usize % 2].to_string()),
+            time_generated: std::time::UNIX_EPOCH,
+            ..MuellerStream::default()
+        }
+    }
+
+    fn seeded_run(seed: u64) -> Vec<String> {
+        let mut anonymizer = seeded_anonymizer(seed);
+        (0..40).for_each(|i| anonymizer.anonymize(tuple(i)));
+        anonymizer.publisher.published
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let run = seeded_run(7);
+
+        assert!(!run.is_empty());
+        assert_eq!(run, seeded_run(7));
+        assert_ne!(run, seeded_run(8))
+    }
+
+    fn batch_run(metric: DistanceMetricType, batch: bool) -> (Vec<String>, usize) {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let mut anonymizer: MicroaggAnonymizer<_, MuellerStream, _> =
+            MicroaggAnonymizer::builder(RecordingPublisher::default(), noiser)
+                .k(3)
+                .diff_thres(0.1)
+                .distance_metric(metric.build())
+                .seed(7)
+                .build()
+                .unwrap();
+        // more tuples than a batch, spread over many clusters
+        let tuples = (0..2500).map(|i| MuellerStream {
+            age: Some(18 + i * 37 % 60),
+            ..tuple(i)
+        });
+        match batch {
+            true => anonymizer.anonymize_batch(tuples),
+            false => tuples.for_each(|x| anonymizer.anonymize(x)),
+        }
+        (anonymizer.publisher.published, anonymizer.cluster_set.len())
+    }
+
+    #[test]
+    fn anonymize_batch_matches_sequential() {
+        [DistanceMetricType::Gower, DistanceMetricType::Mahalanobis]
+            .into_iter()
+            .for_each(|metric| {
+                let sequential = batch_run(metric, false);
+                assert!(sequential.0.len() > 1000);
+                assert_eq!(batch_run(metric, true), sequential)
+            })
+    }
+
+    #[test]
+    fn anonymize_batch_abort() {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::Abort);
+        let result = anonymizer.try_anonymize_batch([tuple(0), bad_record(), tuple(1)]);
+
+        assert!(matches!(result, Err(DiffPrivError::UnknownCategory(_))));
+        // the tuples after the bad record aren't anonymized
+        let tuples: usize = anonymizer
+            .cluster_set
+            .values()
+            .map(|cluster| cluster.w_current.buffer.len())
+            .sum();
+        assert_eq!(tuples, 1)
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let path = std::env::temp_dir().join(format!("{}.snapshot", uuid::Uuid::new_v4()));
+        let mut anonymizer = seeded_anonymizer(7);
+        (0..15).for_each(|i| anonymizer.anonymize(tuple(i)));
+        anonymizer.snapshot(&path).unwrap();
+        let published = anonymizer.publisher.published.len();
+
+        // the seed of the snapshot replaces the seed of the restored anonymizer
+        let mut restored = seeded_anonymizer(8);
+        restored.restore(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.cluster_set.len(), anonymizer.cluster_set.len());
+        assert_eq!(restored.sensitive_freq, anonymizer.sensitive_freq);
+
+        (15..40).for_each(|i| {
+            anonymizer.anonymize(tuple(i));
+            restored.anonymize(tuple(i))
+        });
+        assert!(!restored.publisher.published.is_empty());
+        assert_eq!(
+            restored.publisher.published,
+            anonymizer.publisher.published[published..]
+        );
+
+        assert!(matches!(
+            restored.restore(&path),
+            Err(DiffPrivError::Snapshot(_))
+        ))
+    }
+
+    #[test]
+    fn flush_expired() {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let publisher = DummyPublisher::default();
+        let mut anonymizer = MicroaggAnonymizer::new(2, 10, 2, 2, 0.65, 10, 5, publisher, noiser)
+            .with_time_mode(TimeMode::EventTime);
+        anonymizer.anonymize(MuellerStream {
+            age: Some(30),
+            gender: Some("male".to_string()),
+            strm_meta: StrmMeta {
+                timestamp: Some(0),
+                ..StrmMeta::default()
+            },
+            ..MuellerStream::default()
+        });
+
+        assert_eq!(anonymizer.tick(UNIX_EPOCH + Duration::from_secs(5)), 0);
+        assert_eq!(anonymizer.cluster_set.len(), 1);
+
+        // the single tuple isn't diverse and is suppressed when its cluster expires
+        assert_eq!(anonymizer.tick(UNIX_EPOCH + Duration::from_secs(10)), 1);
+        assert!(anonymizer.cluster_set.is_empty());
+        let report = anonymizer.report();
+        assert_eq!(report["clusters"]["deleted"], 1);
+        assert_eq!(report["suppression"]["suppressed"]["uniform_sample"], 1);
+        assert_eq!(report["publishing_delay"], 10 * 1000000000_u64)
+    }
+
+    fn evicting_anonymizer(
+        merge_evicted: bool,
+    ) -> MicroaggAnonymizer<LaplaceNoiser, MuellerStream, DummyPublisher> {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::Abort)
+            .with_max_clusters(1)
+            .with_merge_evicted(merge_evicted);
+        anonymizer.anonymize(MuellerStream {
+            id: "1".to_string(),
+            age: Some(30),
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        });
+        anonymizer.anonymize(MuellerStream {
+            id: "2".to_string(),
+            age: Some(50),
+            gender: Some("female".to_string()),
+            ..MuellerStream::default()
+        });
+        anonymizer
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        // the male cluster is evicted when the female one is created, its single tuple is suppressed
+        let anonymizer = evicting_anonymizer(false);
+        assert_eq!(anonymizer.cluster_set.len(), 1);
+        let cluster = anonymizer.cluster_set.values().next().unwrap();
+        assert_eq!(cluster.centroid.gender, Some("female".to_string()));
+        let report = anonymizer.report();
+        assert_eq!(report["clusters"]["evicted"], 1);
+        assert_eq!(report["clusters"]["deleted"], 1);
+        assert_eq!(report["suppression"]["suppressed"]["uniform_sample"], 1)
+    }
+
+    #[test]
+    fn evict_merge() {
+        // the tuple of the evicted cluster completes the female cluster instead of being suppressed
+        let anonymizer = evicting_anonymizer(true);
+        let report = anonymizer.report();
+        assert_eq!(report["clusters"]["evicted"], 1);
+        assert_eq!(report["suppression"]["published"], 2);
+        assert_eq!(report["suppression"]["suppressed"], serde_json::json!({}))
+    }
+
+    #[test]
+    fn evict_memory_budget() {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::Abort)
+            .with_eviction_policy(EvictionPolicy::Smallest)
+            .with_memory_budget(1);
+        anonymizer.anonymize(MuellerStream {
+            age: Some(30),
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        });
+        assert!(anonymizer.cluster_set.is_empty());
+        assert_eq!(anonymizer.estimated_memory(), 0);
+        assert_eq!(anonymizer.report()["clusters"]["evicted"], 1)
+    }
+
+    #[test]
+    fn running_memory_estimate() {
+        let mut anonymizer = seeded_anonymizer(3).with_memory_budget(usize::MAX);
+        (0..60).for_each(|i| anonymizer.anonymize(tuple(i)));
+        assert!(!anonymizer.cluster_set.is_empty());
+
+        // the estimate kept on insert and remove is the one of the whole cluster set
+        let tuple_size = anonymizer.tuple_size.unwrap();
+        let recounted: usize = anonymizer
+            .cluster_set
+            .values()
+            .map(|cluster| cluster.estimated_size(tuple_size))
+            .sum();
+        assert_eq!(anonymizer.estimated_memory(), recounted);
+        anonymizer.flush_all();
+        assert_eq!(anonymizer.estimated_memory(), 0)
+    }
 
     #[test]
     fn find_best_cluster() {
@@ -244,7 +1307,9 @@ mod tests {
             ..MuellerStream::default()
         };
 
-        assert!(anonymizer.find_best_cluster(&mueller1).is_none());
+        assert!(anonymizer
+            .find_best_cluster(&QiColumns::extract(&mueller1))
+            .is_none());
 
         anonymizer.anonymize(mueller1);
 
@@ -256,7 +1321,9 @@ mod tests {
             ..MuellerStream::default()
         };
 
-        assert!(anonymizer.find_best_cluster(&mueller2).is_some());
+        assert!(anonymizer
+            .find_best_cluster(&QiColumns::extract(&mueller2))
+            .is_some());
 
         anonymizer.anonymize(mueller2);
 
@@ -272,4 +1339,96 @@ mod tests {
 
         assert_eq!(anonymizer.cluster_set.len(), 2)
     }
+
+    #[test]
+    fn find_best_cluster_distance_metric() {
+        let male = MuellerStream {
+            age: Some(30),
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        };
+        let female = MuellerStream {
+            gender: Some("female".to_string()),
+            ..male.clone()
+        };
+
+        // gower averages the gender difference over both QI's: 0.5
+        let mut gower = create_anonymizer(RecordErrorPolicy::Abort);
+        gower.anonymize(male.clone());
+        assert!(gower
+            .find_best_cluster(&QiColumns::extract(&female))
+            .is_some());
+
+        // manhattan sums them: 1.0, which is above the threshold
+        let mut manhattan = create_anonymizer(RecordErrorPolicy::Abort)
+            .with_distance_metric(DistanceMetricType::Manhattan.build());
+        manhattan.anonymize(male);
+        assert!(manhattan
+            .find_best_cluster(&QiColumns::extract(&female))
+            .is_none())
+    }
+
+    fn create_anonymizer(
+        policy: RecordErrorPolicy,
+    ) -> MicroaggAnonymizer<LaplaceNoiser, MuellerStream, DummyPublisher> {
+        let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
+        let publisher = DummyPublisher::default();
+        MicroaggAnonymizer::new(2, 10, 2, 2, 0.65, 10, 5, publisher, noiser)
+            .with_record_error_policy(policy)
+    }
+
+    fn bad_record() -> MuellerStream {
+        MuellerStream {
+            age: Some(30),
+            gender: Some("unknown".to_string()),
+            ..MuellerStream::default()
+        }
+    }
+
+    #[test]
+    fn try_anonymize_abort() {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::Abort);
+
+        assert_eq!(
+            anonymizer.try_anonymize(bad_record()),
+            Err(DiffPrivError::UnknownCategory(
+                "`unknown` of `gender`".to_string()
+            ))
+        );
+        assert!(anonymizer.cluster_set.is_empty())
+    }
+
+    #[test]
+    fn try_anonymize_skip() {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::Skip);
+
+        assert!(anonymizer.try_anonymize(bad_record()).is_ok());
+        assert!(anonymizer.cluster_set.is_empty());
+        assert!(anonymizer.dead_letters.is_empty());
+
+        anonymizer.anonymize(MuellerStream {
+            age: Some(30),
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        });
+        assert_eq!(anonymizer.cluster_set.len(), 1)
+    }
+
+    #[test]
+    fn try_anonymize_dead_letter() {
+        let mut anonymizer = create_anonymizer(RecordErrorPolicy::DeadLetter);
+
+        assert!(anonymizer.try_anonymize(bad_record()).is_ok());
+        assert!(anonymizer.cluster_set.is_empty());
+
+        let (record, error) = anonymizer.dead_letters.pop().unwrap();
+        assert_eq!(record.gender, bad_record().gender);
+        assert!(matches!(error, DiffPrivError::UnknownCategory(_)))
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown category")]
+    fn anonymize_panics_on_abort() {
+        create_anonymizer(RecordErrorPolicy::Abort).anonymize(bad_record())
+    }
 }
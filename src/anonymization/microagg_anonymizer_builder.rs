@@ -0,0 +1,336 @@
+use crate::analysis::analyser::Analyser;
+use crate::anonymization::clock::TimeMode;
+use crate::anonymization::eviction::EvictionPolicy;
+use crate::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+use crate::anonymization::suppression::SuppressionStrategy;
+use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::data_manipulation::distance::{DistanceMetric, Gower};
+use crate::error::{DiffPrivError, RecordErrorPolicy};
+use crate::noise::noiser::Noiser;
+use crate::publishing::generalized::OutputMode;
+use crate::publishing::publisher::Publisher;
+use std::sync::Arc;
+
+/// Builder of a `MicroaggAnonymizer` with named parameters, `build` checks that the
+/// parameters can be combined. `k_max` defaults to 4k and `buffer_size` to 3k
+pub struct MicroaggAnonymizerBuilder<N, A, P>
+where
+    N: Noiser,
+    A: Anonymizable,
+    P: Publisher,
+{
+    k: usize,
+    k_max: Option<usize>,
+    l: usize,
+    c: i32,
+    t: Option<f64>,
+    diff_thres: f64,
+    delta: u128, // life time delta in seconds
+    buffer_size: Option<usize>,
+    publisher: P,
+    noiser: N,
+    record_error_policy: RecordErrorPolicy,
+    metric: Arc<dyn DistanceMetric>,
+    output_mode: OutputMode,
+    suppression_strategy: SuppressionStrategy,
+    time_mode: TimeMode,
+    seed: Option<u64>, // the anonymizer is seeded from the OS when None
+    max_clusters: Option<usize>,
+    memory_budget: Option<usize>, // in bytes
+    eviction_policy: EvictionPolicy,
+    merge_evicted: bool,
+    analysers: Vec<Box<dyn Analyser<A>>>, // registered next to the default analysers
+}
+
+impl<N, A, P> MicroaggAnonymizerBuilder<N, A, P>
+where
+    N: Noiser,
+    A: Anonymizable,
+    P: Publisher,
+{
+    pub fn new(publisher: P, noiser: N) -> Self {
+        Self {
+            k: 5,
+            k_max: None,
+            l: 2,
+            c: 7,
+            t: None,
+            diff_thres: 0.1,
+            delta: 200,
+            buffer_size: None,
+            publisher,
+            noiser,
+            record_error_policy: RecordErrorPolicy::default(),
+            metric: Arc::new(Gower),
+            output_mode: OutputMode::default(),
+            suppression_strategy: SuppressionStrategy::default(),
+            time_mode: TimeMode::default(),
+            seed: None,
+            max_clusters: None,
+            memory_budget: None,
+            eviction_policy: EvictionPolicy::default(),
+            merge_evicted: false,
+            analysers: Vec::new(),
+        }
+    }
+
+    /// k-anonymity level, defaults to 5
+    pub fn k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// the maximum amount of tuples a cluster can have before being removed, defaults to 4k
+    pub fn k_max(mut self, k_max: usize) -> Self {
+        self.k_max = Some(k_max);
+        self
+    }
+
+    /// l-diversity level, defaults to 2
+    pub fn l(mut self, l: usize) -> Self {
+        self.l = l;
+        self
+    }
+
+    /// recursive (c,l)-diversity, defaults to 7
+    pub fn c(mut self, c: i32) -> Self {
+        self.c = c;
+        self
+    }
+
+    /// t-closeness level, not checked when not set
+    pub fn t_closeness(mut self, t: f64) -> Self {
+        self.t = Some(t);
+        self
+    }
+
+    /// the maximum distance between a tuple and the centroid of its cluster, defaults to 0.1
+    pub fn diff_thres(mut self, diff_thres: f64) -> Self {
+        self.diff_thres = diff_thres;
+        self
+    }
+
+    /// the maximum time in seconds a cluster can exist without new tuples, defaults to 200
+    pub fn delta(mut self, delta: u128) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    /// the amount of tuples used to detect concept drift, defaults to 3k. The centroid of tuples
+    /// with `MissingValuePolicy::Impute` aggregates the whole buffer again for every new tuple,
+    /// because the imputed values change with the centroid
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// what to do with records that can't be anonymized
+    pub fn record_error_policy(mut self, record_error_policy: RecordErrorPolicy) -> Self {
+        self.record_error_policy = record_error_policy;
+        self
+    }
+
+    /// distance between tuples and centroids, defaults to `Gower`
+    pub fn distance_metric(mut self, metric: Arc<dyn DistanceMetric>) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// publish microaggregated tuples, generalized records or both
+    pub fn output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    /// what happens with tuples of clusters that aren't diverse
+    pub fn suppression_strategy(mut self, suppression_strategy: SuppressionStrategy) -> Self {
+        self.suppression_strategy = suppression_strategy;
+        self
+    }
+
+    /// measure the cluster life time delta and the publishing delay in processing or event time
+    pub fn time_mode(mut self, time_mode: TimeMode) -> Self {
+        self.time_mode = time_mode;
+        self
+    }
+
+    /// seed of the noise, the suppressed values and the cluster uuids, defaults to a seed from the OS
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// evict clusters above this count, unbounded by default
+    pub fn max_clusters(mut self, max_clusters: usize) -> Self {
+        self.max_clusters = Some(max_clusters);
+        self
+    }
+
+    /// evict clusters when their estimated memory exceeds the budget in bytes, unbounded by default
+    pub fn memory_budget(mut self, memory_budget: usize) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// which cluster is evicted first, defaults to `EvictionPolicy::LeastRecentlyUsed`
+    pub fn eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// merge the tuples of evicted clusters below k into the nearest cluster instead of suppressing them
+    pub fn merge_evicted(mut self, merge_evicted: bool) -> Self {
+        self.merge_evicted = merge_evicted;
+        self
+    }
+
+    /// register an analyser next to the default ones
+    pub fn analyser(mut self, analyser: Box<dyn Analyser<A>>) -> Self {
+        self.analysers.push(analyser);
+        self
+    }
+
+    /// create the anonymizer, returns `DiffPrivError::InvalidParameter` when
+    /// the parameters can't be combined
+    pub fn build(self) -> Result<MicroaggAnonymizer<N, A, P>, DiffPrivError> {
+        let k_max = self.k_max.unwrap_or(self.k * 4);
+        let buffer_size = self.buffer_size.unwrap_or(self.k * 3);
+        self.validate(k_max, buffer_size)?;
+        self.noiser.validate()?;
+
+        let mut anonymizer = MicroaggAnonymizer::new(
+            self.k,
+            k_max,
+            self.l,
+            self.c,
+            self.diff_thres,
+            self.delta,
+            buffer_size,
+            self.publisher,
+            self.noiser,
+        )
+        .with_record_error_policy(self.record_error_policy)
+        .with_distance_metric(self.metric)
+        .with_output_mode(self.output_mode)
+        .with_suppression_strategy(self.suppression_strategy)
+        .with_time_mode(self.time_mode)
+        .with_eviction_policy(self.eviction_policy)
+        .with_merge_evicted(self.merge_evicted);
+        anonymizer.analysers.extend(self.analysers);
+        if let Some(seed) = self.seed {
+            anonymizer = anonymizer.with_seed(seed);
+        }
+        if let Some(max_clusters) = self.max_clusters {
+            anonymizer = anonymizer.with_max_clusters(max_clusters);
+        }
+        if let Some(memory_budget) = self.memory_budget {
+            anonymizer = anonymizer.with_memory_budget(memory_budget);
+        }
+
+        Ok(match self.t {
+            Some(t) => anonymizer.with_t_closeness(t),
+            None => anonymizer,
+        })
+    }
+
+    fn validate(&self, k_max: usize, buffer_size: usize) -> Result<(), DiffPrivError> {
+        let invalid = |message: String| Err(DiffPrivError::InvalidParameter(message));
+        if self.k == 0 {
+            return invalid("k must be at least 1".to_string());
+        }
+        if k_max < self.k {
+            return invalid(format!("k_max ({}) must be at least k ({})", k_max, self.k));
+        }
+        if self.l == 0 {
+            return invalid("l must be at least 1".to_string());
+        }
+        if self.l > k_max {
+            return invalid(format!(
+                "l ({}) can't be larger than k_max ({}), clusters would never be diverse",
+                self.l, k_max
+            ));
+        }
+        if self.c <= 0 {
+            return invalid(format!("c must be positive, got {}", self.c));
+        }
+        if let Some(t) = self.t {
+            if !(0.0..=1.0).contains(&t) {
+                return invalid(format!("t must be between 0 and 1, got {}", t));
+            }
+        }
+        if self.diff_thres.is_nan() || self.diff_thres < 0.0 {
+            return invalid(format!(
+                "diff_thres can't be negative, got {}",
+                self.diff_thres
+            ));
+        }
+        if self.delta == 0 {
+            return invalid("delta must be at least 1 second".to_string());
+        }
+        if buffer_size < self.k {
+            return invalid(format!(
+                "buffer_size ({}) must be at least k ({})",
+                buffer_size, self.k
+            ));
+        }
+        if self.max_clusters == Some(0) {
+            return invalid("max_clusters must be at least 1".to_string());
+        }
+        if self.memory_budget == Some(0) {
+            return invalid("memory_budget must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MicroaggAnonymizerBuilder;
+    use crate::data_manipulation::mueller::MuellerStream;
+    use crate::error::DiffPrivError;
+    use crate::noise::laplace::laplace_noiser::LaplaceNoiser;
+    use crate::test::dummy_publisher::DummyPublisher;
+
+    fn builder() -> MicroaggAnonymizerBuilder<LaplaceNoiser, MuellerStream, DummyPublisher> {
+        MicroaggAnonymizerBuilder::new(DummyPublisher::default(), LaplaceNoiser::new(0.1, 3, 0.1))
+    }
+
+    fn error_message(
+        builder: MicroaggAnonymizerBuilder<LaplaceNoiser, MuellerStream, DummyPublisher>,
+    ) -> String {
+        match builder.build() {
+            Err(DiffPrivError::InvalidParameter(message)) => message,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("invalid parameters were accepted"),
+        }
+    }
+
+    #[test]
+    fn build_invalid_parameters() {
+        assert_eq!(error_message(builder().k(0)), "k must be at least 1");
+        assert_eq!(
+            error_message(builder().k(5).k_max(4)),
+            "k_max (4) must be at least k (5)"
+        );
+        assert_eq!(
+            error_message(builder().k(5).buffer_size(3)),
+            "buffer_size (3) must be at least k (5)"
+        );
+        assert_eq!(error_message(builder().c(0)), "c must be positive, got 0");
+        assert_eq!(
+            error_message(builder().max_clusters(0)),
+            "max_clusters must be at least 1"
+        );
+        assert_eq!(
+            error_message(builder().t_closeness(1.5)),
+            "t must be between 0 and 1, got 1.5"
+        );
+        assert_eq!(
+            error_message(MicroaggAnonymizerBuilder::<_, MuellerStream, _>::new(
+                DummyPublisher::default(),
+                LaplaceNoiser::new(0.0, 3, 0.1)
+            )),
+            "eps must be positive, got 0"
+        )
+    }
+}
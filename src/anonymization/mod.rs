@@ -1,3 +1,11 @@
 mod buffer;
+pub mod clock;
 mod cluster;
+mod cluster_index;
+pub mod eviction;
 pub mod microagg_anonymizer;
+pub mod microagg_anonymizer_builder;
+pub mod record;
+pub mod sharded_anonymizer;
+mod snapshot;
+pub mod suppression;
@@ -0,0 +1,464 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, MissingValuePolicy, QuasiIdentifierType, QuasiIdentifierTypes,
+};
+use crate::data_manipulation::distance::QiDifference;
+use crate::data_manipulation::geo::{wrap_longitude, GeoPoint};
+use crate::data_manipulation::temporal::GRANULARITY_LADDER;
+use crate::error::DiffPrivError;
+
+/// Attributes of the QI's that are the same for every tuple, like the domain and the weight,
+/// and the position of the values of every QI in the row of a `QiColumns`
+#[derive(Debug)]
+pub struct QiSchema {
+    templates: Vec<QuasiIdentifierTypes>, // QI's of the first tuple, never missing
+    offsets: Vec<usize>, // start of the values of every QI, followed by the length of a row
+    policy: MissingValuePolicy,
+}
+
+impl QiSchema {
+    /// schema of the QI's extracted from the tuple
+    pub fn new<A: Anonymizable>(value: &A, qi: &[QuasiIdentifierTypes]) -> Self {
+        let templates: Vec<QuasiIdentifierTypes> =
+            qi.iter().map(|qi| qi.present().clone()).collect();
+        let mut offsets = vec![0];
+        templates
+            .iter()
+            .for_each(|template| offsets.push(offsets.last().unwrap() + Self::width(template)));
+
+        Self {
+            templates,
+            offsets,
+            policy: value.missing_value_policy(),
+        }
+    }
+
+    /// amount of QI's
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// the QI with the attributes shared by every tuple, its value is the one of the first tuple
+    pub fn template(&self, index: usize) -> &QuasiIdentifierTypes {
+        &self.templates[index]
+    }
+
+    /// check that the QI's have the same amount and types as the schema
+    pub fn check(&self, qi: &[QuasiIdentifierTypes]) -> Result<(), DiffPrivError> {
+        if self.len() != qi.len() {
+            return Err(DiffPrivError::InvalidRecord(format!(
+                "expected {} QI's, found {}",
+                self.len(),
+                qi.len()
+            )));
+        }
+        self.templates
+            .iter()
+            .zip(qi)
+            .try_for_each(|(expected, qi)| expected.check_compatible(qi))
+    }
+
+    /// compare the rows of the QI at the position of 2 tuples with the domain and the weight of
+    /// the template, giving the same difference as the default `Anonymizable::qi_difference`
+    fn difference(&self, index: usize, x: &[f64], y: &[f64]) -> QiDifference {
+        let template = &self.templates[index];
+        if x[0].is_nan() || y[0].is_nan() {
+            let (distance, weight) = self
+                .policy
+                .missing_distance(x[0].is_nan() && y[0].is_nan(), template.weight());
+            // missing coordinates have no difference
+            let deltas = match template {
+                QuasiIdentifierTypes::Interval(_) | QuasiIdentifierTypes::Temporal(_) => vec![0.0],
+                QuasiIdentifierTypes::GeoPoint(_) => vec![0.0; 2],
+                _ => Vec::new(),
+            };
+            return QiDifference {
+                distance,
+                weight,
+                deltas,
+            };
+        }
+
+        let (distance, deltas) = match template {
+            QuasiIdentifierTypes::Interval((_, min, max, weight)) => {
+                let (min, max) = match (min, max) {
+                    (QuasiIdentifierType::Integer(min), QuasiIdentifierType::Integer(max)) => {
+                        (*min as f64, *max as f64)
+                    }
+                    (QuasiIdentifierType::Float(min), QuasiIdentifierType::Float(max)) => {
+                        (*min, *max)
+                    }
+                    _ => panic!("wrong type conversion"),
+                };
+                (
+                    *weight as f64 * (x[0] - y[0]).abs() / (max - min),
+                    vec![x[0] - y[0]],
+                )
+            }
+            // the ranks are scaled to 0..=1 and compared on the domain of the ranks
+            QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                let span = *max_rank as f64 - 1.0;
+                let (rank_x, rank_y) = ((x[0] - 1.0) / span, (y[0] - 1.0) / span);
+                let weight = *weight as f64;
+                (
+                    weight * (weight * (rank_x - rank_y).abs() / span),
+                    Vec::new(),
+                )
+            }
+            QuasiIdentifierTypes::Nominal((_, _, weight)) => match x[0] == y[0] {
+                true => (0.0, Vec::new()),
+                false => (*weight as f64, Vec::new()),
+            },
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => (
+                *weight as f64 * hierarchy.distance(x[0] as i32, y[0] as i32),
+                Vec::new(),
+            ),
+            QuasiIdentifierTypes::Temporal((_, _, domain, weight)) => (
+                *weight as f64 * (x[0] - y[0]).abs() / domain.span() as f64,
+                vec![x[0] - y[0]],
+            ),
+            QuasiIdentifierTypes::GeoPoint((_, domain, weight)) => {
+                let point = |row: &[f64]| GeoPoint {
+                    latitude: row[0],
+                    longitude: row[1],
+                };
+                (
+                    *weight as f64 * domain.normalized_distance(&point(x), &point(y)),
+                    vec![x[0] - y[0], wrap_longitude(x[1] - y[1])],
+                )
+            }
+            QuasiIdentifierTypes::Missing(_) => panic!("Nested missing QI found"),
+        };
+
+        QiDifference {
+            distance,
+            weight: template.weight(),
+            deltas,
+        }
+    }
+
+    /// amount of numbers a value of the QI takes in a row, temporal QI's
+    /// keep their granularity and points their latitude and longitude
+    fn width(template: &QuasiIdentifierTypes) -> usize {
+        match template {
+            QuasiIdentifierTypes::Temporal(_) | QuasiIdentifierTypes::GeoPoint(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// QI's of a tuple extracted once into a row of numbers, laid out by a schema shared with the
+/// other tuples. Integers, categories and timestamps are stored exactly, missing values are NaN
+#[derive(Debug, Clone)]
+pub struct QiColumns {
+    schema: Arc<QiSchema>,
+    values: Box<[f64]>,
+}
+
+impl QiColumns {
+    /// store the QI's in a row of the schema, the QI's have to fit the schema
+    pub fn new(schema: Arc<QiSchema>, qi: &[QuasiIdentifierTypes]) -> Self {
+        let mut values = Vec::with_capacity(*schema.offsets.last().unwrap());
+        qi.iter().for_each(|qi| match qi {
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(value), ..)) => {
+                values.push(*value as f64)
+            }
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Float(value), ..)) => {
+                values.push(*value)
+            }
+            QuasiIdentifierTypes::Ordinal((value, _, _))
+            | QuasiIdentifierTypes::Nominal((value, _, _))
+            | QuasiIdentifierTypes::Hierarchical((value, _, _)) => values.push(*value as f64),
+            QuasiIdentifierTypes::Temporal((timestamp, granularity, _, _)) => {
+                let level = GRANULARITY_LADDER
+                    .iter()
+                    .position(|x| x == granularity)
+                    .unwrap();
+                values.extend([*timestamp as f64, level as f64])
+            }
+            QuasiIdentifierTypes::GeoPoint((point, _, _)) => {
+                values.extend([point.latitude, point.longitude])
+            }
+            QuasiIdentifierTypes::Missing(template) => {
+                values.extend(std::iter::repeat_n(f64::NAN, QiSchema::width(template)))
+            }
+        });
+
+        Self {
+            schema,
+            values: values.into_boxed_slice(),
+        }
+    }
+
+    /// extract the QI's of the tuple with a schema of its own
+    pub fn extract<A: Anonymizable>(value: &A) -> Self {
+        let qi = value.quasi_identifiers();
+        Self::new(Arc::new(QiSchema::new(value, &qi)), &qi)
+    }
+
+    pub fn schema(&self) -> &Arc<QiSchema> {
+        &self.schema
+    }
+
+    /// amount of QI's
+    pub fn len(&self) -> usize {
+        self.schema.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schema.is_empty()
+    }
+
+    /// all the numbers of the QI
+    fn row(&self, index: usize) -> &[f64] {
+        &self.values[self.schema.offsets[index]..self.schema.offsets[index + 1]]
+    }
+
+    pub fn is_missing(&self, index: usize) -> bool {
+        self.row(index)[0].is_nan()
+    }
+
+    /// the value of the QI as numbers, the timestamp of a temporal QI and the latitude and
+    /// longitude of a point. `None` if the value is missing
+    pub fn numbers(&self, index: usize) -> Option<&[f64]> {
+        let row = self.row(index);
+        match (row[0].is_nan(), self.schema.template(index)) {
+            (true, _) => None,
+            (false, QuasiIdentifierTypes::Temporal(_)) => Some(&row[..1]),
+            (false, _) => Some(row),
+        }
+    }
+
+    /// the value of an ordinal, nominal or hierarchical QI,
+    /// `None` if the value is missing or not categorical
+    pub fn category(&self, index: usize) -> Option<i32> {
+        match self.schema.template(index) {
+            QuasiIdentifierTypes::Ordinal(_)
+            | QuasiIdentifierTypes::Nominal(_)
+            | QuasiIdentifierTypes::Hierarchical(_) => {
+                self.numbers(index).map(|value| value[0] as i32)
+            }
+            _ => None,
+        }
+    }
+
+    /// the QI at the position, missing values have the template of the schema
+    pub fn qi(&self, index: usize) -> QuasiIdentifierTypes {
+        let template = self.schema.template(index);
+        let row = self.row(index);
+        if row[0].is_nan() {
+            return QuasiIdentifierTypes::Missing(Box::new(template.clone()));
+        }
+
+        match template {
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(_), min, max, weight)) => {
+                QuasiIdentifierTypes::Interval((
+                    QuasiIdentifierType::Integer(row[0] as i32),
+                    *min,
+                    *max,
+                    *weight,
+                ))
+            }
+            QuasiIdentifierTypes::Interval((_, min, max, weight)) => {
+                QuasiIdentifierTypes::Interval((
+                    QuasiIdentifierType::Float(row[0]),
+                    *min,
+                    *max,
+                    *weight,
+                ))
+            }
+            QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                QuasiIdentifierTypes::Ordinal((row[0] as i32, *max_rank, *weight))
+            }
+            QuasiIdentifierTypes::Nominal((_, max_value, weight)) => {
+                QuasiIdentifierTypes::Nominal((row[0] as i32, *max_value, *weight))
+            }
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                QuasiIdentifierTypes::Hierarchical((row[0] as i32, Arc::clone(hierarchy), *weight))
+            }
+            QuasiIdentifierTypes::Temporal((_, _, domain, weight)) => {
+                QuasiIdentifierTypes::Temporal((
+                    row[0] as i64,
+                    GRANULARITY_LADDER[row[1] as usize],
+                    *domain,
+                    *weight,
+                ))
+            }
+            QuasiIdentifierTypes::GeoPoint((_, domain, weight)) => {
+                QuasiIdentifierTypes::GeoPoint((
+                    GeoPoint {
+                        latitude: row[0],
+                        longitude: row[1],
+                    },
+                    *domain,
+                    *weight,
+                ))
+            }
+            QuasiIdentifierTypes::Missing(_) => panic!("Nested missing QI found"),
+        }
+    }
+
+    /// all the QI's in the order of `Anonymizable::quasi_identifiers`
+    pub fn quasi_identifiers(&self) -> Vec<QuasiIdentifierTypes> {
+        (0..self.len()).map(|index| self.qi(index)).collect()
+    }
+
+    /// compare the QI's one by one like `Anonymizable::try_qi_differences`,
+    /// with the missing value policy of the schema
+    pub fn differences(&self, other: &QiColumns) -> Vec<QiDifference> {
+        (0..self.len())
+            .map(|index| {
+                self.schema
+                    .difference(index, self.row(index), other.row(index))
+            })
+            .collect()
+    }
+}
+
+/// A tuple inside the anonymizer together with its QI's, which are extracted
+/// only once when the tuple enters. Serialized as the tuple itself
+#[derive(Debug, Clone)]
+pub struct Record<A: Anonymizable> {
+    pub value: A,
+    pub qi: QiColumns,
+    pub weight: f64, // `record_weight` of the tuple
+}
+
+impl<A: Anonymizable> Record<A> {
+    pub fn new(value: A, qi: QiColumns) -> Self {
+        Self {
+            weight: value.record_weight(),
+            value,
+            qi,
+        }
+    }
+}
+
+/// extract the QI's of the tuple with a schema of its own
+impl<A: Anonymizable> From<A> for Record<A> {
+    fn from(value: A) -> Self {
+        let qi = QiColumns::extract(&value);
+        Self::new(value, qi)
+    }
+}
+
+impl<A: Anonymizable> Serialize for Record<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, A: Anonymizable + Deserialize<'de>> Deserialize<'de> for Record<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        A::deserialize(deserializer).map(Record::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anonymization::record::QiColumns;
+    use crate::data_manipulation::anonymizable::Anonymizable;
+    use crate::data_manipulation::geo::GeoPoint;
+    use crate::data_manipulation::mueller::MuellerStream;
+    use std::time::SystemTime;
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    #[missing_values(own_category)]
+    struct Visit {
+        #[qi(interval, min = 0.0, max = 250.0, weight = 2)]
+        weight: Option<f64>,
+        #[qi(ordinal, categories = ["low", "medium", "high"], weight = 3)]
+        severity: Option<String>,
+        #[qi(nominal, max = 4)]
+        ward: i32,
+        #[qi(temporal, min = "2022-01-01", max = "2023-01-01")]
+        admitted: Option<String>,
+        #[qi(geo, max_distance = 1000)]
+        home: Option<GeoPoint>,
+        #[sensitive]
+        diagnosis: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Visit {
+        fn default() -> Self {
+            Self {
+                weight: Some(70.5),
+                severity: Some("low".to_string()),
+                ward: 1,
+                admitted: Some("2022-03-01 10:00:00".to_string()),
+                home: Some(GeoPoint::new(52.37, 179.9)),
+                diagnosis: "flu".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn differences_of_every_kind() {
+        let visits = [
+            Visit::default(),
+            Visit {
+                weight: Some(90.0),
+                severity: Some("high".to_string()),
+                ward: 3,
+                admitted: Some("2022-09-15 08:30:00".to_string()),
+                home: Some(GeoPoint::new(50.85, -179.6)),
+                ..Visit::default()
+            },
+            Visit {
+                weight: None,
+                severity: None,
+                admitted: None,
+                home: None,
+                ..Visit::default()
+            },
+        ];
+
+        // the rows give the same differences as the QI's of the tuples
+        visits.iter().for_each(|x| {
+            visits.iter().for_each(|y| {
+                assert_eq!(
+                    QiColumns::extract(x).differences(&QiColumns::extract(y)),
+                    x.try_qi_differences(y).unwrap()
+                )
+            })
+        })
+    }
+
+    #[test]
+    fn extract_columns() {
+        let mueller = MuellerStream {
+            age: Some(40),
+            gender: None,
+            ..MuellerStream::default()
+        };
+        let other = MuellerStream {
+            age: Some(60),
+            gender: Some("female".to_string()),
+            ..MuellerStream::default()
+        };
+        let columns = QiColumns::extract(&mueller);
+
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.numbers(0), Some(&[40.0][..]));
+        assert!(columns.is_missing(1));
+        assert_eq!(QiColumns::extract(&other).category(1), Some(1));
+
+        // the QI's give the same differences as the tuple itself
+        assert_eq!(
+            columns.differences(&QiColumns::extract(&other)),
+            mueller.try_qi_differences(&other).unwrap()
+        );
+        let restored = mueller.update_quasi_identifiers(columns.quasi_identifiers());
+        assert_eq!((restored.age, restored.gender), (Some(40), None))
+    }
+}
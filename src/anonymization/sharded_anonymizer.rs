@@ -0,0 +1,475 @@
+use crate::analysis::analyser::Analyser;
+use crate::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, QuasiIdentifierType, QuasiIdentifierTypes,
+};
+use crate::error::DiffPrivError;
+use crate::noise::noiser::Noiser;
+use crate::publishing::publisher::Publisher;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// tuples waiting for a shard before `anonymize` blocks
+const QUEUE_SIZE: usize = 1024;
+
+/// How the tuples are divided over the shards of a `ShardedAnonymizer`
+pub enum Partition<A> {
+    /// tuples with the same key are anonymized by the same shard, see `by_key`
+    Key(Box<dyn Fn(&A) -> u64 + Send>),
+    /// the domain of every QI is divided into this amount of cells,
+    /// tuples in the same cell of the grid are anonymized by the same shard
+    Grid(usize),
+}
+
+impl<A: Anonymizable> Partition<A> {
+    /// partition by a key of the tuples, e.g. the id of their user
+    pub fn by_key<K: Hash>(key: impl Fn(&A) -> K + Send + 'static) -> Self {
+        Self::Key(Box::new(move |value| hash(&key(value))))
+    }
+
+    /// shard of the tuple out of the amount of shards, records whose QI's can't be
+    /// extracted go to the first shard, which handles them by its `RecordErrorPolicy`
+    pub fn shard(&self, value: &A, shards: usize) -> usize {
+        let hash = match self {
+            Partition::Key(key) => key(value),
+            Partition::Grid(cells) => match value.try_quasi_identifiers() {
+                Ok(qi) => hash(&qi.iter().map(|qi| cell(qi, *cells)).collect::<Vec<u64>>()),
+                Err(_) => return 0,
+            },
+        };
+        (hash % shards as u64) as usize
+    }
+}
+
+/// hash that is the same in every run
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// cell of the QI in a grid with the amount of cells on every axis,
+/// categories are cells of their own and missing values share a cell
+fn cell(qi: &QuasiIdentifierTypes, cells: usize) -> u64 {
+    let cells = cells.max(1);
+    // values outside of the domain end up in the outer cells
+    let position = |fraction: f64| ((fraction * cells as f64) as usize).min(cells - 1) as u64;
+    let point =
+        |latitude: f64, longitude: f64| position(latitude) * cells as u64 + position(longitude);
+
+    match qi {
+        QuasiIdentifierTypes::Interval((value, min, max, _)) => match (value, min, max) {
+            (
+                QuasiIdentifierType::Integer(value),
+                QuasiIdentifierType::Integer(min),
+                QuasiIdentifierType::Integer(max),
+            ) => position((value - min) as f64 / (max - min) as f64),
+            (
+                QuasiIdentifierType::Float(value),
+                QuasiIdentifierType::Float(min),
+                QuasiIdentifierType::Float(max),
+            ) => position((value - min) / (max - min)),
+            _ => 0,
+        },
+        QuasiIdentifierTypes::Ordinal((rank, max_rank, _)) => {
+            position((rank - 1) as f64 / (max_rank - 1).max(1) as f64)
+        }
+        QuasiIdentifierTypes::Nominal((value, _, _))
+        | QuasiIdentifierTypes::Hierarchical((value, _, _)) => *value as u64,
+        QuasiIdentifierTypes::Temporal((timestamp, _, domain, _)) => {
+            position((timestamp - domain.min) as f64 / domain.span() as f64)
+        }
+        QuasiIdentifierTypes::GeoPoint((value, _, _)) => point(
+            (value.latitude + 90.0) / 180.0,
+            (value.longitude + 180.0) / 360.0,
+        ),
+        QuasiIdentifierTypes::Missing(_) => u64::MAX,
+    }
+}
+
+enum Message<A> {
+    Tuple(A),
+    /// merge the analysers of the other shards into the analysers of the shard
+    Merge(Vec<Vec<AnalyserResult>>),
+}
+
+/// report of an analyser of a shard together with its state, see `Analyser::state`
+struct AnalyserResult {
+    name: String,
+    report: serde_json::Value,
+    state: Option<serde_json::Value>,
+}
+
+/// Anonymizes a stream on a thread for every shard, each running its own `MicroaggAnonymizer`.
+/// The tuples are divided over the shards by the `Partition`, the tuples of a shard are anonymized
+/// in the order in which they were passed to `anonymize`. A cluster only contains tuples of a single
+/// shard, so k-anonymity and l-diversity hold for every published cluster like with a single anonymizer
+pub struct ShardedAnonymizer<A: Anonymizable + Send> {
+    partition: Partition<A>,
+    senders: Vec<SyncSender<Message<A>>>,
+    workers: Vec<JoinHandle<Result<Vec<AnalyserResult>, DiffPrivError>>>,
+}
+
+impl<A: Anonymizable + Send> ShardedAnonymizer<A> {
+    /// start a thread for every shard with the anonymizer that `build` creates for the number of the shard.
+    /// Share a `SharedPublisher` between the anonymizers to publish all tuples to the same publisher
+    pub fn new<N, P>(
+        shards: usize,
+        partition: Partition<A>,
+        build: impl Fn(usize) -> MicroaggAnonymizer<N, A, P> + Send + Sync + 'static,
+    ) -> Result<Self, DiffPrivError>
+    where
+        N: Noiser + 'static,
+        P: Publisher + 'static,
+    {
+        if shards == 0 {
+            return Err(DiffPrivError::InvalidParameter(
+                "a sharded anonymizer needs at least 1 shard".to_string(),
+            ));
+        }
+
+        let build = Arc::new(build);
+        let mut senders = Vec::with_capacity(shards);
+        let mut workers = Vec::with_capacity(shards);
+        for shard in 0..shards {
+            let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+            let build = Arc::clone(&build);
+            let worker = thread::Builder::new()
+                .name(format!("shard-{}", shard))
+                .spawn(move || run(build(shard), receiver))
+                .map_err(|e| DiffPrivError::Shard(e.to_string()))?;
+            senders.push(sender);
+            workers.push(worker);
+        }
+
+        Ok(Self {
+            partition,
+            senders,
+            workers,
+        })
+    }
+
+    /// amount of shards
+    pub fn shards(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// send the tuple to its shard. Panics when the shard stopped, see `try_anonymize`
+    pub fn anonymize(&mut self, value: A) {
+        if let Err(e) = self.try_anonymize(value) {
+            panic!("{}", e)
+        }
+    }
+
+    /// send the tuple to its shard, blocks while the shard is behind. Fails when the shard
+    /// stopped on a bad record with `RecordErrorPolicy::Abort`, `finish` returns the error of the record
+    pub fn try_anonymize(&mut self, value: A) -> Result<(), DiffPrivError> {
+        let shard = self.partition.shard(&value, self.shards());
+        self.senders[shard]
+            .send(Message::Tuple(value))
+            .map_err(|_| DiffPrivError::Shard(format!("shard {} stopped", shard)))
+    }
+
+    /// wait until every shard anonymized its tuples, flush the clusters of the shards with
+    /// `MicroaggAnonymizer::flush_all` and stop the threads. Returns the reports of
+    /// the analysers merged over the shards like `MicroaggAnonymizer::report`, analysers without a
+    /// state list the report of every shard. Fails with the error of the first shard that stopped
+    pub fn finish(self) -> Result<serde_json::Map<String, serde_json::Value>, DiffPrivError> {
+        let mut senders = self.senders.into_iter();
+        let mut workers = self.workers.into_iter();
+        let (sender, worker) = (senders.next().unwrap(), workers.next().unwrap());
+        // closing the queues stops the other shards
+        drop(senders);
+        let others: Vec<Result<Vec<AnalyserResult>, DiffPrivError>> = workers.map(join).collect();
+
+        let results = match others.into_iter().collect::<Result<Vec<_>, _>>() {
+            Ok(others) => {
+                // the first shard stopped when this fails, which is returned by `join`
+                let _ = sender.send(Message::Merge(others));
+                drop(sender);
+                join(worker)
+            }
+            Err(e) => {
+                drop(sender);
+                join(worker).and(Err(e))
+            }
+        }?;
+        Ok(results
+            .into_iter()
+            .map(|result| (result.name, result.report))
+            .collect())
+    }
+}
+
+/// anonymize the tuples of a shard until its queue is closed, then flush its clusters
+fn run<N: Noiser, A: Anonymizable, P: Publisher>(
+    mut anonymizer: MicroaggAnonymizer<N, A, P>,
+    receiver: Receiver<Message<A>>,
+) -> Result<Vec<AnalyserResult>, DiffPrivError> {
+    let mut merged = None;
+    for message in receiver {
+        match message {
+            Message::Tuple(value) => anonymizer.try_anonymize(value)?,
+            Message::Merge(shards) => {
+                // the other shards are flushed before they are merged
+                anonymizer.flush_all();
+                merged = Some(merge(&mut anonymizer.analysers, shards))
+            }
+        }
+    }
+    anonymizer.flush_all();
+
+    Ok(merged.unwrap_or_else(|| {
+        anonymizer
+            .analysers
+            .iter()
+            .map(|analyser| AnalyserResult {
+                name: analyser.name().to_string(),
+                report: analyser.report(),
+                state: analyser.state(),
+            })
+            .collect()
+    }))
+}
+
+/// merge the analysers of the other shards into the analysers of this shard,
+/// analysers that can't be merged list the report of every shard
+fn merge<A: Anonymizable>(
+    analysers: &mut [Box<dyn Analyser<A>>],
+    shards: Vec<Vec<AnalyserResult>>,
+) -> Vec<AnalyserResult> {
+    analysers
+        .iter_mut()
+        .map(|analyser| {
+            let others: Vec<&AnalyserResult> = shards
+                .iter()
+                .filter_map(|results| results.iter().find(|x| x.name == analyser.name()))
+                .collect();
+            let states: Option<Vec<&serde_json::Value>> =
+                others.iter().map(|x| x.state.as_ref()).collect();
+
+            let report = match states {
+                Some(states) if analyser.state().is_some() => {
+                    states.into_iter().for_each(|state| analyser.merge(state));
+                    analyser.report()
+                }
+                _ => std::iter::once(analyser.report())
+                    .chain(others.iter().map(|x| x.report.clone()))
+                    .collect(),
+            };
+            AnalyserResult {
+                name: analyser.name().to_string(),
+                report,
+                state: None,
+            }
+        })
+        .collect()
+}
+
+fn join(
+    worker: JoinHandle<Result<Vec<AnalyserResult>, DiffPrivError>>,
+) -> Result<Vec<AnalyserResult>, DiffPrivError> {
+    worker
+        .join()
+        .unwrap_or_else(|_| Err(DiffPrivError::Shard("shard panicked".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Partition, ShardedAnonymizer};
+    use crate::analysis::analyser::{Analyser, PublishContext};
+    use crate::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+    use crate::data_manipulation::anonymizable::Anonymizable;
+    use crate::data_manipulation::mueller::MuellerStream;
+    use crate::error::{DiffPrivError, RecordErrorPolicy};
+    use crate::noise::laplace::laplace_noiser::LaplaceNoiser;
+    use crate::publishing::publisher::Publisher;
+    use crate::publishing::shared_publisher::SharedPublisher;
+    use crate::test::dummy_publisher::DummyPublisher;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct CountingPublisher {
+        clusters: HashMap<Uuid, usize>,
+    }
+
+    impl Publisher for CountingPublisher {
+        fn publish<M: Anonymizable>(&mut self, _value: M, uuid: Uuid, _dr: f64) {
+            *self.clusters.entry(uuid).or_default() += 1
+        }
+    }
+
+    /// analyser without a state, which can't be merged
+    #[derive(Default)]
+    struct PublishedAnalyser {
+        published: usize,
+    }
+
+    impl<A: Anonymizable> Analyser<A> for PublishedAnalyser {
+        fn name(&self) -> &str {
+            "published"
+        }
+
+        fn report(&self) -> serde_json::Value {
+            serde_json::json!(self.published)
+        }
+
+        fn on_publish(&mut self, _original: &A, _published: &A, _ctx: &mut PublishContext) {
+            self.published += 1
+        }
+    }
+
+    fn tuple(i: usize) -> MuellerStream {
+        MuellerStream {
+            id: (i % 10).to_string(),
+            age: Some(18 + (i * 37 % 60) as i32),
+            gender: Some(["male", "female"][i % 2].to_string()),
+            ..MuellerStream::default()
+        }
+    }
+
+    fn build<P: Publisher>(
+        publisher: P,
+        shard: usize,
+    ) -> MicroaggAnonymizer<LaplaceNoiser, MuellerStream, P> {
+        MicroaggAnonymizer::builder(publisher, LaplaceNoiser::new(0.1, 3, 0.1))
+            .k(3)
+            .l(3)
+            .diff_thres(0.3)
+            .seed(shard as u64)
+            .build()
+            .unwrap()
+            .with_analyser(Box::new(PublishedAnalyser::default()))
+    }
+
+    #[test]
+    fn partition() {
+        let by_key = Partition::by_key(|x: &MuellerStream| x.id.clone());
+        let grid = Partition::Grid(4);
+
+        (0..100).for_each(|i| {
+            // tuples with the same key or QI's go to the same shard
+            assert_eq!(by_key.shard(&tuple(i), 4), by_key.shard(&tuple(i + 10), 4));
+            assert_eq!(grid.shard(&tuple(i), 4), grid.shard(&tuple(i + 60), 4));
+            assert!(grid.shard(&tuple(i), 4) < 4)
+        });
+        let shards: Vec<usize> = (0..100).map(|i| grid.shard(&tuple(i), 4)).collect();
+        assert!(shards.iter().any(|shard| *shard != shards[0]))
+    }
+
+    #[test]
+    fn merged_report() {
+        let publisher = SharedPublisher::new(CountingPublisher::default());
+        let shared = publisher.clone();
+        let mut anonymizer = ShardedAnonymizer::new(2, Partition::Grid(4), move |shard| {
+            build(shared.clone(), shard)
+        })
+        .unwrap();
+        (0..500).for_each(|i| anonymizer.anonymize(tuple(i)));
+        let report = anonymizer.finish().unwrap();
+
+        // the same anonymizers one after the other
+        let grid: Partition<MuellerStream> = Partition::Grid(4);
+        let reports: Vec<_> = (0..2)
+            .map(|shard| {
+                let mut anonymizer = build(DummyPublisher::default(), shard);
+                (0..500)
+                    .filter(|i| grid.shard(&tuple(*i), 2) == shard)
+                    .for_each(|i| anonymizer.anonymize(tuple(i)));
+                anonymizer.flush_all();
+                anonymizer.report()
+            })
+            .collect();
+
+        let sum = |analyser: &str, key: &str| -> i64 {
+            reports
+                .iter()
+                .map(|x| x[analyser][key].as_i64().unwrap())
+                .sum()
+        };
+        assert_eq!(report["clusters"]["created"], sum("clusters", "created"));
+        assert_eq!(
+            report["suppression"]["published"],
+            sum("suppression", "published")
+        );
+        assert_eq!(
+            report["sse"],
+            reports[0]["sse"].as_f64().unwrap() + reports[1]["sse"].as_f64().unwrap()
+        );
+        let suppressed = |report: &serde_json::Map<String, serde_json::Value>| -> u64 {
+            let suppressed = report["suppression"]["suppressed"].as_object().unwrap();
+            suppressed.values().map(|x| x.as_u64().unwrap()).sum()
+        };
+        assert!(suppressed(&report) > 0);
+        assert_eq!(
+            suppressed(&report),
+            suppressed(&reports[0]) + suppressed(&reports[1])
+        );
+        // analysers without a state list the report of every shard
+        assert_eq!(
+            report["published"],
+            serde_json::json!([reports[0]["published"], reports[1]["published"]])
+        );
+        let published: usize = publisher.lock().clusters.values().sum();
+        assert_eq!(
+            published as u64,
+            report["published"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_u64().unwrap())
+                .sum::<u64>()
+        )
+    }
+
+    #[test]
+    fn every_tuple_flushed() {
+        let mut anonymizer = ShardedAnonymizer::new(3, Partition::Grid(4), |shard| {
+            build(DummyPublisher::default(), shard)
+        })
+        .unwrap();
+        (0..500).for_each(|i| anonymizer.anonymize(tuple(i)));
+        let report = anonymizer.finish().unwrap();
+
+        // the tuples left in the clusters at the end are published or suppressed as well
+        let suppressed: u64 = report["suppression"]["suppressed"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|x| x.as_u64().unwrap())
+            .sum();
+        let published = report["suppression"]["published"].as_u64().unwrap();
+        assert_eq!(published + suppressed, 500);
+        assert_eq!(report["clusters"]["created"], report["clusters"]["deleted"])
+    }
+
+    #[test]
+    fn shard_error() {
+        let mut anonymizer = ShardedAnonymizer::new(2, Partition::Grid(4), |shard| {
+            build(DummyPublisher::default(), shard)
+                .with_record_error_policy(RecordErrorPolicy::Abort)
+        })
+        .unwrap();
+        anonymizer.anonymize(tuple(0));
+        anonymizer.anonymize(MuellerStream {
+            gender: Some("unknown".to_string()),
+            ..tuple(1)
+        });
+
+        assert!(matches!(
+            anonymizer.finish(),
+            Err(DiffPrivError::UnknownCategory(_))
+        ));
+        assert!(matches!(
+            ShardedAnonymizer::new(0, Partition::Grid(4), |shard| build(
+                DummyPublisher::default(),
+                shard
+            )),
+            Err(DiffPrivError::InvalidParameter(_))
+        ))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::anonymization::cluster::{Cluster, SensitiveFrequencies};
+use crate::data_manipulation::anonymizable::{Anonymizable, SensitiveAttribute};
+use crate::error::DiffPrivError;
+use crate::noise::noiser::Noiser;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// version of the snapshot format, snapshots of another version can't be restored
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// state of an anonymizer written by `MicroaggAnonymizer::snapshot`
+#[derive(Serialize)]
+#[serde(bound(serialize = "A: Anonymizable, N: Noiser + Serialize"))]
+pub(crate) struct SnapshotRef<'a, A, N>
+where
+    A: Anonymizable,
+    N: Noiser,
+{
+    pub version: u32,
+    pub cluster_set: &'a BTreeMap<u128, Cluster<A, N>>,
+    pub noiser: &'a N,
+    #[serde(with = "sensitive_frequencies")]
+    pub sensitive_freq: &'a SensitiveFrequencies,
+    pub watermark: u64, // latest event time of the clock
+    pub seed: u64,      // the random number generator of the anonymizer continues from this seed
+}
+
+/// state of an anonymizer read by `MicroaggAnonymizer::restore`
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "A: Anonymizable + DeserializeOwned, N: Noiser + DeserializeOwned"))]
+pub(crate) struct Snapshot<A, N>
+where
+    A: Anonymizable,
+    N: Noiser,
+{
+    pub version: u32,
+    pub cluster_set: BTreeMap<u128, Cluster<A, N>>,
+    pub noiser: N,
+    #[serde(with = "sensitive_frequencies")]
+    pub sensitive_freq: SensitiveFrequencies,
+    pub watermark: u64,
+    pub seed: u64,
+}
+
+/// write the snapshot next to the path first, a crash while writing
+/// never leaves a partial snapshot behind
+pub(crate) fn write<A, N>(path: &Path, snapshot: &SnapshotRef<A, N>) -> Result<(), DiffPrivError>
+where
+    A: Anonymizable,
+    N: Noiser + Serialize,
+{
+    let partial = path.with_extension("partial");
+    let mut writer = BufWriter::new(File::create(&partial).map_err(snapshot_error)?);
+    bincode::serialize_into(&mut writer, snapshot).map_err(snapshot_error)?;
+    writer.flush().map_err(snapshot_error)?;
+    std::fs::rename(&partial, path).map_err(snapshot_error)
+}
+
+pub(crate) fn read<A, N>(path: &Path) -> Result<Snapshot<A, N>, DiffPrivError>
+where
+    A: Anonymizable + DeserializeOwned,
+    N: Noiser + DeserializeOwned,
+{
+    let reader = BufReader::new(File::open(path).map_err(snapshot_error)?);
+    let snapshot: Snapshot<A, N> = bincode::deserialize_from(reader).map_err(snapshot_error)?;
+    match snapshot.version == SNAPSHOT_VERSION {
+        true => Ok(snapshot),
+        false => Err(DiffPrivError::Snapshot(format!(
+            "version {} can't be restored, expected version {}",
+            snapshot.version, SNAPSHOT_VERSION
+        ))),
+    }
+}
+
+fn snapshot_error<E: ToString>(error: E) -> DiffPrivError {
+    DiffPrivError::Snapshot(error.to_string())
+}
+
+/// `SensitiveAttribute` is serialized as its plain value for the published records,
+/// in a snapshot it needs its variant and bucket width to be read back
+pub(crate) mod sensitive_frequencies {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    enum Attribute {
+        String(String),
+        Integer(i32),
+        Float(f64, f64),
+    }
+
+    type Frequencies = Vec<(usize, Vec<(Attribute, i32)>)>;
+
+    pub fn serialize<S: Serializer>(
+        freq: &SensitiveFrequencies,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let frequencies: Frequencies = freq
+            .iter()
+            .map(|(index, counts)| {
+                let counts = counts
+                    .iter()
+                    .map(|(attribute, count)| {
+                        let attribute = match attribute.clone() {
+                            SensitiveAttribute::String(value) => Attribute::String(value),
+                            SensitiveAttribute::Integer(value) => Attribute::Integer(value),
+                            SensitiveAttribute::Float(value, width) => {
+                                Attribute::Float(value, width)
+                            }
+                        };
+                        (attribute, *count)
+                    })
+                    .collect();
+                (*index, counts)
+            })
+            .collect();
+        frequencies.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SensitiveFrequencies, D::Error> {
+        let frequencies = Frequencies::deserialize(deserializer)?;
+        Ok(frequencies
+            .into_iter()
+            .map(|(index, counts)| {
+                let counts = counts
+                    .into_iter()
+                    .map(|(attribute, count)| {
+                        let attribute = match attribute {
+                            Attribute::String(value) => SensitiveAttribute::String(value),
+                            Attribute::Integer(value) => SensitiveAttribute::Integer(value),
+                            Attribute::Float(value, width) => {
+                                SensitiveAttribute::Float(value, width)
+                            }
+                        };
+                        (attribute, count)
+                    })
+                    .collect();
+                (index, counts)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,21 @@
+/// What happens with a tuple of a cluster that doesn't satisfy l-diversity or t-closeness
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionStrategy {
+    /// the tuple is not published
+    Drop,
+    /// every QI is replaced by the midpoint of its domain
+    Midpoint,
+    /// every QI is replaced by a missing value, which is published as `*` in generalized records.
+    /// Falls back to the midpoint when the tuple can't contain missing values
+    Wildcard,
+    /// every QI is generalized to its full domain in generalized records,
+    /// microaggregated tuples are published with the midpoint
+    FullDomain,
+    /// every QI is sampled uniformly from its domain
+    #[default]
+    UniformSample,
+    /// the tuple is kept in the cluster until it satisfies l-diversity and t-closeness.
+    /// Tuples that are still held when the cluster is removed are dropped
+    Hold,
+}
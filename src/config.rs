@@ -1,5 +1,12 @@
 use hocon::HoconLoader;
 
+use crate::anonymization::clock::TimeMode;
+use crate::anonymization::eviction::EvictionPolicy;
+use crate::anonymization::suppression::SuppressionStrategy;
+use crate::data_manipulation::distance::DistanceMetricType;
+use crate::error::RecordErrorPolicy;
+use crate::publishing::generalized::OutputMode;
+
 #[derive(Deserialize)]
 pub struct Config {
     pub topic_in: String,
@@ -13,6 +20,46 @@ pub struct Config {
     pub delta: Vec<u64>,
     pub noise_thr: Vec<f64>,
     pub publish_remaining_tuples: bool,
+    #[serde(default)]
+    pub datasets: Vec<DatasetConfig>, // when empty the built-in datasets are used
+    #[serde(default)]
+    pub record_error_policy: RecordErrorPolicy, // what to do with records that can't be anonymized
+    #[serde(default)]
+    pub dead_letter_topic: Option<String>, // topic of rejected records with the `dead_letter` policy
+    #[serde(default)]
+    pub distance_metric: DistanceMetricType, // metric used for clustering, the analysers and concept drift
+    #[serde(default)]
+    pub output_mode: OutputMode, // publish microaggregated tuples, generalized records or both
+    #[serde(default)]
+    pub generalized_topic: Option<String>, // topic of the generalized records as JSON
+    #[serde(default)]
+    pub suppression_strategy: SuppressionStrategy, // what happens with tuples of clusters that aren't diverse
+    #[serde(default)]
+    pub time_mode: TimeMode, // measure the cluster life time and publishing delay in processing or event time
+    #[serde(default)]
+    pub seed: Option<u64>, // makes runs over the same data reproducible, seeded from the OS when missing
+    #[serde(default)]
+    pub checkpoint_path: Option<String>, // file the anonymizer state is written to, restored from on startup
+    #[serde(default)]
+    pub checkpoint_interval: u64, // seconds between checkpoints, 0 writes one after every poll
+    #[serde(default)]
+    pub flush_interval: u64, // seconds between flushes of expired clusters, 0 flushes after every poll
+    #[serde(default)]
+    pub max_clusters: Option<usize>, // clusters are evicted above this count, unbounded when missing
+    #[serde(default)]
+    pub memory_budget: Option<usize>, // estimated bytes of the clusters before they are evicted
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy, // which cluster is evicted first
+    #[serde(default)]
+    pub merge_evicted: bool, // merge evicted clusters below k into the nearest cluster instead of suppressing
+}
+
+/// CSV dataset that is anonymized with a `DynamicSchema`
+#[derive(Deserialize)]
+pub struct DatasetConfig {
+    pub path: String,   // path of the CSV file
+    pub export: String, // path prefix of the exported files
+    pub schema: String, // path of the HOCON or JSON schema file
 }
 
 impl Config {
@@ -1,94 +1,377 @@
 use crate::data_manipulation::anonymizable::{
-    IntervalType, NominalType, OrdinalType, QuasiIdentifierType, QuasiIdentifierTypes,
+    GeoPointType, HierarchicalType, NominalType, OrdinalType, QuasiIdentifierType,
+    QuasiIdentifierTypes, TemporalType,
 };
+use crate::data_manipulation::geo::{spherical_centroid, spherical_centroid_of_sum, GeoPoint};
+use crate::error::DiffPrivError;
 use itertools::Itertools;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+/// Aggregation of the values of a single QI into the value of the centroid,
+/// `Default` chooses the aggregation based on the data category of the QI
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Aggregator {
+    #[default]
+    Default,
+    Mean,
+    Median,
+    /// mean without the given proportion of the lowest and highest values, in 0..0.5
+    TrimmedMean(f64),
+    /// mean weighted by the `record_weight` of the tuples
+    WeightedMean,
+    Mode,
+    LowestCommonAncestor,
+    SphericalCentroid,
+}
+
+impl Aggregator {
+    /// the aggregation of the list, `None` for `Aggregator::Default`.
+    /// The weights are only used by `WeightedMean`
+    pub fn aggregate_type(
+        self,
+        list: Vec<QuasiIdentifierTypes>,
+        weights: Vec<f64>,
+    ) -> Option<AggregateType> {
+        match self {
+            Aggregator::Default => None,
+            Aggregator::Mean => Some(AggregateType::Mean(list)),
+            Aggregator::Median => Some(AggregateType::Median(list)),
+            Aggregator::TrimmedMean(proportion) => {
+                Some(AggregateType::TrimmedMean(list, proportion))
+            }
+            Aggregator::WeightedMean => Some(AggregateType::WeightedMean(list, weights)),
+            Aggregator::Mode => Some(AggregateType::Mode(list)),
+            Aggregator::LowestCommonAncestor => Some(AggregateType::LowestCommonAncestor(list)),
+            Aggregator::SphericalCentroid => Some(AggregateType::SphericalCentroid(list)),
+        }
+    }
+}
+
+/// Rounding of aggregated values of integer QI's
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// round towards zero
+    #[default]
+    Truncate,
+    /// round half away from zero
+    Nearest,
+    /// round half to the nearest even number
+    HalfEven,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    pub fn round(self, value: f64) -> f64 {
+        match self {
+            Rounding::Truncate => value.trunc(),
+            Rounding::Nearest => value.round(),
+            Rounding::HalfEven => value.round_ties_even(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+        }
+    }
+}
 
-/// TODO: check adding MEDIAN
 pub enum AggregateType {
     Mean(Vec<QuasiIdentifierTypes>),
+    /// middle value of interval, temporal and ordinal QI's, the lower one for an even amount of values
+    Median(Vec<QuasiIdentifierTypes>),
+    /// mean of interval and temporal QI's without the given proportion
+    /// of the lowest and highest values
+    TrimmedMean(Vec<QuasiIdentifierTypes>, f64),
+    /// mean of interval and temporal QI's where every value has the weight at the same position
+    WeightedMean(Vec<QuasiIdentifierTypes>, Vec<f64>),
     Mode(Vec<QuasiIdentifierTypes>),
+    /// most specific generalization of all the values of a hierarchical QI
+    LowestCommonAncestor(Vec<QuasiIdentifierTypes>),
+    /// centroid of geo points on the sphere
+    SphericalCentroid(Vec<QuasiIdentifierTypes>),
 }
 
 impl AggregateType {
-    /// the vector should contain only the same type of QI type
+    /// aggregate the QI's into a single one, return an error if the list contains
+    /// different QI types or the list is empty
+    pub fn try_aggregate(self) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        self.try_aggregate_with(Rounding::default())
+    }
+
+    /// aggregate the QI's into a single one and round integer values with the rounding,
+    /// return an error if the list contains different QI types or the list is empty
+    pub fn try_aggregate_with(
+        self,
+        rounding: Rounding,
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        let list = match &self {
+            AggregateType::Mean(list) => list,
+            AggregateType::Median(list) => list,
+            AggregateType::TrimmedMean(list, proportion) => match (0.0..0.5).contains(proportion) {
+                true => list,
+                false => {
+                    return Err(DiffPrivError::InvalidRecord(format!(
+                        "trimmed mean proportion {} is not in 0..0.5",
+                        proportion
+                    )))
+                }
+            },
+            AggregateType::WeightedMean(list, weights) => {
+                match weights.len() == list.len()
+                    && weights
+                        .iter()
+                        .all(|weight| weight.is_finite() && *weight >= 0.0)
+                {
+                    true => list,
+                    false => {
+                        return Err(DiffPrivError::InvalidRecord(
+                            "weighted mean needs a positive weight for every value".to_string(),
+                        ))
+                    }
+                }
+            }
+            AggregateType::Mode(list) => list,
+            AggregateType::LowestCommonAncestor(list) => list,
+            AggregateType::SphericalCentroid(list) => list,
+        };
+        let first = list.first().ok_or_else(|| {
+            DiffPrivError::InvalidRecord("can't aggregate an empty list of QI's".to_string())
+        })?;
+        list.iter().try_for_each(|qi| {
+            qi.validate()?;
+            qi.check_compatible(first)
+        })?;
+
+        let supported = matches!(
+            (&self, first.present()),
+            (
+                AggregateType::Mean(_)
+                    | AggregateType::Median(_)
+                    | AggregateType::TrimmedMean(..)
+                    | AggregateType::WeightedMean(..),
+                QuasiIdentifierTypes::Interval(_) | QuasiIdentifierTypes::Temporal(_)
+            ) | (AggregateType::Median(_), QuasiIdentifierTypes::Ordinal(_))
+                | (
+                    AggregateType::Mode(_),
+                    QuasiIdentifierTypes::Ordinal(_)
+                        | QuasiIdentifierTypes::Nominal(_)
+                        | QuasiIdentifierTypes::Hierarchical(_)
+                )
+                | (
+                    AggregateType::LowestCommonAncestor(_),
+                    QuasiIdentifierTypes::Hierarchical(_)
+                )
+                | (
+                    AggregateType::SphericalCentroid(_),
+                    QuasiIdentifierTypes::GeoPoint(_)
+                )
+        );
+        match supported {
+            true => Ok(self.aggregate_with(rounding)),
+            false => Err(DiffPrivError::TypeMismatch(format!(
+                "{} QI's can't be aggregated with this aggregation",
+                first.category_name()
+            ))),
+        }
+    }
+
+    /// the vector should contain only the same type of QI type.
+    /// Missing values are left out of the mean and the lowest common ancestor,
+    /// for the mode they count as a category of their own.
+    /// If all values are missing the result is missing as well.
+    /// Integer values are truncated, see `aggregate_with`
     pub fn aggregate(self) -> QuasiIdentifierTypes {
+        self.aggregate_with(Rounding::default())
+    }
+
+    /// aggregate the QI's like `aggregate` and round integer values with the rounding
+    pub fn aggregate_with(self, rounding: Rounding) -> QuasiIdentifierTypes {
         match self {
-            AggregateType::Mean(mut list) => match list.pop().unwrap() {
-                QuasiIdentifierTypes::Interval(interval) => {
-                    Self::aggregate_interval(interval, list)
-                }
-                _ => panic!("Wrong QI type found during aggregation for Mean"),
+            AggregateType::Mean(list) => {
+                Self::aggregate_numerical(Self::unweighted(list), rounding, weighted_mean)
+            }
+            AggregateType::Median(list) => match list.iter().find(|qi| !qi.is_missing()) {
+                Some(QuasiIdentifierTypes::Ordinal(_)) => Self::aggregate_ordinal_median(list),
+                _ => Self::aggregate_numerical(Self::unweighted(list), rounding, median),
             },
-            AggregateType::Mode(mut list) => {
+            AggregateType::TrimmedMean(list, proportion) => {
+                Self::aggregate_numerical(Self::unweighted(list), rounding, |values| {
+                    trimmed_mean(values, proportion)
+                })
+            }
+            AggregateType::WeightedMean(list, weights) => Self::aggregate_numerical(
+                list.into_iter().zip(weights).collect(),
+                rounding,
+                weighted_mean,
+            ),
+            AggregateType::Mode(list) => {
+                let (mut missing, mut list) = Self::partition_missing(list);
+                let values: Vec<QuasiIdentifierType> = list
+                    .iter()
+                    .cloned()
+                    .map(QuasiIdentifierTypes::extract_value)
+                    .collect();
+
                 // we need to pop the first element one to
                 // get the shared attributes between different elements in the QI list
-                match list.pop().unwrap() {
-                    QuasiIdentifierTypes::Ordinal(ordinal) => {
+                let mode = match list.pop() {
+                    None => return missing.pop().unwrap(),
+                    Some(QuasiIdentifierTypes::Ordinal(ordinal)) => {
                         Self::aggregate_ordinal(ordinal, list)
                     }
-                    QuasiIdentifierTypes::Nominal(nominal) => {
+                    Some(QuasiIdentifierTypes::Nominal(nominal)) => {
                         Self::aggregate_nominal(nominal, list)
                     }
+                    Some(QuasiIdentifierTypes::Hierarchical(hierarchical)) => {
+                        Self::aggregate_hierarchical(hierarchical, list)
+                    }
                     _ => panic!("Wrong QI type for calculating mode"),
+                };
+
+                let mode_value = mode.clone().extract_value();
+                let frequency = values.into_iter().filter(|x| *x == mode_value).count();
+                match missing.len() > frequency {
+                    true => QuasiIdentifierTypes::Missing(Box::new(mode)),
+                    false => mode,
+                }
+            }
+            AggregateType::LowestCommonAncestor(list) => {
+                let (mut missing, mut list) = Self::partition_missing(list);
+                match list.pop() {
+                    None => missing.pop().unwrap(),
+                    Some(QuasiIdentifierTypes::Hierarchical(hierarchical)) => {
+                        Self::aggregate_lowest_common_ancestor(hierarchical, list)
+                    }
+                    _ => panic!("Wrong QI type for calculating lowest common ancestor"),
+                }
+            }
+            AggregateType::SphericalCentroid(list) => {
+                let (mut missing, mut list) = Self::partition_missing(list);
+                match list.pop() {
+                    None => missing.pop().unwrap(),
+                    Some(QuasiIdentifierTypes::GeoPoint(geo_point)) => {
+                        Self::aggregate_geo_point(geo_point, list)
+                    }
+                    _ => panic!("Wrong QI type for calculating spherical centroid"),
                 }
             }
         }
     }
 
-    /// aggregate interval QI type
-    fn aggregate_interval(
-        interval: IntervalType,
+    /// split the list into the missing and the present values
+    fn partition_missing(
         list: Vec<QuasiIdentifierTypes>,
-    ) -> QuasiIdentifierTypes {
-        // we need to increment is by one as the first interval has already been popped
-        let size = list.len() + 1;
-        match interval {
-            (
-                QuasiIdentifierType::Float(value),
-                QuasiIdentifierType::Float(min),
-                QuasiIdentifierType::Float(max),
-                weight,
-            ) => {
-                let sum: f64 = list
-                    .into_iter()
-                    .map(|x| match x.extract_value() {
-                        QuasiIdentifierType::Float(temp) => temp,
-                        _ => panic!("Wrong type found for Mean aggregation"),
-                    })
-                    .sum();
+    ) -> (Vec<QuasiIdentifierTypes>, Vec<QuasiIdentifierTypes>) {
+        list.into_iter().partition(QuasiIdentifierTypes::is_missing)
+    }
 
-                QuasiIdentifierTypes::Interval((
-                    QuasiIdentifierType::Float((value + sum) / size as f64),
-                    QuasiIdentifierType::Float(min),
-                    QuasiIdentifierType::Float(max),
-                    weight,
-                ))
+    /// give every QI a weight of 1
+    fn unweighted(list: Vec<QuasiIdentifierTypes>) -> Vec<(QuasiIdentifierTypes, f64)> {
+        list.into_iter().map(|qi| (qi, 1.0)).collect()
+    }
+
+    /// aggregate interval or temporal QI's into the value the centroid function computes
+    /// from their values and weights, missing values are left out
+    fn aggregate_numerical<F>(
+        list: Vec<(QuasiIdentifierTypes, f64)>,
+        rounding: Rounding,
+        centroid: F,
+    ) -> QuasiIdentifierTypes
+    where
+        F: Fn(&mut [(f64, f64)]) -> f64,
+    {
+        let (mut missing, list): (Vec<_>, Vec<_>) =
+            list.into_iter().partition(|(qi, _)| qi.is_missing());
+        let mut values: Vec<(f64, f64)> = list
+            .iter()
+            .map(|(qi, weight)| match qi {
+                QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(value), ..)) => {
+                    (*value as f64, *weight)
+                }
+                QuasiIdentifierTypes::Interval((QuasiIdentifierType::Float(value), ..)) => {
+                    (*value, *weight)
+                }
+                QuasiIdentifierTypes::Temporal((timestamp, ..)) => (*timestamp as f64, *weight),
+                _ => panic!("Wrong QI type found during numerical aggregation"),
+            })
+            .collect();
+
+        match list.into_iter().next() {
+            None => missing.pop().unwrap().0,
+            Some((QuasiIdentifierTypes::Interval((value, min, max, weight)), _)) => {
+                let value = match value {
+                    QuasiIdentifierType::Integer(_) => {
+                        QuasiIdentifierType::Integer(rounding.round(centroid(&mut values)) as i32)
+                    }
+                    _ => QuasiIdentifierType::Float(centroid(&mut values)),
+                };
+                QuasiIdentifierTypes::Interval((value, min, max, weight))
             }
-            (
-                QuasiIdentifierType::Integer(value),
-                QuasiIdentifierType::Integer(min),
-                QuasiIdentifierType::Integer(max),
-                weight,
-            ) => {
-                let sum: i32 = list
-                    .into_iter()
-                    .map(|x| match x.extract_value() {
-                        QuasiIdentifierType::Integer(temp) => temp,
-                        _ => panic!("Wrong type found for Mean aggregation"),
-                    })
-                    .sum();
+            Some((QuasiIdentifierTypes::Temporal(temporal), _)) => {
+                let timestamps: Vec<i64> = values.iter().map(|(x, _)| *x as i64).collect();
+                let spread = timestamps.iter().max().unwrap() - timestamps.iter().min().unwrap();
+                let centroid = rounding.round(centroid(&mut values)) as i64;
+                Self::generalize_temporal(temporal, centroid, spread)
+            }
+            _ => panic!("Wrong QI type found during numerical aggregation"),
+        }
+    }
 
-                QuasiIdentifierTypes::Interval((
-                    QuasiIdentifierType::Integer((value + sum) / size as i32),
-                    QuasiIdentifierType::Integer(min),
-                    QuasiIdentifierType::Integer(max),
-                    weight,
-                ))
+    /// aggregate ordinal QI type into the middle rank, the lower one for an even amount of values
+    fn aggregate_ordinal_median(list: Vec<QuasiIdentifierTypes>) -> QuasiIdentifierTypes {
+        let (mut missing, list) = Self::partition_missing(list);
+        let mut ranks: Vec<i32> = list
+            .iter()
+            .map(|x| match x {
+                QuasiIdentifierTypes::Ordinal((rank, _, _)) => *rank,
+                _ => panic!("Wrong QI type"),
+            })
+            .collect();
+        ranks.sort_unstable();
+
+        match list.into_iter().next() {
+            Some(QuasiIdentifierTypes::Ordinal((_, max_rank, weight))) => {
+                QuasiIdentifierTypes::Ordinal((ranks[(ranks.len() - 1) / 2], max_rank, weight))
             }
-            _ => panic!("Wrong interval type set found during aggregation"),
+            _ => missing.pop().unwrap(),
         }
     }
 
+    /// aggregate geo point QI type into the centroid of the points on the sphere
+    fn aggregate_geo_point(
+        geo_point: GeoPointType,
+        list: Vec<QuasiIdentifierTypes>,
+    ) -> QuasiIdentifierTypes {
+        let (point, domain, weight) = geo_point;
+        let mut points: Vec<GeoPoint> = list
+            .into_iter()
+            .map(|x| match x {
+                QuasiIdentifierTypes::GeoPoint((temp, _, _)) => temp,
+                _ => panic!("Wrong QI type"),
+            })
+            .collect();
+        points.push(point);
+
+        QuasiIdentifierTypes::GeoPoint((spherical_centroid(&points).unwrap(), domain, weight))
+    }
+
+    /// generalize the centroid of a temporal QI by climbing the granularity ladder
+    /// until a unit covers the spread of the timestamps, and truncating the centroid to it
+    fn generalize_temporal(
+        temporal: TemporalType,
+        centroid: i64,
+        spread: i64,
+    ) -> QuasiIdentifierTypes {
+        let (_, _, domain, weight) = temporal;
+        let granularity = domain.granularity.generalize(spread);
+
+        QuasiIdentifierTypes::Temporal((
+            granularity.truncate(centroid),
+            granularity,
+            domain,
+            weight,
+        ))
+    }
+
     /// aggregate ordinal QI type
     fn aggregate_ordinal(
         ordinal: OrdinalType,
@@ -127,6 +410,41 @@ impl AggregateType {
         QuasiIdentifierTypes::Nominal((mode, max_value, weight))
     }
 
+    /// aggregate hierarchical QI type into its mode
+    fn aggregate_hierarchical(
+        hierarchical: HierarchicalType,
+        list: Vec<QuasiIdentifierTypes>,
+    ) -> QuasiIdentifierTypes {
+        let (value, hierarchy, weight) = hierarchical;
+        let mut mode_list = Vec::new();
+        list.into_iter().for_each(|x| match x {
+            QuasiIdentifierTypes::Hierarchical((temp, _, _)) => mode_list.push(temp),
+            _ => panic!("Wrong QI type"),
+        });
+
+        mode_list.push(value);
+
+        let mode = Self::get_mode(mode_list);
+
+        QuasiIdentifierTypes::Hierarchical((mode, hierarchy, weight))
+    }
+
+    /// aggregate hierarchical QI type into the lowest common ancestor of all values
+    fn aggregate_lowest_common_ancestor(
+        hierarchical: HierarchicalType,
+        list: Vec<QuasiIdentifierTypes>,
+    ) -> QuasiIdentifierTypes {
+        let (value, hierarchy, weight) = hierarchical;
+        let ancestor = list.into_iter().fold(value, |ancestor, x| match x {
+            QuasiIdentifierTypes::Hierarchical((temp, _, _)) => {
+                hierarchy.lowest_common_ancestor(ancestor, temp)
+            }
+            _ => panic!("Wrong QI type"),
+        });
+
+        QuasiIdentifierTypes::Hierarchical((ancestor, hierarchy, weight))
+    }
+
     /// retrieve mode from list of i32
     fn get_mode(mode_list: Vec<i32>) -> i32 {
         let mut mode_grouped: Vec<(i32, Vec<i32>)> = Vec::new();
@@ -144,6 +462,455 @@ impl AggregateType {
     }
 }
 
+/// aggregation of the values of a single QI that is updated one value at a time,
+/// so a centroid doesn't need to aggregate all the values of its buffer again for every
+/// new tuple. Gives the same result as `AggregateType::aggregate_with` on the added values,
+/// up to the rounding of the float sum of a `TrimmedMean`
+#[derive(Debug, Clone, Default)]
+pub struct RunningAggregate {
+    aggregator: Aggregator,
+    state: Option<RunningState>, // chosen with the first present value
+    template: Option<QuasiIdentifierTypes>, // latest present value, holds the attributes shared by all values
+    missing: Option<QuasiIdentifierTypes>,  // latest missing value
+    missing_amount: usize,
+    spread: Option<(i64, i64)>, // lowest and highest timestamp of temporal values
+}
+
+#[derive(Debug, Clone)]
+enum RunningState {
+    Mean {
+        sum: f64,
+        weighted_sum: f64,
+        total_weight: f64,
+        amount: usize,
+        weighted: bool,
+    },
+    /// the lower half of the values in a max heap and the upper half in a min heap,
+    /// the lower half holds the middle value
+    Median {
+        lower: BinaryHeap<TotalOrder>,
+        upper: BinaryHeap<Reverse<TotalOrder>>,
+    },
+    /// the trimmed lowest values in a max heap and the trimmed highest values in a min heap,
+    /// the kept values in between with their frequency and their sum
+    TrimmedMean {
+        proportion: f64,
+        lowest: BinaryHeap<TotalOrder>,
+        highest: BinaryHeap<Reverse<TotalOrder>>,
+        kept: BTreeMap<TotalOrder, usize>,
+        sum: f64,
+        amount: usize, // amount of all values
+    },
+    /// frequency of every value, the mode and its frequency
+    Mode {
+        frequencies: HashMap<i32, usize>,
+        mode: (i32, usize),
+    },
+    LowestCommonAncestor(i32),
+    /// first point and the sum of the unit vectors of all points
+    SphericalCentroid {
+        first: GeoPoint,
+        sum: (f64, f64, f64),
+        amount: usize,
+    },
+}
+
+/// float ordered by `f64::total_cmp` to be used in a heap
+#[derive(Debug, Copy, Clone)]
+struct TotalOrder(f64);
+
+impl PartialEq for TotalOrder {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalOrder {}
+
+impl PartialOrd for TotalOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalOrder {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl RunningAggregate {
+    pub fn new(aggregator: Aggregator) -> Self {
+        Self {
+            aggregator,
+            ..Self::default()
+        }
+    }
+
+    /// whether the aggregator can be updated one value at a time, an invalid
+    /// trimmed mean proportion is left to `AggregateType::try_aggregate_with`
+    pub fn supports(aggregator: Aggregator) -> bool {
+        match aggregator {
+            Aggregator::TrimmedMean(proportion) => (0.0..0.5).contains(&proportion),
+            _ => true,
+        }
+    }
+
+    /// add a value with its weight, the weight is only used by `WeightedMean`
+    pub fn add(&mut self, qi: QuasiIdentifierTypes, weight: f64) {
+        if qi.is_missing() {
+            self.missing_amount += 1;
+            self.missing = Some(qi);
+            return;
+        }
+
+        if let QuasiIdentifierTypes::Temporal((timestamp, ..)) = qi {
+            self.spread = Some(match self.spread {
+                None => (timestamp, timestamp),
+                Some((min, max)) => (min.min(timestamp), max.max(timestamp)),
+            });
+        }
+
+        let state = self
+            .state
+            .get_or_insert_with(|| RunningState::start(self.aggregator, &qi));
+        match state {
+            RunningState::Mean {
+                sum,
+                weighted_sum,
+                total_weight,
+                amount,
+                weighted,
+            } => {
+                let weight = match weighted {
+                    true => weight,
+                    false => 1.0,
+                };
+                let value = numerical_value(&qi);
+                *sum += value;
+                *weighted_sum += value * weight;
+                *total_weight += weight;
+                *amount += 1;
+            }
+            RunningState::Median { lower, upper } => {
+                let value = match &qi {
+                    QuasiIdentifierTypes::Ordinal((rank, _, _)) => TotalOrder(*rank as f64),
+                    qi => TotalOrder(numerical_value(qi)),
+                };
+                match lower.peek() {
+                    Some(middle) if value > *middle => upper.push(Reverse(value)),
+                    _ => lower.push(value),
+                }
+                // keep the middle value on top of the lower half
+                if lower.len() > upper.len() + 1 {
+                    upper.push(Reverse(lower.pop().unwrap()));
+                } else if upper.len() > lower.len() {
+                    lower.push(upper.pop().unwrap().0);
+                }
+            }
+            RunningState::TrimmedMean {
+                proportion,
+                lowest,
+                highest,
+                kept,
+                sum,
+                amount,
+            } => {
+                let value = TotalOrder(numerical_value(&qi));
+                match (lowest.peek(), highest.peek()) {
+                    (Some(low), _) if value < *low => lowest.push(value),
+                    (_, Some(Reverse(high))) if value > *high => highest.push(Reverse(value)),
+                    _ => {
+                        *kept.entry(value).or_insert(0) += 1;
+                        *sum += value.0
+                    }
+                }
+                *amount += 1;
+
+                // trim the same amount of values as `trimmed_mean` at both ends
+                let trimmed = (*amount as f64 * *proportion).floor() as usize;
+                while lowest.len() > trimmed {
+                    let value = lowest.pop().unwrap();
+                    *kept.entry(value).or_insert(0) += 1;
+                    *sum += value.0
+                }
+                while highest.len() > trimmed {
+                    let value = highest.pop().unwrap().0;
+                    *kept.entry(value).or_insert(0) += 1;
+                    *sum += value.0
+                }
+                while lowest.len() < trimmed {
+                    let value = take_kept(kept, sum, false);
+                    lowest.push(value)
+                }
+                while highest.len() < trimmed {
+                    let value = take_kept(kept, sum, true);
+                    highest.push(Reverse(value))
+                }
+            }
+            RunningState::Mode { frequencies, mode } => {
+                let value = match &qi {
+                    QuasiIdentifierTypes::Ordinal((value, _, _))
+                    | QuasiIdentifierTypes::Nominal((value, _, _))
+                    | QuasiIdentifierTypes::Hierarchical((value, _, _)) => *value,
+                    _ => panic!("Wrong QI type for calculating mode"),
+                };
+                let frequency = frequencies.entry(value).or_insert(0);
+                *frequency += 1;
+                // ties go to the highest value like `AggregateType::get_mode`
+                if (*frequency, value) > (mode.1, mode.0) {
+                    *mode = (value, *frequency);
+                }
+            }
+            RunningState::LowestCommonAncestor(ancestor) => match &qi {
+                QuasiIdentifierTypes::Hierarchical((value, hierarchy, _)) => {
+                    *ancestor = hierarchy.lowest_common_ancestor(*ancestor, *value)
+                }
+                _ => panic!("Wrong QI type for calculating lowest common ancestor"),
+            },
+            RunningState::SphericalCentroid { sum, amount, .. } => match &qi {
+                QuasiIdentifierTypes::GeoPoint((point, _, _)) => {
+                    let (x, y, z) = point.to_cartesian();
+                    *sum = (sum.0 + x, sum.1 + y, sum.2 + z);
+                    *amount += 1;
+                }
+                _ => panic!("Wrong QI type for calculating spherical centroid"),
+            },
+        }
+        self.template = Some(qi);
+    }
+
+    /// the aggregation of all added values rounded with the rounding,
+    /// `None` when nothing has been added
+    pub fn aggregate(&self, rounding: Rounding) -> Option<QuasiIdentifierTypes> {
+        let (state, template) = match (&self.state, &self.template) {
+            (Some(state), Some(template)) => (state, template.clone()),
+            _ => return self.missing.clone(),
+        };
+
+        let aggregate = match state {
+            RunningState::Mean {
+                sum,
+                weighted_sum,
+                total_weight,
+                amount,
+                ..
+            } => {
+                let mean = match *total_weight > 0.0 {
+                    true => weighted_sum / total_weight,
+                    false => sum / *amount as f64,
+                };
+                self.numerical(template, mean, rounding)
+            }
+            RunningState::Median { lower, .. } => {
+                let median = lower.peek().unwrap().0;
+                match template {
+                    QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                        QuasiIdentifierTypes::Ordinal((median as i32, max_rank, weight))
+                    }
+                    template => self.numerical(template, median, rounding),
+                }
+            }
+            RunningState::TrimmedMean { kept, sum, .. } => {
+                let kept_amount: usize = kept.values().sum();
+                self.numerical(template, sum / kept_amount as f64, rounding)
+            }
+            RunningState::Mode { mode, .. } => {
+                let (value, frequency) = *mode;
+                let mode = match template {
+                    QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                        QuasiIdentifierTypes::Ordinal((value, max_rank, weight))
+                    }
+                    QuasiIdentifierTypes::Nominal((_, max_value, weight)) => {
+                        QuasiIdentifierTypes::Nominal((value, max_value, weight))
+                    }
+                    QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                        QuasiIdentifierTypes::Hierarchical((value, hierarchy, weight))
+                    }
+                    _ => panic!("Wrong QI type for calculating mode"),
+                };
+                match self.missing_amount > frequency {
+                    true => QuasiIdentifierTypes::Missing(Box::new(mode)),
+                    false => mode,
+                }
+            }
+            RunningState::LowestCommonAncestor(ancestor) => match template {
+                QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                    QuasiIdentifierTypes::Hierarchical((*ancestor, hierarchy, weight))
+                }
+                _ => panic!("Wrong QI type for calculating lowest common ancestor"),
+            },
+            RunningState::SphericalCentroid { first, sum, amount } => match template {
+                QuasiIdentifierTypes::GeoPoint((_, domain, weight)) => {
+                    QuasiIdentifierTypes::GeoPoint((
+                        spherical_centroid_of_sum(*first, *sum, *amount),
+                        domain,
+                        weight,
+                    ))
+                }
+                _ => panic!("Wrong QI type for calculating spherical centroid"),
+            },
+        };
+
+        Some(aggregate)
+    }
+
+    /// interval or temporal QI with the aggregated value
+    fn numerical(
+        &self,
+        template: QuasiIdentifierTypes,
+        value: f64,
+        rounding: Rounding,
+    ) -> QuasiIdentifierTypes {
+        match template {
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(_), min, max, weight)) => {
+                QuasiIdentifierTypes::Interval((
+                    QuasiIdentifierType::Integer(rounding.round(value) as i32),
+                    min,
+                    max,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::Interval((_, min, max, weight)) => {
+                QuasiIdentifierTypes::Interval((
+                    QuasiIdentifierType::Float(value),
+                    min,
+                    max,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::Temporal(temporal) => {
+                let (min, max) = self.spread.unwrap();
+                AggregateType::generalize_temporal(
+                    temporal,
+                    rounding.round(value) as i64,
+                    max - min,
+                )
+            }
+            _ => panic!("Wrong QI type found during numerical aggregation"),
+        }
+    }
+}
+
+impl RunningState {
+    /// empty state of the aggregator, `Aggregator::Default` chooses it
+    /// based on the data category of the first value
+    fn start(aggregator: Aggregator, qi: &QuasiIdentifierTypes) -> Self {
+        let aggregator = match (aggregator, qi) {
+            (Aggregator::Default, QuasiIdentifierTypes::Interval(_)) => Aggregator::Mean,
+            (Aggregator::Default, QuasiIdentifierTypes::Temporal((_, _, domain, _))) => {
+                match domain.median_centroid() {
+                    true => Aggregator::Median,
+                    false => Aggregator::Mean,
+                }
+            }
+            (Aggregator::Default, QuasiIdentifierTypes::GeoPoint(_)) => {
+                Aggregator::SphericalCentroid
+            }
+            (Aggregator::Default, QuasiIdentifierTypes::Hierarchical((_, hierarchy, _)))
+                if hierarchy.ancestor_centroid() =>
+            {
+                Aggregator::LowestCommonAncestor
+            }
+            (Aggregator::Default, _) => Aggregator::Mode,
+            (aggregator, _) => aggregator,
+        };
+
+        match (aggregator, qi) {
+            (Aggregator::Mean, _) | (Aggregator::WeightedMean, _) => RunningState::Mean {
+                sum: 0.0,
+                weighted_sum: 0.0,
+                total_weight: 0.0,
+                amount: 0,
+                weighted: aggregator == Aggregator::WeightedMean,
+            },
+            (Aggregator::Median, _) => RunningState::Median {
+                lower: BinaryHeap::new(),
+                upper: BinaryHeap::new(),
+            },
+            (Aggregator::TrimmedMean(proportion), _) => RunningState::TrimmedMean {
+                proportion,
+                lowest: BinaryHeap::new(),
+                highest: BinaryHeap::new(),
+                kept: BTreeMap::new(),
+                sum: 0.0,
+                amount: 0,
+            },
+            (Aggregator::Mode, _) => RunningState::Mode {
+                frequencies: HashMap::new(),
+                mode: (i32::MIN, 0),
+            },
+            (Aggregator::LowestCommonAncestor, QuasiIdentifierTypes::Hierarchical((value, ..))) => {
+                RunningState::LowestCommonAncestor(*value)
+            }
+            (Aggregator::SphericalCentroid, QuasiIdentifierTypes::GeoPoint((point, ..))) => {
+                RunningState::SphericalCentroid {
+                    first: *point,
+                    sum: (0.0, 0.0, 0.0),
+                    amount: 0,
+                }
+            }
+            (Aggregator::LowestCommonAncestor, _) => {
+                panic!("Wrong QI type for calculating lowest common ancestor")
+            }
+            (Aggregator::SphericalCentroid, _) => {
+                panic!("Wrong QI type for calculating spherical centroid")
+            }
+            (Aggregator::Default, _) => unreachable!("the aggregator has been chosen"),
+        }
+    }
+}
+
+/// remove the lowest or highest kept value of a trimmed mean and subtract it from the sum
+fn take_kept(kept: &mut BTreeMap<TotalOrder, usize>, sum: &mut f64, highest: bool) -> TotalOrder {
+    let mut entry = match highest {
+        true => kept.last_entry(),
+        false => kept.first_entry(),
+    }
+    .unwrap();
+    let value = *entry.key();
+    *entry.get_mut() -= 1;
+    if *entry.get() == 0 {
+        entry.remove();
+    }
+    *sum -= value.0;
+    value
+}
+
+/// value of an interval or temporal QI
+fn numerical_value(qi: &QuasiIdentifierTypes) -> f64 {
+    match qi {
+        QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(value), ..)) => *value as f64,
+        QuasiIdentifierTypes::Interval((QuasiIdentifierType::Float(value), ..)) => *value,
+        QuasiIdentifierTypes::Temporal((timestamp, ..)) => *timestamp as f64,
+        _ => panic!("Wrong QI type found during numerical aggregation"),
+    }
+}
+
+/// mean of the values weighted by the second element of the pairs, the plain mean
+/// when the weights don't add up to a positive number
+fn weighted_mean(values: &mut [(f64, f64)]) -> f64 {
+    let total_weight: f64 = values.iter().map(|(_, weight)| weight).sum();
+    match total_weight > 0.0 {
+        true => values.iter().map(|(x, weight)| x * weight).sum::<f64>() / total_weight,
+        false => values.iter().map(|(x, _)| x).sum::<f64>() / values.len() as f64,
+    }
+}
+
+/// middle value, the lower one for an even amount of values
+fn median(values: &mut [(f64, f64)]) -> f64 {
+    values.sort_by(|(x, _), (y, _)| x.partial_cmp(y).unwrap());
+    values[(values.len() - 1) / 2].0
+}
+
+/// mean of the values without the proportion of the lowest and highest ones
+fn trimmed_mean(values: &mut [(f64, f64)], proportion: f64) -> f64 {
+    values.sort_by(|(x, _), (y, _)| x.partial_cmp(y).unwrap());
+    let trimmed = (values.len() as f64 * proportion).floor() as usize;
+    let kept = &values[trimmed..values.len() - trimmed];
+    kept.iter().map(|(x, _)| x).sum::<f64>() / kept.len() as f64
+}
+
 /// check if the value that was randomly generated is contained within its domain
 pub fn truncate_to_domain<T: PartialOrd>(value: T, min: T, max: T) -> T {
     match value {
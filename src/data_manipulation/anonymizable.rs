@@ -1,16 +1,102 @@
-use crate::data_manipulation::aggregation::truncate_to_domain;
+use crate::anonymization::suppression::SuppressionStrategy;
+use crate::data_manipulation::aggregation::{truncate_to_domain, Aggregator, Rounding};
+use crate::data_manipulation::distance::{coordinate_deltas, DistanceMetric, Gower, QiDifference};
+use crate::data_manipulation::geo::{GeoDomain, GeoPoint};
+use crate::data_manipulation::hierarchy::Hierarchy;
+use crate::data_manipulation::temporal::{Granularity, TemporalDomain};
+use crate::error::DiffPrivError;
 use num::abs;
 use rand::distributions::{Distribution, Uniform};
-use rand::thread_rng;
+use rand::Rng;
 use rand_distr::Normal;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use std::time::SystemTime;
 use uuid::Uuid;
 
-#[derive(Hash, Eq, PartialEq)]
+pub use diff_priv_derive::Anonymizable;
+
+#[derive(Debug, Clone)]
 pub enum SensitiveAttribute {
     String(String),
     Integer(i32),
+    /// value and bucket width, floats falling into the same bucket are equal
+    /// so a numeric value like a salary doesn't give every tuple its own class
+    Float(f64, f64),
+}
+
+impl SensitiveAttribute {
+    /// position on the ordered ground used by t-closeness,
+    /// `None` for strings as they have no order
+    pub fn rank(&self) -> Option<i64> {
+        match self {
+            SensitiveAttribute::String(_) => None,
+            SensitiveAttribute::Integer(value) => Some(*value as i64),
+            SensitiveAttribute::Float(value, bucket_width) => {
+                Some((value / bucket_width).floor() as i64)
+            }
+        }
+    }
+}
+
+impl PartialEq for SensitiveAttribute {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SensitiveAttribute::String(x), SensitiveAttribute::String(y)) => x == y,
+            (SensitiveAttribute::Integer(x), SensitiveAttribute::Integer(y)) => x == y,
+            (SensitiveAttribute::Float(..), SensitiveAttribute::Float(..)) => {
+                self.rank() == other.rank()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SensitiveAttribute {}
+
+impl Hash for SensitiveAttribute {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            SensitiveAttribute::String(value) => value.hash(state),
+            _ => self.rank().hash(state),
+        }
+    }
+}
+
+impl Display for SensitiveAttribute {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SensitiveAttribute::String(value) => write!(f, "{}", value),
+            SensitiveAttribute::Integer(value) => write!(f, "{}", value),
+            SensitiveAttribute::Float(value, _) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// serialized as the plain value, without the bucket width of floats
+impl Serialize for SensitiveAttribute {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SensitiveAttribute::String(value) => serializer.serialize_str(value),
+            SensitiveAttribute::Integer(value) => serializer.serialize_i32(*value),
+            SensitiveAttribute::Float(value, _) => serializer.serialize_f64(*value),
+        }
+    }
+}
+
+impl From<String> for SensitiveAttribute {
+    fn from(value: String) -> Self {
+        SensitiveAttribute::String(value)
+    }
+}
+
+impl From<i32> for SensitiveAttribute {
+    fn from(value: i32) -> Self {
+        SensitiveAttribute::Integer(value)
+    }
 }
 
 /// value, min_value, max_value, weight of attribute
@@ -27,14 +113,23 @@ pub type OrdinalType = (i32, i32, usize);
 /// value, max value, weight of attribute
 pub type NominalType = (i32, i32, usize);
 
-#[derive(Debug, Copy, Clone)]
+/// value, generalization hierarchy, weight of attribute
+pub type HierarchicalType = (i32, Arc<Hierarchy>, usize);
+
+/// timestamp in seconds since the unix epoch, granularity of the timestamp, domain, weight of attribute
+pub type TemporalType = (i64, Granularity, TemporalDomain, usize);
+
+/// point on the earth, domain, weight of attribute
+pub type GeoPointType = (GeoPoint, GeoDomain, usize);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum QuasiIdentifierType {
     Float(f64),
     Integer(i32),
 }
 
 /// Possible quasi identifier data category types
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum QuasiIdentifierTypes {
     /// value, min_value, max_value, weight of attribute
     Interval(IntervalType),
@@ -42,20 +137,189 @@ pub enum QuasiIdentifierTypes {
     Ordinal(OrdinalType),
     /// value, weight of attribute
     Nominal(NominalType),
+    /// value, generalization hierarchy, weight of attribute
+    Hierarchical(HierarchicalType),
+    /// timestamp, granularity of the timestamp, domain, weight of attribute
+    Temporal(TemporalType),
+    /// point on the earth, domain, weight of attribute
+    GeoPoint(GeoPointType),
+    /// absent value, the boxed QI is a template containing the domain and weight
+    /// of the attribute, its value is never used
+    Missing(Box<QuasiIdentifierTypes>),
+}
+
+/// How absent QI values are handled by the distance calculation, the aggregation
+/// of centroids and the concept drift detection
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MissingValuePolicy {
+    /// leave the missing value out of distances and aggregations
+    #[default]
+    Ignore,
+    /// replace the missing value with the value of the cluster centroid
+    Impute,
+    /// treat a missing value as a category of its own, which has the maximum
+    /// distance to every present value
+    OwnCategory,
+}
+
+impl MissingValuePolicy {
+    /// distance and weight of a QI with the given weight that is missing in one or both tuples
+    pub fn missing_distance(&self, both_missing: bool, weight: usize) -> (f64, usize) {
+        match (both_missing, self) {
+            (_, MissingValuePolicy::Ignore) => (0.0, 0),
+            (true, _) | (false, MissingValuePolicy::Impute) => (0.0, weight),
+            (false, MissingValuePolicy::OwnCategory) => (weight as f64, weight),
+        }
+    }
 }
 
 impl QuasiIdentifierTypes {
     /// consume itself and extract the value of the quasi identifier
     pub fn extract_value(self) -> QuasiIdentifierType {
+        self.try_extract_value()
+            .expect("Can't extract the value of a missing QI or a geo point")
+    }
+
+    /// consume itself and extract the value of the quasi identifier, return `None` if the
+    /// value is missing or a geo point, which isn't a single value and is matched directly
+    pub fn try_extract_value(self) -> Option<QuasiIdentifierType> {
+        match self {
+            QuasiIdentifierTypes::Interval((value, _, _, _)) => Some(value),
+            QuasiIdentifierTypes::Ordinal((value, _, _)) => {
+                Some(QuasiIdentifierType::Integer(value))
+            }
+            QuasiIdentifierTypes::Nominal((value, _, _)) => {
+                Some(QuasiIdentifierType::Integer(value))
+            }
+            QuasiIdentifierTypes::Hierarchical((value, _, _)) => {
+                Some(QuasiIdentifierType::Integer(value))
+            }
+            QuasiIdentifierTypes::Temporal((timestamp, _, _, _)) => {
+                Some(QuasiIdentifierType::Float(timestamp as f64))
+            }
+            QuasiIdentifierTypes::GeoPoint(_) | QuasiIdentifierTypes::Missing(_) => None,
+        }
+    }
+
+    /// the QI itself, or the template of a missing QI
+    pub(crate) fn present(&self) -> &QuasiIdentifierTypes {
+        match self {
+            QuasiIdentifierTypes::Missing(template) => template.present(),
+            qi => qi,
+        }
+    }
+
+    /// name of the data category and value type, used in errors
+    pub fn category_name(&self) -> &'static str {
+        match self.present() {
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Integer(_), _, _, _)) => {
+                "integer interval"
+            }
+            QuasiIdentifierTypes::Interval((QuasiIdentifierType::Float(_), _, _, _)) => {
+                "float interval"
+            }
+            QuasiIdentifierTypes::Ordinal(_) => "ordinal",
+            QuasiIdentifierTypes::Nominal(_) => "nominal",
+            QuasiIdentifierTypes::Hierarchical(_) => "hierarchical",
+            QuasiIdentifierTypes::Temporal(_) => "temporal",
+            QuasiIdentifierTypes::GeoPoint(_) => "geo point",
+            QuasiIdentifierTypes::Missing(_) => "missing",
+        }
+    }
+
+    /// check that the value and the domain of the QI have the same type and that
+    /// categorical values are part of their domain
+    pub fn validate(&self) -> Result<(), DiffPrivError> {
+        let (value, lowest, max) = match self {
+            QuasiIdentifierTypes::Interval((value, min, max, _)) => {
+                return match (value, min, max) {
+                    (
+                        QuasiIdentifierType::Integer(_),
+                        QuasiIdentifierType::Integer(_),
+                        QuasiIdentifierType::Integer(_),
+                    )
+                    | (
+                        QuasiIdentifierType::Float(_),
+                        QuasiIdentifierType::Float(_),
+                        QuasiIdentifierType::Float(_),
+                    ) => Ok(()),
+                    _ => Err(DiffPrivError::TypeMismatch(
+                        "interval QI mixes integer and float values".to_string(),
+                    )),
+                }
+            }
+            // ordinal ranks start at 1, nominal values and hierarchy nodes at 0
+            QuasiIdentifierTypes::Ordinal((rank, max_rank, _)) => (*rank, 1, *max_rank),
+            QuasiIdentifierTypes::Nominal((value, max_value, _)) => (*value, 0, *max_value),
+            QuasiIdentifierTypes::Hierarchical((value, hierarchy, _)) => {
+                (*value, 0, hierarchy.node_count() as i32 - 1)
+            }
+            QuasiIdentifierTypes::Temporal((_, _, domain, _)) => {
+                return match domain.min < domain.max {
+                    true => Ok(()),
+                    false => Err(DiffPrivError::TypeMismatch(
+                        "temporal QI needs a domain with min before max".to_string(),
+                    )),
+                }
+            }
+            QuasiIdentifierTypes::GeoPoint((point, domain, _)) => {
+                return match (point.is_valid(), domain.max_distance > 0.0) {
+                    (true, true) => Ok(()),
+                    (false, _) => Err(DiffPrivError::UnknownCategory(format!(
+                        "geo point {} is outside of latitude -90..=90 and longitude -180..=180",
+                        point
+                    ))),
+                    (_, false) => Err(DiffPrivError::TypeMismatch(
+                        "geo point QI needs a positive max distance".to_string(),
+                    )),
+                }
+            }
+            QuasiIdentifierTypes::Missing(template) => return template.validate(),
+        };
+
+        match (lowest..=max).contains(&value) {
+            true => Ok(()),
+            false => Err(DiffPrivError::UnknownCategory(format!(
+                "{} value {} is outside of its domain {}..={}",
+                self.category_name(),
+                value,
+                lowest,
+                max
+            ))),
+        }
+    }
+
+    /// check that 2 QI's have the same data category and value type
+    /// so they can be compared and aggregated
+    pub fn check_compatible(&self, other: &QuasiIdentifierTypes) -> Result<(), DiffPrivError> {
+        match self.category_name() == other.category_name() {
+            true => Ok(()),
+            false => Err(DiffPrivError::TypeMismatch(format!(
+                "{} QI can't be combined with {} QI",
+                self.category_name(),
+                other.category_name()
+            ))),
+        }
+    }
+
+    pub fn is_missing(&self) -> bool {
+        matches!(self, QuasiIdentifierTypes::Missing(_))
+    }
+
+    /// weight of the attribute
+    pub fn weight(&self) -> usize {
         match self {
-            QuasiIdentifierTypes::Interval((value, _, _, _)) => value,
-            QuasiIdentifierTypes::Ordinal((value, _, _)) => QuasiIdentifierType::Integer(value),
-            QuasiIdentifierTypes::Nominal((value, _, _)) => QuasiIdentifierType::Integer(value),
+            QuasiIdentifierTypes::Interval((_, _, _, weight)) => *weight,
+            QuasiIdentifierTypes::Ordinal((_, _, weight)) => *weight,
+            QuasiIdentifierTypes::Nominal((_, _, weight)) => *weight,
+            QuasiIdentifierTypes::Hierarchical((_, _, weight)) => *weight,
+            QuasiIdentifierTypes::Temporal((_, _, _, weight)) => *weight,
+            QuasiIdentifierTypes::GeoPoint((_, _, weight)) => *weight,
+            QuasiIdentifierTypes::Missing(template) => template.weight(),
         }
     }
 
-    pub fn randomize(self) -> QuasiIdentifierTypes {
-        let mut rng = thread_rng();
+    pub fn randomize<R: Rng + ?Sized>(self, rng: &mut R) -> QuasiIdentifierTypes {
         match self {
             QuasiIdentifierTypes::Interval((value, min, max, weight)) => match (value, min, max) {
                 (
@@ -64,7 +328,7 @@ impl QuasiIdentifierTypes {
                     QuasiIdentifierType::Float(max_val),
                 ) => {
                     let normal: Normal<f64> = Normal::new(val_fl, 1.0).unwrap();
-                    let e = normal.sample(&mut rng);
+                    let e = normal.sample(rng);
                     QuasiIdentifierTypes::Interval((
                         QuasiIdentifierType::Float(truncate_to_domain(e, min_val, max_val)),
                         QuasiIdentifierType::Float(min_val),
@@ -78,7 +342,7 @@ impl QuasiIdentifierTypes {
                     QuasiIdentifierType::Integer(max_val),
                 ) => {
                     let normal: Normal<f64> = Normal::new(val_int as f64, 1.0).unwrap();
-                    let e = normal.sample(&mut rng);
+                    let e = normal.sample(rng);
                     QuasiIdentifierTypes::Interval((
                         QuasiIdentifierType::Integer(truncate_to_domain(
                             e as i32, min_val, max_val,
@@ -92,83 +356,294 @@ impl QuasiIdentifierTypes {
             },
             QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
                 let between = Uniform::<i32>::from(0..max_rank + 1);
-                let random_ordinal_qi = between.sample(&mut rng);
-                QuasiIdentifierTypes::Ordinal((random_ordinal_qi as i32, max_rank, weight))
+                let random_ordinal_qi = between.sample(rng);
+                QuasiIdentifierTypes::Ordinal((random_ordinal_qi, max_rank, weight))
             }
             QuasiIdentifierTypes::Nominal((_, max_value, weight)) => {
                 let between = Uniform::<i32>::from(0..max_value + 1);
-                let random_nominal_qi = between.sample(&mut rng);
+                let random_nominal_qi = between.sample(rng);
                 QuasiIdentifierTypes::Nominal((random_nominal_qi, max_value, weight))
             }
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                let between = Uniform::<i32>::from(0..hierarchy.leaf_count() as i32);
+                let random_leaf = between.sample(rng);
+                QuasiIdentifierTypes::Hierarchical((random_leaf, hierarchy, weight))
+            }
+            QuasiIdentifierTypes::Temporal((timestamp, granularity, domain, weight)) => {
+                let normal: Normal<f64> =
+                    Normal::new(timestamp as f64, domain.granularity.seconds() as f64).unwrap();
+                let e = normal.sample(rng) as i64;
+                QuasiIdentifierTypes::Temporal((
+                    granularity.truncate(truncate_to_domain(e, domain.min, domain.max)),
+                    granularity,
+                    domain,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::GeoPoint((point, domain, weight)) => {
+                let normal: Normal<f64> = Normal::new(0.0, domain.privacy_radius).unwrap();
+                let bearing = Uniform::<f64>::from(0.0..std::f64::consts::TAU).sample(rng);
+                let distance = normal.sample(rng).abs();
+                QuasiIdentifierTypes::GeoPoint((
+                    point.destination(bearing, distance),
+                    domain,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::Missing(template) => template.randomize(rng),
+        }
+    }
+
+    /// replace the value with the midpoint of the domain, the root for hierarchical QI's
+    /// and the first category for nominal QI's as they have no order
+    pub fn midpoint(self) -> QuasiIdentifierTypes {
+        match self {
+            QuasiIdentifierTypes::Interval((_, min, max, weight)) => match (min, max) {
+                (QuasiIdentifierType::Float(min_val), QuasiIdentifierType::Float(max_val)) => {
+                    QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Float((min_val + max_val) / 2.0),
+                        min,
+                        max,
+                        weight,
+                    ))
+                }
+                (QuasiIdentifierType::Integer(min_val), QuasiIdentifierType::Integer(max_val)) => {
+                    QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Integer(min_val + (max_val - min_val) / 2),
+                        min,
+                        max,
+                        weight,
+                    ))
+                }
+                _ => panic!("Wrong combination of type found in midpoint of interval"),
+            },
+            QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                QuasiIdentifierTypes::Ordinal(((1 + max_rank) / 2, max_rank, weight))
+            }
+            QuasiIdentifierTypes::Nominal((_, max_value, weight)) => {
+                QuasiIdentifierTypes::Nominal((0, max_value, weight))
+            }
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                QuasiIdentifierTypes::Hierarchical((hierarchy.root(), hierarchy, weight))
+            }
+            QuasiIdentifierTypes::Temporal((_, granularity, domain, weight)) => {
+                QuasiIdentifierTypes::Temporal((
+                    granularity.truncate(domain.min + (domain.max - domain.min) / 2),
+                    granularity,
+                    domain,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::GeoPoint((_, domain, weight)) => {
+                QuasiIdentifierTypes::GeoPoint((GeoPoint::default(), domain, weight))
+            }
+            QuasiIdentifierTypes::Missing(template) => template.midpoint(),
+        }
+    }
+
+    /// replace the value with a value sampled uniformly from the domain,
+    /// the sample is independent of the original value
+    pub fn sample_uniform<R: Rng + ?Sized>(self, rng: &mut R) -> QuasiIdentifierTypes {
+        match self {
+            QuasiIdentifierTypes::Interval((_, min, max, weight)) => match (min, max) {
+                (QuasiIdentifierType::Float(min_val), QuasiIdentifierType::Float(max_val)) => {
+                    QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Float(
+                            Uniform::<f64>::new_inclusive(min_val, max_val).sample(rng),
+                        ),
+                        min,
+                        max,
+                        weight,
+                    ))
+                }
+                (QuasiIdentifierType::Integer(min_val), QuasiIdentifierType::Integer(max_val)) => {
+                    QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Integer(
+                            Uniform::<i32>::new_inclusive(min_val, max_val).sample(rng),
+                        ),
+                        min,
+                        max,
+                        weight,
+                    ))
+                }
+                _ => panic!("Wrong combination of type found in sampling of interval"),
+            },
+            QuasiIdentifierTypes::Ordinal((_, max_rank, weight)) => {
+                let rank = Uniform::<i32>::new_inclusive(1, max_rank).sample(rng);
+                QuasiIdentifierTypes::Ordinal((rank, max_rank, weight))
+            }
+            QuasiIdentifierTypes::Nominal((_, max_value, weight)) => {
+                let value = Uniform::<i32>::new_inclusive(0, max_value).sample(rng);
+                QuasiIdentifierTypes::Nominal((value, max_value, weight))
+            }
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => {
+                let leaf = Uniform::<i32>::from(0..hierarchy.leaf_count() as i32).sample(rng);
+                QuasiIdentifierTypes::Hierarchical((leaf, hierarchy, weight))
+            }
+            QuasiIdentifierTypes::Temporal((_, granularity, domain, weight)) => {
+                let timestamp = Uniform::<i64>::new_inclusive(domain.min, domain.max).sample(rng);
+                QuasiIdentifierTypes::Temporal((
+                    granularity.truncate(timestamp),
+                    granularity,
+                    domain,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::GeoPoint((_, domain, weight)) => {
+                // uniform over the surface of the sphere instead of the lat/lon rectangle
+                let z = Uniform::<f64>::new_inclusive(-1.0, 1.0).sample(rng);
+                let longitude = Uniform::<f64>::from(-180.0..180.0).sample(rng);
+                QuasiIdentifierTypes::GeoPoint((
+                    GeoPoint::new(z.asin().to_degrees(), longitude),
+                    domain,
+                    weight,
+                ))
+            }
+            QuasiIdentifierTypes::Missing(template) => template.sample_uniform(rng),
         }
     }
 }
 
 /// The role of this trait is to create a generic way of making sure that the struct can be anonymized
-/// using the Anonymizer
-pub trait Anonymizable: Default + Clone + Serialize + Sync {
+/// using the Anonymizer. It is `'static` so analysers of the struct can be boxed
+pub trait Anonymizable: Default + Clone + Serialize + Sync + 'static {
     /// compare 2 data points and return the euclidean difference between them
+    /// missing values are handled according to the `MissingValuePolicy`.
+    /// Panics when the QI's of both can't be compared, see `try_calculate_difference`
     fn calculate_difference(&self, other: &Self) -> f64 {
-        let mut sum_weight: usize = 0;
-        let diff: f64 = self
-            .quasi_identifiers()
+        self.try_calculate_difference(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// compare 2 data points and return the euclidean difference between them,
+    /// return an error when the QI's of both can't be compared
+    fn try_calculate_difference(&self, other: &Self) -> Result<f64, DiffPrivError> {
+        Ok(Gower.distance(&self.try_qi_differences(other)?))
+    }
+
+    /// compare the QI's of 2 data points one by one, to be combined by a `DistanceMetric`.
+    /// Return an error when the QI's of both can't be compared
+    fn try_qi_differences(&self, other: &Self) -> Result<Vec<QiDifference>, DiffPrivError> {
+        let policy = self.missing_value_policy();
+        let self_qi = self.try_quasi_identifiers()?;
+        let other_qi = other.try_quasi_identifiers()?;
+        if self_qi.len() != other_qi.len() {
+            return Err(DiffPrivError::InvalidRecord(format!(
+                "can't compare {} QI's with {} QI's",
+                self_qi.len(),
+                other_qi.len()
+            )));
+        }
+
+        self_qi
             .into_iter()
-            .zip(other.quasi_identifiers().into_iter())
-            .map(|(x, y)| match (x, y) {
-                (
-                    QuasiIdentifierTypes::Interval(interval_x),
-                    QuasiIdentifierTypes::Interval(interval_y),
-                ) => {
-                    let (_, _, _, weight) = interval_x;
-                    sum_weight += weight;
-                    Self::calculate_interval_distance(interval_x, interval_y)
-                }
-                (
-                    QuasiIdentifierTypes::Ordinal(ordinal_x),
-                    QuasiIdentifierTypes::Ordinal(ordinal_y),
-                ) => {
-                    let (_, _, weight) = ordinal_x;
-                    sum_weight += weight;
-                    Self::calculate_ordinal_distance(ordinal_x, ordinal_y)
-                }
-                (
-                    QuasiIdentifierTypes::Nominal(nominal_x),
-                    QuasiIdentifierTypes::Nominal(nominal_y),
-                ) => {
-                    let (_, _, weight) = nominal_x;
-                    sum_weight += weight;
-                    Self::calculate_nominal_distance(nominal_x, nominal_y)
-                }
-                _ => {
-                    panic!("wrong types provided")
-                }
+            .zip(other_qi)
+            .map(|(x, y)| {
+                x.validate()?;
+                y.validate()?;
+                x.check_compatible(&y)?;
+                Ok(Self::qi_difference(x, y, policy))
             })
-            .sum();
+            .collect()
+    }
+
+    /// compare the values of a single QI of 2 data points, the QI's have to be compatible
+    fn qi_difference(
+        x: QuasiIdentifierTypes,
+        y: QuasiIdentifierTypes,
+        policy: MissingValuePolicy,
+    ) -> QiDifference {
+        let deltas = coordinate_deltas(&x, &y);
+        let (distance, weight) = match (x, y) {
+            (x, y) if x.is_missing() || y.is_missing() => {
+                Self::calculate_missing_distance(&x, &y, policy)
+            }
+            (
+                QuasiIdentifierTypes::Interval(interval_x),
+                QuasiIdentifierTypes::Interval(interval_y),
+            ) => (
+                Self::calculate_interval_distance(interval_x, interval_y),
+                interval_x.3,
+            ),
+            (
+                QuasiIdentifierTypes::Ordinal(ordinal_x),
+                QuasiIdentifierTypes::Ordinal(ordinal_y),
+            ) => (
+                Self::calculate_ordinal_distance(ordinal_x, ordinal_y),
+                ordinal_x.2,
+            ),
+            (
+                QuasiIdentifierTypes::Nominal(nominal_x),
+                QuasiIdentifierTypes::Nominal(nominal_y),
+            ) => (
+                Self::calculate_nominal_distance(nominal_x, nominal_y),
+                nominal_x.2,
+            ),
+            (
+                QuasiIdentifierTypes::Hierarchical(hierarchical_x),
+                QuasiIdentifierTypes::Hierarchical(hierarchical_y),
+            ) => {
+                let weight = hierarchical_x.2;
+                (
+                    Self::calculate_hierarchical_distance(hierarchical_x, hierarchical_y),
+                    weight,
+                )
+            }
+            (
+                QuasiIdentifierTypes::Temporal(temporal_x),
+                QuasiIdentifierTypes::Temporal(temporal_y),
+            ) => (
+                Self::calculate_temporal_distance(temporal_x, temporal_y),
+                temporal_x.3,
+            ),
+            (
+                QuasiIdentifierTypes::GeoPoint(geo_point_x),
+                QuasiIdentifierTypes::GeoPoint(geo_point_y),
+            ) => (
+                Self::calculate_geo_point_distance(geo_point_x, geo_point_y),
+                geo_point_x.2,
+            ),
+            _ => unreachable!("QI's have been checked to be compatible"),
+        };
 
-        diff / sum_weight as f64
+        QiDifference {
+            distance,
+            weight,
+            deltas,
+        }
     }
 
     /// calculate the info loss between 2 different Anonymizable
-    /// structs
+    /// structs, QI's that are missing in one of both are skipped
     fn calculate_info_loss(&self, other: &Self) -> f64 {
         let mut distance = 0.0;
         let self_qi = self.quasi_identifiers();
         let other_qi = other.quasi_identifiers();
 
-        self_qi
-            .into_iter()
-            .zip(other_qi.into_iter())
-            .for_each(|(x, y)| match (x.extract_value(), y.extract_value()) {
-                (QuasiIdentifierType::Integer(value1), QuasiIdentifierType::Integer(value2)) => {
-                    distance += (value1 as f64 - value2 as f64).powi(2)
-                }
-                (QuasiIdentifierType::Float(value1), QuasiIdentifierType::Float(value2)) => {
-                    distance += (value1 - value2).powi(2)
-                }
+        self_qi.into_iter().zip(other_qi).for_each(|(x, y)| {
+            if let (
+                QuasiIdentifierTypes::GeoPoint((point1, _, _)),
+                QuasiIdentifierTypes::GeoPoint((point2, _, _)),
+            ) = (&x, &y)
+            {
+                distance += point1.haversine(point2).powi(2);
+                return;
+            }
+            match (x.try_extract_value(), y.try_extract_value()) {
+                (None, _) | (_, None) => {}
+                (
+                    Some(QuasiIdentifierType::Integer(value1)),
+                    Some(QuasiIdentifierType::Integer(value2)),
+                ) => distance += (value1 as f64 - value2 as f64).powi(2),
+                (
+                    Some(QuasiIdentifierType::Float(value1)),
+                    Some(QuasiIdentifierType::Float(value2)),
+                ) => distance += (value1 - value2).powi(2),
                 _ => {
                     panic!("Incompatible values have been found")
                 }
-            });
+            }
+        });
 
         distance.sqrt()
     }
@@ -176,27 +651,101 @@ pub trait Anonymizable: Default + Clone + Serialize + Sync {
     /// return the values of the quasi identifiers in the data struct
     fn quasi_identifiers(&self) -> Vec<QuasiIdentifierTypes>;
 
+    /// return the values of the quasi identifiers in the data struct,
+    /// return an error instead of panicking on a bad record
+    fn try_quasi_identifiers(&self) -> Result<Vec<QuasiIdentifierTypes>, DiffPrivError> {
+        Ok(self.quasi_identifiers())
+    }
+
     /// return a copy of the Anonymizable struct and replace its
     /// quasi identifier attributes with given QI's
     /// we return a copy because we want to keep the original intact for new aggregation
     fn update_quasi_identifiers(&self, qi: Vec<QuasiIdentifierTypes>) -> Self;
 
+    /// replace the quasi identifier attributes of a copy with given QI's,
+    /// return an error instead of panicking when the QI's don't fit
+    fn try_update_quasi_identifiers(
+        &self,
+        qi: Vec<QuasiIdentifierTypes>,
+    ) -> Result<Self, DiffPrivError> {
+        Ok(self.update_quasi_identifiers(qi))
+    }
+
     /// return a copy of the sensitive attribute of the struct
     fn sensitive_value(&self) -> SensitiveAttribute;
 
+    /// return a copy of every sensitive attribute of the struct, clusters enforce
+    /// l-diversity for each of them. Defaults to only the `sensitive_value`
+    fn sensitive_values(&self) -> Vec<SensitiveAttribute> {
+        vec![self.sensitive_value()]
+    }
+
     /// extract all the values in string format to be used for creating CSV
     fn extract_string_values(&self, uuid: Uuid, dr: f64) -> Vec<String>;
 
     // get the timestamp that the tuple has entered the algorithm
     fn get_timestamp(&self) -> SystemTime;
 
-    /// suppress the qi's based on a buffer of Anonymizables
-    fn suppress(&self) -> Self {
-        let suppressed_qi = self
-            .quasi_identifiers()
-            .into_iter()
-            .map(|x| x.randomize())
-            .collect();
+    /// the time the event described by the tuple happened, used instead of the arrival time
+    /// with `TimeMode::EventTime`. Defaults to the arrival time
+    fn event_time(&self) -> SystemTime {
+        self.get_timestamp()
+    }
+
+    /// how missing QI values are handled
+    fn missing_value_policy(&self) -> MissingValuePolicy {
+        MissingValuePolicy::Ignore
+    }
+
+    /// aggregation of each QI into the centroid, in the order of `quasi_identifiers`.
+    /// QI's without an aggregator use `Aggregator::Default`
+    fn aggregators(&self) -> Vec<Aggregator> {
+        Vec::new()
+    }
+
+    /// rounding of aggregated integer QI's
+    fn rounding(&self) -> Rounding {
+        Rounding::Truncate
+    }
+
+    /// weight of the tuple in QI's aggregated with `Aggregator::WeightedMean`,
+    /// e.g. the sampling weight of a survey record
+    fn record_weight(&self) -> f64 {
+        1.0
+    }
+
+    /// return a copy of the struct with its QI's suppressed by the given strategy.
+    /// `Drop` and `Hold` are handled by the cluster and return the QI's untouched
+    fn suppress_with<R: Rng + ?Sized>(&self, strategy: SuppressionStrategy, rng: &mut R) -> Self {
+        let quasi_identifiers = self.quasi_identifiers();
+        let suppressed_qi = match strategy {
+            SuppressionStrategy::Drop | SuppressionStrategy::Hold => return self.clone(),
+            SuppressionStrategy::Wildcard => {
+                let missing = quasi_identifiers
+                    .iter()
+                    .map(|qi| match qi.is_missing() {
+                        true => qi.clone(),
+                        false => QuasiIdentifierTypes::Missing(Box::new(qi.clone())),
+                    })
+                    .collect();
+                // fields that can't be missing get the midpoint instead
+                if let Ok(wildcard) = self.try_update_quasi_identifiers(missing) {
+                    return wildcard;
+                }
+                quasi_identifiers
+                    .into_iter()
+                    .map(|x| x.midpoint())
+                    .collect()
+            }
+            SuppressionStrategy::Midpoint | SuppressionStrategy::FullDomain => quasi_identifiers
+                .into_iter()
+                .map(|x| x.midpoint())
+                .collect(),
+            SuppressionStrategy::UniformSample => quasi_identifiers
+                .into_iter()
+                .map(|x| x.sample_uniform(rng))
+                .collect(),
+        };
 
         self.update_quasi_identifiers(suppressed_qi)
     }
@@ -261,17 +810,222 @@ pub trait Anonymizable: Default + Clone + Serialize + Sync {
             false => weight as f64,
         }
     }
+
+    /// calculate the distance and the weight of 2 QI's of which at least one is missing
+    fn calculate_missing_distance(
+        x: &QuasiIdentifierTypes,
+        y: &QuasiIdentifierTypes,
+        policy: MissingValuePolicy,
+    ) -> (f64, usize) {
+        policy.missing_distance(x.is_missing() && y.is_missing(), x.weight())
+    }
+
+    /// calculate the distance between 2 values of a generalization hierarchy
+    /// using the height of their lowest common ancestor
+    fn calculate_hierarchical_distance(
+        hierarchical_x: HierarchicalType,
+        hierarchical_y: HierarchicalType,
+    ) -> f64 {
+        let (x, hierarchy, weight) = hierarchical_x;
+        let (y, _, _) = hierarchical_y;
+
+        weight as f64 * hierarchy.distance(x, y)
+    }
+
+    /// calculate the distance between 2 timestamps relative to the span of their domain
+    fn calculate_temporal_distance(temporal_x: TemporalType, temporal_y: TemporalType) -> f64 {
+        let (x, _, domain, weight) = temporal_x;
+        let (y, _, _, _) = temporal_y;
+
+        weight as f64 * abs(x - y) as f64 / domain.span() as f64
+    }
+
+    /// calculate the distance between 2 geo points relative to the max distance of their domain
+    fn calculate_geo_point_distance(geo_point_x: GeoPointType, geo_point_y: GeoPointType) -> f64 {
+        let (x, domain, weight) = geo_point_x;
+        let (y, _, _) = geo_point_y;
+
+        weight as f64 * domain.normalized_distance(&x, &y)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::data_manipulation::aggregation::AggregateType;
-    use crate::data_manipulation::anonymizable::Anonymizable;
+    use crate::anonymization::suppression::SuppressionStrategy;
+    use crate::data_manipulation::aggregation::{AggregateType, Rounding};
     use crate::data_manipulation::anonymizable::QuasiIdentifierType::{Float, Integer};
     use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::{
-        Interval, Nominal, Ordinal,
+        GeoPoint as Geo, Hierarchical, Interval, Missing, Nominal, Ordinal, Temporal,
     };
+    use crate::data_manipulation::anonymizable::{Anonymizable, QuasiIdentifierTypes};
+    use crate::data_manipulation::anonymizable::{MissingValuePolicy, SensitiveAttribute};
+    use crate::data_manipulation::geo::{GeoDistance, GeoDomain, GeoPoint};
+    use crate::data_manipulation::hierarchy::Hierarchy;
     use crate::data_manipulation::mueller::MuellerStream;
+    use crate::data_manipulation::temporal::{
+        format_datetime, from_system_time, parse_datetime, Granularity, TemporalDomain,
+    };
+    use crate::error::DiffPrivError;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    lazy_static! {
+        static ref COUNTRIES: Arc<Hierarchy> = Arc::new(Hierarchy::from_paths(vec![
+            vec!["Europe", "Western-Europe", "Germany"],
+            vec!["Europe", "Western-Europe", "France"],
+            vec!["Europe", "Eastern-Europe", "Poland"],
+            vec!["Asia", "East-Asia", "Japan"],
+        ]));
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Patient {
+        #[qi(interval, min = 0, max = 120, weight = 2)]
+        age: i32,
+        #[qi(interval, min = 0, max = 250)]
+        weight: f64,
+        #[qi(ordinal, categories = ["low", "medium", "high"])]
+        income: String,
+        #[qi(nominal, categories = ["male", "female"])]
+        gender: String,
+        #[sensitive]
+        diagnosis: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Patient {
+        fn default() -> Self {
+            Self {
+                age: 40,
+                weight: 80.5,
+                income: "medium".to_string(),
+                gender: "female".to_string(),
+                diagnosis: "flu".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Traveller {
+        #[qi(nominal, hierarchy = COUNTRIES, weight = 2)]
+        country: String,
+        #[sensitive]
+        diagnosis: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Traveller {
+        fn default() -> Self {
+            Self {
+                country: "Germany".to_string(),
+                diagnosis: "flu".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    #[missing_values(own_category)]
+    struct Survey {
+        #[qi(interval, min = 0, max = 100)]
+        age: Option<i32>,
+        #[qi(nominal, categories = ["male", "female"])]
+        gender: Option<String>,
+        #[sensitive]
+        answer: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Survey {
+        fn default() -> Self {
+            Self {
+                age: Some(50),
+                gender: Some("male".to_string()),
+                answer: "yes".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn derive_quasi_identifiers() {
+        let patient = Patient::default();
+        let mut quasi_identifiers = patient.quasi_identifiers();
+
+        assert_eq!(quasi_identifiers.len(), 4);
+        match quasi_identifiers.remove(0) {
+            Interval((Integer(40), Integer(0), Integer(120), 2)) => {}
+            _ => panic!(),
+        }
+        match quasi_identifiers.remove(0) {
+            Interval((Float(weight), Float(min), Float(max), 1)) => {
+                assert_eq!((weight, min, max), (80.5, 0.0, 250.0))
+            }
+            _ => panic!(),
+        }
+        match quasi_identifiers.remove(0) {
+            Ordinal((2, 3, 1)) => {}
+            _ => panic!(),
+        }
+        match quasi_identifiers.remove(0) {
+            Nominal((1, 1, 1)) => {}
+            _ => panic!(),
+        }
+
+        assert!(patient.sensitive_value() == SensitiveAttribute::String("flu".to_string()));
+        assert_eq!(patient.get_timestamp(), patient.time_generated)
+    }
+
+    #[test]
+    fn derive_update_quasi_identifiers() {
+        let patient = Patient::default();
+        let centroid = Patient {
+            age: 60,
+            weight: 70.0,
+            income: "high".to_string(),
+            gender: "male".to_string(),
+            diagnosis: "cold".to_string(),
+            ..Patient::default()
+        };
+
+        let anonymized = patient.update_quasi_identifiers(centroid.quasi_identifiers());
+
+        assert_eq!(
+            anonymized,
+            Patient {
+                diagnosis: "flu".to_string(),
+                time_generated: patient.time_generated,
+                ..centroid
+            }
+        );
+    }
+
+    #[test]
+    fn derive_extract_string_values() {
+        let patient = Patient::default();
+        let uuid = Uuid::new_v4();
+
+        assert_eq!(
+            patient.extract_string_values(uuid, 0.5),
+            vec![
+                uuid.to_string(),
+                "0.5".to_string(),
+                "40".to_string(),
+                "80.5".to_string(),
+                "medium".to_string(),
+                "female".to_string(),
+                "flu".to_string(),
+            ]
+        )
+    }
 
     #[test]
     fn get_quasi_identifiers() {
@@ -456,4 +1210,682 @@ mod tests {
             panic!()
         }
     }
+
+    #[test]
+    fn derive_hierarchical_quasi_identifiers() {
+        let traveller = Traveller {
+            country: "Poland".to_string(),
+            ..Traveller::default()
+        };
+
+        match traveller.quasi_identifiers().remove(0) {
+            Hierarchical((2, hierarchy, 2)) => assert!(Arc::ptr_eq(&hierarchy, &COUNTRIES)),
+            _ => panic!(),
+        }
+
+        let centroid = vec![Hierarchical((
+            COUNTRIES.value("Europe").unwrap(),
+            Arc::clone(&COUNTRIES),
+            2,
+        ))];
+        let anonymized = traveller.update_quasi_identifiers(centroid);
+
+        assert_eq!(anonymized.country, "Europe")
+    }
+
+    #[test]
+    fn calculate_difference_hierarchical() {
+        let germany = Traveller {
+            country: "Germany".to_string(),
+            ..Traveller::default()
+        };
+        let france = Traveller {
+            country: "France".to_string(),
+            ..Traveller::default()
+        };
+        let japan = Traveller {
+            country: "Japan".to_string(),
+            ..Traveller::default()
+        };
+
+        assert!((germany.calculate_difference(&france) - 1.0 / 3.0).abs() <= f64::EPSILON);
+        assert_eq!(germany.calculate_difference(&japan), 1.0)
+    }
+
+    #[test]
+    fn aggregation_hierarchical() {
+        let germany = COUNTRIES.value("Germany").unwrap();
+        let france = COUNTRIES.value("France").unwrap();
+        let poland = COUNTRIES.value("Poland").unwrap();
+        let agg1 = Hierarchical((germany, Arc::clone(&COUNTRIES), 1));
+        let agg2 = Hierarchical((germany, Arc::clone(&COUNTRIES), 1));
+        let agg3 = Hierarchical((france, Arc::clone(&COUNTRIES), 1));
+        let agg4 = Hierarchical((poland, Arc::clone(&COUNTRIES), 1));
+
+        let mode = AggregateType::Mode(vec![agg1.clone(), agg2.clone(), agg3.clone()]).aggregate();
+        let ancestor =
+            AggregateType::LowestCommonAncestor(vec![agg1, agg2, agg3, agg4]).aggregate();
+
+        match (mode.extract_value(), ancestor.extract_value()) {
+            (Integer(mode), Integer(ancestor)) => {
+                assert_eq!(mode, germany);
+                assert_eq!(COUNTRIES.label(ancestor), "Europe")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn derive_missing_quasi_identifiers() {
+        let survey = Survey {
+            age: None,
+            ..Survey::default()
+        };
+        let mut quasi_identifiers = survey.quasi_identifiers();
+
+        assert_eq!(
+            survey.missing_value_policy(),
+            MissingValuePolicy::OwnCategory
+        );
+        match quasi_identifiers.remove(0) {
+            Missing(template) => match *template {
+                Interval((Integer(0), Integer(0), Integer(100), 1)) => {}
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+
+        let centroid = Survey {
+            age: Some(20),
+            gender: None,
+            ..Survey::default()
+        };
+        let anonymized = survey.update_quasi_identifiers(centroid.quasi_identifiers());
+
+        assert_eq!((anonymized.age, anonymized.gender), (Some(20), None));
+        assert_eq!(
+            survey.extract_string_values(Uuid::nil(), 0.0)[2..],
+            ["".to_string(), "male".to_string(), "yes".to_string()]
+        )
+    }
+
+    #[test]
+    fn calculate_difference_missing_ignore() {
+        let mueller = MuellerStream {
+            age: None,
+            gender: Some("male".to_string()),
+            ..MuellerStream::default()
+        };
+
+        let centroid = MuellerStream {
+            age: Some(50),
+            gender: Some("female".to_string()),
+            ..MuellerStream::default()
+        };
+
+        assert_eq!(mueller.calculate_difference(&centroid), 1.0);
+        assert_eq!(mueller.calculate_difference(&mueller), 0.0)
+    }
+
+    #[test]
+    fn calculate_difference_missing_own_category() {
+        let survey = Survey {
+            age: None,
+            ..Survey::default()
+        };
+
+        let centroid = Survey {
+            age: Some(0),
+            gender: Some("male".to_string()),
+            ..Survey::default()
+        };
+
+        assert_eq!(survey.calculate_difference(&centroid), 0.5);
+        assert_eq!(survey.calculate_difference(&survey), 0.0)
+    }
+
+    #[test]
+    fn aggregation_missing() {
+        let template = Box::new(Nominal((0, 4, 1)));
+        let mode = AggregateType::Mode(vec![
+            Nominal((1, 4, 1)),
+            Missing(template.clone()),
+            Missing(template.clone()),
+            Nominal((2, 4, 1)),
+        ])
+        .aggregate();
+        assert!(mode.is_missing());
+
+        let mean = AggregateType::Mean(vec![
+            Interval((Integer(2), Integer(0), Integer(10), 1)),
+            Missing(Box::new(Interval((Integer(0), Integer(0), Integer(10), 1)))),
+            Interval((Integer(4), Integer(0), Integer(10), 1)),
+        ])
+        .aggregate();
+        assert_eq!(mean.try_extract_value(), Some(Integer(3)));
+
+        let all_missing = AggregateType::Mode(vec![Missing(template)]).aggregate();
+        assert!(all_missing.is_missing())
+    }
+
+    #[test]
+    fn derive_try_quasi_identifiers_unknown_category() {
+        let patient = Patient {
+            income: "very high".to_string(),
+            ..Patient::default()
+        };
+
+        assert_eq!(
+            patient.try_quasi_identifiers().unwrap_err(),
+            DiffPrivError::UnknownCategory("`very high` of `income`".to_string())
+        );
+
+        let traveller = Traveller {
+            country: "Atlantis".to_string(),
+            ..Traveller::default()
+        };
+        assert!(matches!(
+            traveller.try_quasi_identifiers(),
+            Err(DiffPrivError::UnknownCategory(_))
+        ))
+    }
+
+    #[test]
+    fn try_update_quasi_identifiers_mismatch() {
+        let patient = Patient::default();
+        let mut quasi_identifiers = patient.quasi_identifiers();
+        quasi_identifiers.swap(0, 2);
+
+        assert!(matches!(
+            patient.try_update_quasi_identifiers(quasi_identifiers),
+            Err(DiffPrivError::TypeMismatch(_))
+        ))
+    }
+
+    #[test]
+    fn validate_quasi_identifiers() {
+        assert!(Nominal((1, 1, 1)).validate().is_ok());
+        assert!(matches!(
+            Nominal((2, 1, 1)).validate(),
+            Err(DiffPrivError::UnknownCategory(_))
+        ));
+        assert!(matches!(
+            Interval((Integer(1), Float(0.0), Integer(10), 1)).validate(),
+            Err(DiffPrivError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            Nominal((0, 1, 1)).check_compatible(&Ordinal((0, 1, 1))),
+            Err(DiffPrivError::TypeMismatch(_))
+        ));
+        assert!(Missing(Box::new(Nominal((0, 1, 1))))
+            .check_compatible(&Nominal((1, 1, 1)))
+            .is_ok())
+    }
+
+    #[test]
+    fn try_aggregate_mismatch() {
+        let mixed = AggregateType::Mode(vec![Nominal((1, 4, 1)), Ordinal((2, 4, 1))]);
+        assert!(matches!(
+            mixed.try_aggregate(),
+            Err(DiffPrivError::TypeMismatch(_))
+        ));
+
+        let unsupported = AggregateType::Mean(vec![Nominal((1, 4, 1))]);
+        assert!(matches!(
+            unsupported.try_aggregate(),
+            Err(DiffPrivError::TypeMismatch(_))
+        ));
+
+        assert!(matches!(
+            AggregateType::Mode(vec![]).try_aggregate(),
+            Err(DiffPrivError::InvalidRecord(_))
+        ));
+
+        let mode = AggregateType::Mode(vec![Nominal((1, 4, 1)), Nominal((1, 4, 1))]);
+        assert_eq!(
+            mode.try_aggregate().unwrap().try_extract_value(),
+            Some(Integer(1))
+        )
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Prescription {
+        #[qi(interval, min = 0, max = 120)]
+        age: i32,
+        #[sensitive]
+        diagnosis: String,
+        #[sensitive]
+        medication: String,
+        #[sensitive]
+        income_bracket: i32,
+        #[sensitive(bucket_width = 1000)]
+        salary: f64,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Prescription {
+        fn default() -> Self {
+            Self {
+                age: 40,
+                diagnosis: "flu".to_string(),
+                medication: "aspirin".to_string(),
+                income_bracket: 2,
+                salary: 41250.5,
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn derive_multiple_sensitive_attributes() {
+        let prescription = Prescription::default();
+
+        assert!(prescription.sensitive_value() == SensitiveAttribute::String("flu".to_string()));
+        assert!(
+            prescription.sensitive_values()
+                == vec![
+                    SensitiveAttribute::String("flu".to_string()),
+                    SensitiveAttribute::String("aspirin".to_string()),
+                    SensitiveAttribute::Integer(2),
+                    SensitiveAttribute::Float(41000.0, 1000.0),
+                ]
+        );
+        assert!(Patient::default().sensitive_values().len() == 1)
+    }
+
+    #[test]
+    fn sensitive_float_buckets() {
+        use SensitiveAttribute::Float;
+
+        let mut frequencies = HashMap::new();
+        [41250.5, 41999.0, 42000.0]
+            .into_iter()
+            .for_each(|salary| *frequencies.entry(Float(salary, 1000.0)).or_insert(0) += 1);
+
+        assert_eq!(frequencies.len(), 2);
+        assert_eq!(frequencies[&Float(41000.0, 1000.0)], 2);
+        assert_eq!(Float(-0.5, 1.0).rank(), Some(-1));
+        assert_eq!(SensitiveAttribute::String("flu".to_string()).rank(), None);
+        assert!(Float(1.0, 1.0) != SensitiveAttribute::Integer(1))
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Shift {
+        #[qi(
+            temporal,
+            min = "2022-06-20",
+            max = "2022-06-27",
+            granularity = "minute",
+            median
+        )]
+        #[event_time]
+        start: String,
+        #[qi(temporal, min = 1655683200, max = 1656288000, weight = 2)]
+        end: SystemTime,
+        #[sensitive]
+        department: String,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Shift {
+        fn default() -> Self {
+            Self {
+                start: "2022-06-20 12:39:34".to_string(),
+                end: crate::data_manipulation::temporal::to_system_time(
+                    parse_datetime("2022-06-20 20:00:00").unwrap(),
+                ),
+                department: "emergency".to_string(),
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn derive_event_time() {
+        let shift = Shift::default();
+        assert_eq!(
+            shift.event_time(),
+            crate::data_manipulation::temporal::to_system_time(
+                parse_datetime("2022-06-20 12:39:34").unwrap()
+            )
+        );
+
+        // unparsable event times and structs without one use the arrival time
+        let shift = Shift {
+            start: "tomorrow".to_string(),
+            ..Shift::default()
+        };
+        assert_eq!(shift.event_time(), shift.time_generated);
+        let patient = Patient::default();
+        assert_eq!(patient.event_time(), patient.time_generated)
+    }
+
+    #[test]
+    fn derive_temporal_quasi_identifiers() {
+        let shift = Shift::default();
+        let qi = shift.quasi_identifiers();
+
+        match &qi[0] {
+            Temporal((timestamp, Granularity::Minute, domain, 1)) => {
+                assert_eq!(*timestamp, parse_datetime("2022-06-20 12:39:34").unwrap());
+                assert_eq!(domain.span(), 7 * 86400);
+                assert!(domain.median_centroid())
+            }
+            _ => panic!(),
+        }
+        match &qi[1] {
+            Temporal((timestamp, Granularity::Second, domain, 2)) => {
+                assert_eq!(*timestamp, from_system_time(shift.end));
+                assert!(!domain.median_centroid())
+            }
+            _ => panic!(),
+        }
+
+        let anonymized = shift.update_quasi_identifiers(qi);
+        assert_eq!(anonymized.start, "2022-06-20 12:39:34");
+        assert_eq!(anonymized.end, shift.end);
+        assert!(Shift {
+            start: "tomorrow".to_string(),
+            ..Shift::default()
+        }
+        .try_quasi_identifiers()
+        .is_err())
+    }
+
+    #[test]
+    fn calculate_difference_temporal() {
+        let shift = Shift::default();
+        let later = Shift {
+            start: "2022-06-23 12:39:34".to_string(),
+            ..Shift::default()
+        };
+
+        // 3 out of 7 days apart on a QI with weight 1 out of a total weight of 3
+        let difference = shift.calculate_difference(&later);
+        assert!((difference - 3.0 / 7.0 / 3.0).abs() <= f64::EPSILON)
+    }
+
+    #[test]
+    fn aggregation_temporal() {
+        let domain = TemporalDomain::new(0, 7 * 86400, Granularity::Second);
+        let temporal = |datetime: &str| {
+            let timestamp = parse_datetime(datetime).unwrap();
+            Temporal((timestamp, Granularity::Second, domain, 1))
+        };
+        let list = vec![
+            temporal("1970-01-01 12:00:10"),
+            temporal("1970-01-01 12:00:20"),
+            temporal("1970-01-01 12:00:50"),
+        ];
+
+        // the values span 40 seconds, so the centroid is published with minute granularity
+        match AggregateType::Mean(list.clone()).aggregate() {
+            Temporal((timestamp, granularity, _, _)) => {
+                assert_eq!(granularity, Granularity::Minute);
+                assert_eq!(format_datetime(timestamp), "1970-01-01 12:00:00")
+            }
+            _ => panic!(),
+        }
+
+        let mut list = list;
+        list.push(temporal("1970-01-01 15:00:00"));
+        match AggregateType::Median(list).aggregate() {
+            Temporal((timestamp, granularity, _, _)) => {
+                assert_eq!(granularity, Granularity::Day);
+                assert_eq!(format_datetime(timestamp), "1970-01-01 00:00:00")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[derive(Debug, Serialize, Clone, PartialEq, Anonymizable)]
+    struct Trip {
+        #[qi(geo, max_distance = 1000, privacy_radius = 0.5)]
+        pickup: GeoPoint,
+        #[qi(geo, equirectangular)]
+        dropoff: Option<GeoPoint>,
+        #[sensitive]
+        fare: i32,
+        #[arrival_time]
+        time_generated: SystemTime,
+    }
+
+    impl Default for Trip {
+        fn default() -> Self {
+            Self {
+                pickup: GeoPoint::default(),
+                dropoff: None,
+                fare: 10,
+                time_generated: SystemTime::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn derive_geo_point_quasi_identifiers() {
+        let trip = Trip {
+            pickup: GeoPoint::new(52.37, 4.9),
+            ..Trip::default()
+        };
+        let qi = trip.quasi_identifiers();
+
+        match &qi[0] {
+            Geo((point, domain, 1)) => {
+                assert_eq!(*point, trip.pickup);
+                assert_eq!(domain.max_distance, 1000.0);
+                assert_eq!(domain.privacy_radius, 0.5);
+                assert_eq!(domain.formula, GeoDistance::Haversine)
+            }
+            _ => panic!(),
+        }
+        match &qi[1] {
+            Missing(template) => match template.as_ref() {
+                Geo((_, domain, _)) => assert_eq!(domain.formula, GeoDistance::Equirectangular),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+
+        let centroid = vec![
+            Geo((GeoPoint::new(50.0, 5.0), GeoDomain::default(), 1)),
+            Geo((GeoPoint::new(51.0, 6.0), GeoDomain::default(), 1)),
+        ];
+        let anonymized = trip.update_quasi_identifiers(centroid);
+        assert_eq!(anonymized.pickup, GeoPoint::new(50.0, 5.0));
+        assert_eq!(anonymized.dropoff, Some(GeoPoint::new(51.0, 6.0)))
+    }
+
+    #[test]
+    fn calculate_difference_geo_point() {
+        let trip = |longitude: f64| Trip {
+            pickup: GeoPoint::new(0.0, longitude),
+            dropoff: Some(GeoPoint::new(0.0, 0.0)),
+            ..Trip::default()
+        };
+        let one_degree = GeoPoint::new(0.0, 0.0).haversine(&GeoPoint::new(0.0, 1.0));
+
+        // the points are 1 degree apart across the antimeridian, not 359
+        let difference = trip(179.5).calculate_difference(&trip(-179.5));
+        assert!((difference - one_degree / 1000.0 / 2.0).abs() <= 1e-9);
+        assert_eq!(trip(10.0).calculate_difference(&trip(-170.0)), 0.5)
+    }
+
+    #[test]
+    fn aggregation_geo_point() {
+        let domain = GeoDomain::default();
+        let list = vec![
+            Geo((GeoPoint::new(10.0, 179.0), domain, 1)),
+            Geo((GeoPoint::new(10.0, -179.0), domain, 1)),
+        ];
+
+        match AggregateType::SphericalCentroid(list.clone()).aggregate() {
+            Geo((point, _, _)) => {
+                assert!((point.longitude.abs() - 180.0).abs() < 1e-9);
+                assert!((point.latitude - 10.0).abs() < 0.01)
+            }
+            _ => panic!(),
+        }
+        assert!(matches!(
+            AggregateType::Mean(list).try_aggregate(),
+            Err(DiffPrivError::TypeMismatch(_))
+        ))
+    }
+
+    #[test]
+    fn aggregation_median_trimmed_weighted_mean() {
+        let interval = |value: i32| Interval((Integer(value), Integer(0), Integer(1000), 1));
+        let list: Vec<QuasiIdentifierTypes> = [0, 1, 2, 3, 100].into_iter().map(interval).collect();
+        let value = |aggregate_type: AggregateType| aggregate_type.aggregate().extract_value();
+
+        assert_eq!(value(AggregateType::Mean(list.clone())), Integer(21));
+        assert_eq!(value(AggregateType::Median(list.clone())), Integer(2));
+        // 1 out of 5 values is left out at both ends
+        assert_eq!(
+            value(AggregateType::TrimmedMean(list.clone(), 0.2)),
+            Integer(2)
+        );
+        assert_eq!(
+            value(AggregateType::WeightedMean(
+                list,
+                vec![0.0, 0.0, 1.0, 1.0, 0.0]
+            )),
+            Integer(2)
+        )
+    }
+
+    #[test]
+    fn aggregation_ordinal_median() {
+        let list = vec![
+            Ordinal((1, 5, 1)),
+            Ordinal((1, 5, 1)),
+            Ordinal((4, 5, 1)),
+            Ordinal((5, 5, 1)),
+            Ordinal((5, 5, 1)),
+        ];
+
+        // the mode is ambiguous, the median respects the ordering of the ranks
+        assert_eq!(
+            AggregateType::Median(list).aggregate().extract_value(),
+            Integer(4)
+        )
+    }
+
+    #[test]
+    fn aggregation_rounding() {
+        let interval = |value: i32| Interval((Integer(value), Integer(0), Integer(10), 1));
+        let mean = |values: [i32; 2], rounding: Rounding| {
+            AggregateType::Mean(values.into_iter().map(interval).collect())
+                .aggregate_with(rounding)
+                .extract_value()
+        };
+
+        assert_eq!(mean([1, 2], Rounding::Truncate), Integer(1));
+        assert_eq!(mean([1, 2], Rounding::Nearest), Integer(2));
+        assert_eq!(mean([1, 2], Rounding::HalfEven), Integer(2));
+        assert_eq!(mean([2, 3], Rounding::HalfEven), Integer(2));
+        assert_eq!(mean([2, 3], Rounding::Floor), Integer(2));
+        assert_eq!(mean([2, 3], Rounding::Ceil), Integer(3))
+    }
+
+    #[test]
+    fn try_aggregate_invalid_aggregator() {
+        let interval = Interval((Integer(1), Integer(0), Integer(10), 1));
+
+        assert!(matches!(
+            AggregateType::TrimmedMean(vec![interval.clone()], 0.5).try_aggregate(),
+            Err(DiffPrivError::InvalidRecord(_))
+        ));
+        assert!(matches!(
+            AggregateType::WeightedMean(vec![interval], vec![]).try_aggregate(),
+            Err(DiffPrivError::InvalidRecord(_))
+        ));
+        assert!(matches!(
+            AggregateType::Median(vec![Nominal((1, 4, 1))]).try_aggregate(),
+            Err(DiffPrivError::TypeMismatch(_))
+        ))
+    }
+
+    #[test]
+    fn suppress_with_midpoint_and_wildcard() {
+        let patient = Patient::default();
+        let mut rng = StdRng::seed_from_u64(42);
+        let midpoint = patient.suppress_with(SuppressionStrategy::Midpoint, &mut rng);
+
+        assert_eq!(
+            (
+                midpoint.age,
+                midpoint.weight,
+                midpoint.income,
+                midpoint.gender
+            ),
+            (60, 125.0, "medium".to_string(), "male".to_string())
+        );
+
+        // fields that can't be missing fall back to the midpoint
+        let wildcard = patient.suppress_with(SuppressionStrategy::Wildcard, &mut rng);
+        assert_eq!((wildcard.age, wildcard.weight), (60, 125.0));
+
+        let wildcard = Survey::default().suppress_with(SuppressionStrategy::Wildcard, &mut rng);
+        assert_eq!((wildcard.age, wildcard.gender), (None, None));
+        assert_eq!(wildcard.answer, "yes");
+
+        let dropped = patient.suppress_with(SuppressionStrategy::Drop, &mut rng);
+        assert_eq!(dropped, patient)
+    }
+
+    #[test]
+    fn suppress_uniform_sample() {
+        let patient = Patient::default();
+        let mut rng = StdRng::seed_from_u64(42);
+        let sample = patient.suppress_with(SuppressionStrategy::UniformSample, &mut rng);
+
+        // unlike `randomize` the sample doesn't stay close to the original value
+        assert_eq!(
+            (sample.age, sample.weight, sample.income, sample.gender),
+            (
+                16,
+                62.18459442761711,
+                "medium".to_string(),
+                "female".to_string()
+            )
+        );
+        // the same seed gives the same sample
+        assert_eq!(
+            patient.suppress_with(
+                SuppressionStrategy::UniformSample,
+                &mut StdRng::seed_from_u64(42)
+            ),
+            patient.suppress_with(
+                SuppressionStrategy::UniformSample,
+                &mut StdRng::seed_from_u64(42)
+            )
+        );
+
+        let hierarchical = Hierarchical((0, Arc::clone(&COUNTRIES), 1)).sample_uniform(&mut rng);
+        assert!(matches!(hierarchical, Hierarchical((2, _, 1))));
+
+        let domain = TemporalDomain::new(0, 86400 * 10, Granularity::Day);
+        assert!(matches!(
+            Temporal((0, Granularity::Day, domain, 1)).sample_uniform(&mut rng),
+            Temporal((345600, Granularity::Day, _, 1))
+        ));
+    }
+
+    #[test]
+    fn sample_uniform_ordinal() {
+        let mut rng = StdRng::seed_from_u64(42);
+        // every rank that passes validation can be sampled, rank 0 never is
+        let ranks: Vec<i32> = (0..200)
+            .map(|_| match Ordinal((1, 3, 1)).sample_uniform(&mut rng) {
+                Ordinal((rank, 3, 1)) => rank,
+                _ => panic!(),
+            })
+            .collect();
+        assert!((1..=3).all(|rank| ranks.contains(&rank)));
+        assert!(!ranks.contains(&0));
+        assert!(Ordinal((0, 3, 1)).validate().is_err());
+        assert!(Ordinal((3, 3, 1)).validate().is_ok());
+    }
 }
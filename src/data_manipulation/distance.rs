@@ -0,0 +1,521 @@
+use std::sync::{Arc, RwLock};
+
+use crate::anonymization::record::QiColumns;
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, QuasiIdentifierType, QuasiIdentifierTypes,
+};
+use crate::data_manipulation::geo::wrap_longitude;
+use crate::error::DiffPrivError;
+
+/// small value added to the variances so the covariance matrix can always be inverted
+const REGULARIZATION: f64 = 1e-9;
+
+/// observations between 2 inversions of the covariance matrix, the first ones are inverted every time
+const INVERSION_INTERVAL: usize = 64;
+
+/// Difference between the values of a QI of 2 tuples
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QiDifference {
+    pub distance: f64, // distance relative to the domain of the QI, multiplied by its weight
+    pub weight: usize, // weight of the QI, 0 when the QI is left out
+    pub deltas: Vec<f64>, // differences of the numerical coordinates, empty for categorical QI's
+}
+
+/// Metric combining the differences of the QI's of 2 tuples into a single distance.
+/// It is used to find the best cluster of a tuple, detect concept drift and by the analysers
+pub trait DistanceMetric: Send + Sync {
+    fn distance(&self, differences: &[QiDifference]) -> f64;
+
+    /// update the metric with the QI's of a tuple entering the algorithm,
+    /// used by metrics that are estimated from the stream
+    fn observe(&self, _qi: &[QuasiIdentifierTypes]) {}
+
+    /// whether `observe` changes the distances, the nearest cluster of a tuple in
+    /// `anonymize_batch` can then only be looked up after the tuples before it are observed
+    fn is_adaptive(&self) -> bool {
+        false
+    }
+
+    /// lower bound of the distance from lower bounds of the QI distances, every QI with its
+    /// full weight. Used by the cluster index to skip clusters that can't be the nearest
+    fn lower_bound(&self, _differences: &[QiDifference]) -> Option<f64> {
+        None
+    }
+
+    /// whether `lower_bound` is implemented, otherwise every cluster is compared with the tuple
+    fn supports_lower_bound(&self) -> bool {
+        false
+    }
+}
+
+impl<'a> dyn DistanceMetric + 'a {
+    /// distance between 2 tuples, panics when their QI's can't be compared
+    pub fn difference<A: Anonymizable>(&self, x: &A, y: &A) -> f64 {
+        self.try_difference(x, y)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// distance between 2 tuples, return an error when their QI's can't be compared
+    pub fn try_difference<A: Anonymizable>(&self, x: &A, y: &A) -> Result<f64, DiffPrivError> {
+        Ok(self.distance(&x.try_qi_differences(y)?))
+    }
+
+    /// distance between the QI's of 2 tuples that have already been extracted
+    pub fn qi_difference(&self, x: &QiColumns, y: &QiColumns) -> f64 {
+        self.distance(&x.differences(y))
+    }
+}
+
+/// Distance metrics that can be set in the configuration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetricType {
+    #[default]
+    Gower,
+    Manhattan,
+    Euclidean,
+    Mahalanobis,
+}
+
+impl DistanceMetricType {
+    pub fn build(&self) -> Arc<dyn DistanceMetric> {
+        match self {
+            DistanceMetricType::Gower => Arc::new(Gower),
+            DistanceMetricType::Manhattan => Arc::new(Manhattan),
+            DistanceMetricType::Euclidean => Arc::new(NormalizedEuclidean),
+            DistanceMetricType::Mahalanobis => Arc::new(Mahalanobis::default()),
+        }
+    }
+}
+
+/// weighted mean of the distances of the QI's, the distance of `Anonymizable::calculate_difference`
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Gower;
+
+impl DistanceMetric for Gower {
+    fn distance(&self, differences: &[QiDifference]) -> f64 {
+        match total_weight(differences) {
+            0 => 0.0,
+            weight => differences.iter().map(|x| x.distance).sum::<f64>() / weight as f64,
+        }
+    }
+
+    // left out QI's only lower the total weight and increase the distance
+    fn lower_bound(&self, differences: &[QiDifference]) -> Option<f64> {
+        Some(self.distance(differences))
+    }
+
+    fn supports_lower_bound(&self) -> bool {
+        true
+    }
+}
+
+/// sum of the weighted distances of the QI's
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Manhattan;
+
+impl DistanceMetric for Manhattan {
+    fn distance(&self, differences: &[QiDifference]) -> f64 {
+        differences.iter().map(|x| x.distance).sum()
+    }
+
+    fn lower_bound(&self, differences: &[QiDifference]) -> Option<f64> {
+        Some(self.distance(differences))
+    }
+
+    fn supports_lower_bound(&self) -> bool {
+        true
+    }
+}
+
+/// weighted euclidean distance of the distances of the QI's relative to their domain,
+/// between 0 and 1 like the gower distance
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NormalizedEuclidean;
+
+impl DistanceMetric for NormalizedEuclidean {
+    fn distance(&self, differences: &[QiDifference]) -> f64 {
+        match total_weight(differences) {
+            0 => 0.0,
+            weight => (squared_distances(differences.iter()) / weight as f64).sqrt(),
+        }
+    }
+
+    fn lower_bound(&self, differences: &[QiDifference]) -> Option<f64> {
+        Some(self.distance(differences))
+    }
+
+    fn supports_lower_bound(&self) -> bool {
+        true
+    }
+}
+
+/// Mahalanobis distance between the numerical coordinates of 2 tuples, using an online estimate
+/// of their covariance. Categorical QI's add their squared distance relative to their domain.
+/// Until the covariance can be estimated the normalized euclidean distance is used
+#[derive(Debug, Default)]
+pub struct Mahalanobis {
+    covariance: RwLock<OnlineCovariance>,
+}
+
+impl DistanceMetric for Mahalanobis {
+    fn distance(&self, differences: &[QiDifference]) -> f64 {
+        let deltas: Vec<f64> = differences
+            .iter()
+            .flat_map(|x| x.deltas.iter().copied())
+            .collect();
+        let covariance = self.covariance.read().unwrap();
+        let inverse = match &covariance.inverse {
+            Some(inverse) if inverse.len() == deltas.len() => inverse,
+            _ => return NormalizedEuclidean.distance(differences),
+        };
+
+        let numerical: f64 = inverse
+            .iter()
+            .zip(deltas.iter())
+            .map(|(row, delta_x)| {
+                delta_x
+                    * row
+                        .iter()
+                        .zip(deltas.iter())
+                        .map(|(value, delta_y)| value * delta_y)
+                        .sum::<f64>()
+            })
+            .sum();
+        let categorical = squared_distances(differences.iter().filter(|x| x.deltas.is_empty()));
+
+        (numerical.max(0.0) + categorical).sqrt()
+    }
+
+    fn is_adaptive(&self) -> bool {
+        true
+    }
+
+    fn observe(&self, qi: &[QuasiIdentifierTypes]) {
+        let coordinates: Option<Vec<f64>> = qi.iter().flat_map(coordinates).collect();
+        // tuples with missing numerical values are left out of the estimate
+        if let Some(coordinates) = coordinates {
+            self.covariance.write().unwrap().update(&coordinates)
+        }
+    }
+}
+
+/// Welford's online estimate of the covariance matrix
+#[derive(Debug, Default, Clone)]
+struct OnlineCovariance {
+    count: usize,
+    mean: Vec<f64>,
+    comoment: Vec<Vec<f64>>, // sum of the products of the deviations from the mean
+    inverse: Option<Vec<Vec<f64>>>, // inverse of the covariance matrix, see `INVERSION_INTERVAL`
+}
+
+impl OnlineCovariance {
+    fn update(&mut self, values: &[f64]) {
+        if self.count == 0 {
+            self.mean = vec![0.0; values.len()];
+            self.comoment = vec![vec![0.0; values.len()]; values.len()];
+        }
+        if values.len() != self.mean.len() {
+            return;
+        }
+
+        self.count += 1;
+        let old_deviation: Vec<f64> = values.iter().zip(&self.mean).map(|(x, m)| x - m).collect();
+        self.mean
+            .iter_mut()
+            .zip(&old_deviation)
+            .for_each(|(mean, deviation)| *mean += deviation / self.count as f64);
+        let new_deviation: Vec<f64> = values.iter().zip(&self.mean).map(|(x, m)| x - m).collect();
+
+        self.comoment
+            .iter_mut()
+            .zip(&old_deviation)
+            .for_each(|(row, old)| {
+                row.iter_mut()
+                    .zip(&new_deviation)
+                    .for_each(|(value, new)| *value += old * new)
+            });
+        // a single tuple barely moves an estimate of many tuples, so it isn't inverted every time
+        if self.count <= INVERSION_INTERVAL || self.count.is_multiple_of(INVERSION_INTERVAL) {
+            self.inverse = self.invert();
+        }
+    }
+
+    /// inverse of the sample covariance matrix, `None` when there are less than 2
+    /// observations or the matrix is singular
+    fn invert(&self) -> Option<Vec<Vec<f64>>> {
+        if self.count < 2 {
+            return None;
+        }
+        let size = self.mean.len();
+        let mut matrix: Vec<Vec<f64>> = self
+            .comoment
+            .iter()
+            .enumerate()
+            .map(|(row_index, row)| {
+                let mut row: Vec<f64> = row
+                    .iter()
+                    .map(|value| value / (self.count - 1) as f64)
+                    .collect();
+                row[row_index] += REGULARIZATION * (1.0 + row[row_index].abs());
+                row.extend((0..size).map(|index| (index == row_index) as i32 as f64));
+                row
+            })
+            .collect();
+
+        // gauss-jordan elimination with partial pivoting on [covariance | identity]
+        for column in 0..size {
+            let pivot = (column..size).max_by(|x, y| {
+                matrix[*x][column]
+                    .abs()
+                    .partial_cmp(&matrix[*y][column].abs())
+                    .unwrap()
+            })?;
+            if matrix[pivot][column].abs() <= f64::EPSILON {
+                return None;
+            }
+            matrix.swap(column, pivot);
+
+            let divisor = matrix[column][column];
+            matrix[column]
+                .iter_mut()
+                .for_each(|value| *value /= divisor);
+            let pivot_row = matrix[column].clone();
+            matrix
+                .iter_mut()
+                .enumerate()
+                .filter(|(row_index, _)| *row_index != column)
+                .for_each(|(_, row)| {
+                    let factor = row[column];
+                    row.iter_mut()
+                        .zip(&pivot_row)
+                        .for_each(|(value, pivot_value)| *value -= factor * pivot_value)
+                });
+        }
+
+        Some(matrix.into_iter().map(|row| row[size..].to_vec()).collect())
+    }
+}
+
+/// numerical coordinates of a QI, `None` for the coordinates of a missing value
+/// and empty for categorical QI's
+pub fn coordinates(qi: &QuasiIdentifierTypes) -> Vec<Option<f64>> {
+    match qi {
+        QuasiIdentifierTypes::Interval((value, _, _, _)) => match value {
+            QuasiIdentifierType::Integer(value) => vec![Some(*value as f64)],
+            QuasiIdentifierType::Float(value) => vec![Some(*value)],
+        },
+        QuasiIdentifierTypes::Temporal((timestamp, _, _, _)) => vec![Some(*timestamp as f64)],
+        QuasiIdentifierTypes::GeoPoint((point, _, _)) => {
+            vec![Some(point.latitude), Some(point.longitude)]
+        }
+        QuasiIdentifierTypes::Missing(template) => vec![None; coordinates(template).len()],
+        _ => vec![],
+    }
+}
+
+/// coordinate of a QI on an axis where the distance between 2 values is the distance of the QI,
+/// the value itself for categories and `NaN` for missing values and geo points
+pub fn normalized_coordinate(qi: &QuasiIdentifierTypes) -> f64 {
+    match qi {
+        QuasiIdentifierTypes::Interval((value, min, max, weight)) => match (value, min, max) {
+            (
+                QuasiIdentifierType::Integer(value),
+                QuasiIdentifierType::Integer(min),
+                QuasiIdentifierType::Integer(max),
+            ) => *weight as f64 * (*value as f64 - *min as f64) / (*max as f64 - *min as f64),
+            (
+                QuasiIdentifierType::Float(value),
+                QuasiIdentifierType::Float(min),
+                QuasiIdentifierType::Float(max),
+            ) => *weight as f64 * (value - min) / (max - min),
+            _ => f64::NAN,
+        },
+        // the rank is scaled to 0..=1 and then again by the span of the ranks
+        QuasiIdentifierTypes::Ordinal((rank, max_rank, weight)) => {
+            let span = *max_rank as f64 - 1.0;
+            (*weight as f64).powi(2) * ((*rank as f64 - 1.0) / span) / span
+        }
+        QuasiIdentifierTypes::Temporal((timestamp, _, domain, weight)) => {
+            *weight as f64 * (timestamp - domain.min) as f64 / domain.span() as f64
+        }
+        QuasiIdentifierTypes::Nominal((value, _, _)) => *value as f64,
+        QuasiIdentifierTypes::Hierarchical((value, _, _)) => *value as f64,
+        _ => f64::NAN,
+    }
+}
+
+/// smallest distance between 2 different values of a categorical QI,
+/// `None` for QI's on a line
+pub fn category_distance(qi: &QuasiIdentifierTypes) -> Option<f64> {
+    match qi.present() {
+        QuasiIdentifierTypes::Nominal((_, _, weight)) => Some(*weight as f64),
+        // siblings of the deepest leaves have the closest common ancestor
+        QuasiIdentifierTypes::Hierarchical((_, hierarchy, weight)) => match hierarchy.height() {
+            0 => Some(0.0),
+            height => Some(*weight as f64 / height as f64),
+        },
+        _ => None,
+    }
+}
+
+/// differences of the numerical coordinates of 2 QI's, missing coordinates have no difference.
+/// Longitudes are compared the short way around the earth
+pub fn coordinate_deltas(x: &QuasiIdentifierTypes, y: &QuasiIdentifierTypes) -> Vec<f64> {
+    match (x, y) {
+        (QuasiIdentifierTypes::GeoPoint((x, _, _)), QuasiIdentifierTypes::GeoPoint((y, _, _))) => {
+            vec![
+                x.latitude - y.latitude,
+                wrap_longitude(x.longitude - y.longitude),
+            ]
+        }
+        _ => coordinates(x)
+            .into_iter()
+            .zip(coordinates(y))
+            .map(|coordinate| match coordinate {
+                (Some(x), Some(y)) => x - y,
+                _ => 0.0,
+            })
+            .collect(),
+    }
+}
+
+fn total_weight(differences: &[QiDifference]) -> usize {
+    differences.iter().map(|x| x.weight).sum()
+}
+
+/// sum of the squared distances relative to the domain multiplied by the weight of every QI
+fn squared_distances<'a>(differences: impl Iterator<Item = &'a QiDifference>) -> f64 {
+    differences
+        .filter(|x| x.weight > 0)
+        .map(|x| x.distance.powi(2) / x.weight as f64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn difference(distance: f64, weight: usize, deltas: Vec<f64>) -> QiDifference {
+        QiDifference {
+            distance,
+            weight,
+            deltas,
+        }
+    }
+
+    #[test]
+    fn gower_manhattan_euclidean() {
+        let differences = vec![
+            difference(0.5, 1, vec![5.0]),
+            difference(0.4, 2, vec![]),
+            difference(0.0, 0, vec![0.0]),
+        ];
+
+        assert!((Gower.distance(&differences) - 0.3).abs() <= f64::EPSILON);
+        assert!((Manhattan.distance(&differences) - 0.9).abs() <= f64::EPSILON);
+        // 1 * 0.5^2 + 2 * 0.2^2 = 0.33 over a total weight of 3
+        assert!((NormalizedEuclidean.distance(&differences) - 0.11_f64.sqrt()).abs() <= 1e-12);
+        assert_eq!(Gower.distance(&[]), 0.0)
+    }
+
+    #[test]
+    fn supports_lower_bound() {
+        let metrics: [&dyn DistanceMetric; 3] = [&Gower, &Manhattan, &NormalizedEuclidean];
+        assert!(metrics.iter().all(|metric| metric.supports_lower_bound()));
+        assert!(!Mahalanobis::default().supports_lower_bound())
+    }
+
+    #[test]
+    fn normalized_coordinates() {
+        use crate::data_manipulation::anonymizable::QuasiIdentifierType::Integer;
+        use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::{
+            Interval, Nominal, Ordinal,
+        };
+
+        let distance = |x: &QuasiIdentifierTypes, y: &QuasiIdentifierTypes| {
+            (normalized_coordinate(x) - normalized_coordinate(y)).abs()
+        };
+        // 2 * 20 / 100
+        let age = |age: i32| Interval((Integer(age), Integer(0), Integer(100), 2));
+        assert!((distance(&age(30), &age(50)) - 0.4).abs() < 1e-12);
+        // 2 * (2 * 0.5 / 4) like `calculate_ordinal_distance`
+        assert!((distance(&Ordinal((3, 5, 2)), &Ordinal((5, 5, 2))) - 0.5).abs() < 1e-12);
+        assert_eq!(category_distance(&Nominal((1, 3, 2))), Some(2.0));
+        assert_eq!(category_distance(&age(30)), None)
+    }
+
+    #[test]
+    fn online_covariance() {
+        let mut covariance = OnlineCovariance::default();
+        [[1.0, 2.0], [2.0, 1.0], [3.0, 4.0], [4.0, 3.0]]
+            .iter()
+            .for_each(|values| covariance.update(values));
+
+        // sample covariance [[5/3, 1], [1, 5/3]] computed by hand
+        let inverse = covariance.inverse.unwrap();
+        let (a, b, d) = (5.0 / 3.0, 1.0, 5.0 / 3.0);
+        let determinant = a * d - b * b;
+        assert!((inverse[0][0] - d / determinant).abs() < 1e-6);
+        assert!((inverse[0][1] + b / determinant).abs() < 1e-6);
+        assert!((inverse[1][1] - a / determinant).abs() < 1e-6)
+    }
+
+    #[test]
+    fn periodic_inversion() {
+        let mut covariance = OnlineCovariance::default();
+        let values = |i: usize| [i as f64, (i * i % 17) as f64];
+        (0..INVERSION_INTERVAL).for_each(|i| covariance.update(&values(i)));
+        let inverted = covariance.inverse.clone();
+
+        // the inverse is kept until the next interval
+        (INVERSION_INTERVAL..2 * INVERSION_INTERVAL - 1)
+            .for_each(|i| covariance.update(&values(i)));
+        assert_eq!(covariance.inverse, inverted);
+        covariance.update(&values(2 * INVERSION_INTERVAL - 1));
+        assert_ne!(covariance.inverse, inverted);
+        assert_eq!(covariance.inverse, covariance.invert())
+    }
+
+    #[test]
+    fn mahalanobis_scale_invariant() {
+        use crate::data_manipulation::anonymizable::QuasiIdentifierType::Integer;
+        use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::Interval;
+
+        let mahalanobis = Mahalanobis::default();
+        let tuple = |age: i32, fnlwgt: i32| {
+            vec![
+                Interval((Integer(age), Integer(0), Integer(100), 1)),
+                Interval((Integer(fnlwgt), Integer(0), Integer(1500000), 1)),
+            ]
+        };
+        // before the covariance is known the normalized euclidean distance is used
+        let differences = vec![
+            difference(0.1, 1, vec![10.0]),
+            difference(0.0, 1, vec![0.0]),
+        ];
+        assert_eq!(
+            mahalanobis.distance(&differences),
+            NormalizedEuclidean.distance(&differences)
+        );
+
+        // every combination of 8 ages and 8 weights, so both are uncorrelated
+        (0..64).for_each(|index| mahalanobis.observe(&tuple(index % 8 * 5, index / 8 * 100000)));
+        let variance = |index: usize| {
+            let covariance = mahalanobis.covariance.read().unwrap();
+            covariance.comoment[index][index] / (covariance.count - 1) as f64
+        };
+
+        // a difference of 1 standard deviation has a distance of 1, whatever the scale of the QI
+        let age = mahalanobis.distance(&[
+            difference(0.0, 1, vec![variance(0).sqrt()]),
+            difference(0.0, 1, vec![0.0]),
+        ]);
+        let fnlwgt = mahalanobis.distance(&[
+            difference(0.0, 1, vec![0.0]),
+            difference(0.0, 1, vec![variance(1).sqrt()]),
+        ]);
+        assert!((age - 1.0).abs() < 1e-6);
+        assert!((fnlwgt - 1.0).abs() < 1e-6)
+    }
+}
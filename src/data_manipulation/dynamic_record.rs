@@ -0,0 +1,514 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use csv::StringRecord;
+use hocon::HoconLoader;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::data_manipulation::aggregation::truncate_to_domain;
+use crate::data_manipulation::anonymizable::{
+    Anonymizable, QuasiIdentifierType, QuasiIdentifierTypes, SensitiveAttribute,
+};
+
+/// The role a column plays during the anonymization
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// numerical quasi identifier with a `min` and `max` domain
+    Interval,
+    /// categorical quasi identifier where the order of the `categories` matters
+    Ordinal,
+    /// categorical quasi identifier without an order between the `categories`
+    Nominal,
+    /// a sensitive attribute, at least one column needs to be sensitive
+    Sensitive,
+    /// published unchanged
+    Passthrough,
+}
+
+/// The type of the values of an interval column
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    #[default]
+    Integer,
+    Float,
+}
+
+/// Description of a single CSV column
+#[derive(Deserialize, Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(default)]
+    pub value_type: ValueType, // only used by interval columns
+    #[serde(default)]
+    pub min: f64, // only used by interval columns
+    #[serde(default)]
+    pub max: f64, // only used by interval columns
+    #[serde(default)]
+    pub categories: Vec<String>, // only used by ordinal and nominal columns
+    #[serde(default = "default_weight")]
+    pub weight: usize,
+    #[serde(default)]
+    pub bucket_width: Option<f64>, // only used by sensitive columns, makes the column numeric
+}
+
+fn default_weight() -> usize {
+    1
+}
+
+impl Column {
+    fn is_quasi_identifier(&self) -> bool {
+        matches!(
+            self.column_type,
+            ColumnType::Interval | ColumnType::Ordinal | ColumnType::Nominal
+        )
+    }
+}
+
+/// Schema describing which columns of a dataset are quasi identifiers,
+/// which columns are sensitive and which columns pass through unchanged.
+/// The schema can be written in HOCON or JSON:
+/// ```hocon
+/// columns = [
+///     { name = "age", type = "interval", min = 1, max = 100, weight = 1 }
+///     { name = "gender", type = "nominal", categories = ["male", "female"] }
+///     { name = "class", type = "sensitive" }
+///     { name = "salary", type = "sensitive", bucket_width = 1000 }
+///     { name = "timestamp", type = "passthrough" }
+/// ]
+/// ```
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DynamicSchema {
+    pub columns: Vec<Column>,
+}
+
+impl DynamicSchema {
+    /// load the schema from a `.json` or HOCON file and validate it
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let schema: DynamicSchema = match path.ends_with(".json") {
+            true => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            false => HoconLoader::new().load_file(path)?.resolve()?,
+        };
+
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// check if the schema can be used for anonymization
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let sensitive_count = self
+            .columns
+            .iter()
+            .filter(|column| column.column_type == ColumnType::Sensitive)
+            .count();
+        if sensitive_count == 0 {
+            return Err("schema needs at least one sensitive column".into());
+        }
+
+        if !self.columns.iter().any(Column::is_quasi_identifier) {
+            return Err("schema needs at least one quasi identifier column".into());
+        }
+
+        for column in &self.columns {
+            match column.column_type {
+                ColumnType::Interval if column.min >= column.max => {
+                    return Err(
+                        format!("column {} needs a min smaller than max", column.name).into(),
+                    )
+                }
+                ColumnType::Ordinal | ColumnType::Nominal if column.categories.is_empty() => {
+                    return Err(format!("column {} has no categories", column.name).into())
+                }
+                ColumnType::Sensitive if column.bucket_width.is_some_and(|width| width <= 0.0) => {
+                    return Err(
+                        format!("column {} needs a positive bucket_width", column.name).into(),
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// names of the columns in the order they are exported
+    pub fn headers(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| column.name.to_owned())
+            .collect()
+    }
+
+    /// find the position of every schema column inside the CSV headers
+    pub fn column_indices(&self, headers: &StringRecord) -> Result<Vec<usize>, Box<dyn Error>> {
+        self.columns
+            .iter()
+            .map(|column| {
+                headers
+                    .iter()
+                    .position(|header| header == column.name)
+                    .ok_or_else(|| format!("column {} not found in dataset", column.name).into())
+            })
+            .collect()
+    }
+}
+
+/// A single value of a `DynamicRecord`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum DynamicValue {
+    Integer(i32),
+    Float(f64),
+    Text(String),
+}
+
+impl Display for DynamicValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DynamicValue::Integer(value) => write!(f, "{}", value),
+            DynamicValue::Float(value) => write!(f, "{}", value),
+            DynamicValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Data tuple whose quasi identifiers are described by a `DynamicSchema`
+/// instead of Rust code. Makes it possible to anonymize any CSV by only
+/// writing a schema.
+#[derive(Debug, Clone)]
+pub struct DynamicRecord {
+    schema: Arc<DynamicSchema>,
+    values: Vec<DynamicValue>,
+    time_generated: SystemTime,
+}
+
+impl DynamicRecord {
+    /// create a record out of a CSV row, `indices` contains the position of every schema
+    /// column in the row and can be retrieved with `DynamicSchema::column_indices`
+    pub fn from_csv(
+        schema: &Arc<DynamicSchema>,
+        indices: &[usize],
+        row: &StringRecord,
+    ) -> Result<Self, Box<dyn Error>> {
+        let values = schema
+            .columns
+            .iter()
+            .zip(indices)
+            .map(|(column, index)| {
+                let raw = row
+                    .get(*index)
+                    .ok_or_else(|| format!("column {} missing in row", column.name))?;
+                Self::parse_value(column, raw)
+            })
+            .collect::<Result<Vec<DynamicValue>, Box<dyn Error>>>()?;
+
+        Ok(Self {
+            schema: schema.clone(),
+            values,
+            time_generated: SystemTime::now(),
+        })
+    }
+
+    fn parse_value(column: &Column, raw: &str) -> Result<DynamicValue, Box<dyn Error>> {
+        match column.column_type {
+            ColumnType::Interval => match column.value_type {
+                ValueType::Integer => Ok(DynamicValue::Integer(raw.trim().parse()?)),
+                ValueType::Float => Ok(DynamicValue::Float(raw.trim().parse()?)),
+            },
+            ColumnType::Ordinal | ColumnType::Nominal => {
+                match column.categories.iter().any(|category| category == raw) {
+                    true => Ok(DynamicValue::Text(raw.to_string())),
+                    false => Err(format!(
+                        "unknown category {} found for column {}",
+                        raw, column.name
+                    )
+                    .into()),
+                }
+            }
+            ColumnType::Sensitive if column.bucket_width.is_some() => {
+                Ok(DynamicValue::Float(raw.trim().parse()?))
+            }
+            ColumnType::Sensitive | ColumnType::Passthrough => {
+                Ok(DynamicValue::Text(raw.to_string()))
+            }
+        }
+    }
+
+    /// value of the column with the given name
+    pub fn get(&self, name: &str) -> Option<&DynamicValue> {
+        self.schema
+            .columns
+            .iter()
+            .position(|column| column.name == name)
+            .and_then(|index| self.values.get(index))
+    }
+
+    /// position of a category inside the categories of a column
+    fn category_index(column: &Column, value: &DynamicValue) -> i32 {
+        match value {
+            DynamicValue::Text(text) => column
+                .categories
+                .iter()
+                .position(|category| category == text)
+                .expect("category has been validated when parsing")
+                as i32,
+            _ => panic!("Wrong value type found for categorical column"),
+        }
+    }
+}
+
+impl Default for DynamicRecord {
+    fn default() -> Self {
+        Self {
+            schema: Default::default(),
+            values: vec![],
+            time_generated: SystemTime::now(),
+        }
+    }
+}
+
+impl Serialize for DynamicRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (column, value) in self.schema.columns.iter().zip(&self.values) {
+            map.serialize_entry(&column.name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Anonymizable for DynamicRecord {
+    fn quasi_identifiers(&self) -> Vec<QuasiIdentifierTypes> {
+        self.schema
+            .columns
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(column, value)| match (column.column_type, value) {
+                (ColumnType::Interval, DynamicValue::Integer(value)) => {
+                    Some(QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Integer(*value),
+                        QuasiIdentifierType::Integer(column.min as i32),
+                        QuasiIdentifierType::Integer(column.max as i32),
+                        column.weight,
+                    )))
+                }
+                (ColumnType::Interval, DynamicValue::Float(value)) => {
+                    Some(QuasiIdentifierTypes::Interval((
+                        QuasiIdentifierType::Float(*value),
+                        QuasiIdentifierType::Float(column.min),
+                        QuasiIdentifierType::Float(column.max),
+                        column.weight,
+                    )))
+                }
+                // ranks start at 1
+                (ColumnType::Ordinal, value) => Some(QuasiIdentifierTypes::Ordinal((
+                    Self::category_index(column, value) + 1,
+                    column.categories.len() as i32,
+                    column.weight,
+                ))),
+                (ColumnType::Nominal, value) => Some(QuasiIdentifierTypes::Nominal((
+                    Self::category_index(column, value),
+                    column.categories.len() as i32 - 1,
+                    column.weight,
+                ))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn update_quasi_identifiers(&self, qi: Vec<QuasiIdentifierTypes>) -> Self {
+        let mut update = self.clone();
+        let mut qi = qi.into_iter();
+
+        for (column, value) in update.schema.columns.iter().zip(update.values.iter_mut()) {
+            if !column.is_quasi_identifier() {
+                continue;
+            }
+
+            *value = match (column.column_type, qi.next().map(|x| x.extract_value())) {
+                (ColumnType::Interval, Some(QuasiIdentifierType::Integer(new))) => {
+                    DynamicValue::Integer(new)
+                }
+                (ColumnType::Interval, Some(QuasiIdentifierType::Float(new))) => {
+                    DynamicValue::Float(new)
+                }
+                (ColumnType::Ordinal, Some(QuasiIdentifierType::Integer(rank))) => {
+                    let rank = truncate_to_domain(rank, 1, column.categories.len() as i32);
+                    DynamicValue::Text(column.categories[(rank - 1) as usize].to_owned())
+                }
+                (ColumnType::Nominal, Some(QuasiIdentifierType::Integer(index))) => {
+                    DynamicValue::Text(column.categories[index as usize].to_owned())
+                }
+                _ => panic!("Couldn't update column {} with QI's", column.name),
+            }
+        }
+
+        update
+    }
+
+    fn sensitive_value(&self) -> SensitiveAttribute {
+        self.sensitive_values()
+            .into_iter()
+            .next()
+            .expect("schema has been validated to contain a sensitive column")
+    }
+
+    fn sensitive_values(&self) -> Vec<SensitiveAttribute> {
+        self.schema
+            .columns
+            .iter()
+            .zip(self.values.iter())
+            .filter(|(column, _)| column.column_type == ColumnType::Sensitive)
+            .map(|(column, value)| match (value, column.bucket_width) {
+                (DynamicValue::Float(value), Some(bucket_width)) => {
+                    SensitiveAttribute::Float(*value, bucket_width)
+                }
+                _ => SensitiveAttribute::String(value.to_string()),
+            })
+            .collect()
+    }
+
+    fn extract_string_values(&self, uuid: Uuid, dr: f64) -> Vec<String> {
+        let mut values = vec![uuid.to_string(), dr.to_string()];
+        values.extend(self.values.iter().map(|value| value.to_string()));
+        values
+    }
+
+    fn get_timestamp(&self) -> SystemTime {
+        self.time_generated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use csv::StringRecord;
+
+    use crate::data_manipulation::anonymizable::QuasiIdentifierType::{Float, Integer};
+    use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::{
+        Interval, Nominal, Ordinal,
+    };
+    use crate::data_manipulation::anonymizable::{Anonymizable, SensitiveAttribute};
+    use crate::data_manipulation::dynamic_record::{DynamicRecord, DynamicSchema, DynamicValue};
+
+    const SCHEMA: &str = r#"{"columns": [
+        {"name": "id", "type": "passthrough"},
+        {"name": "age", "type": "interval", "min": 1, "max": 100},
+        {"name": "score", "type": "interval", "value_type": "float", "min": 0, "max": 1, "weight": 2},
+        {"name": "size", "type": "ordinal", "categories": ["small", "medium", "large"]},
+        {"name": "gender", "type": "nominal", "categories": ["male", "female"]},
+        {"name": "class", "type": "sensitive"}
+    ]}"#;
+
+    fn create_record() -> DynamicRecord {
+        let schema: DynamicSchema = serde_json::from_str(SCHEMA).unwrap();
+        schema.validate().unwrap();
+        let schema = Arc::new(schema);
+        let headers = StringRecord::from(vec!["gender", "age", "class", "size", "score", "id"]);
+        let row = StringRecord::from(vec!["female", "32", "<=50K", "large", "0.25", "7"]);
+        let indices = schema.column_indices(&headers).unwrap();
+
+        DynamicRecord::from_csv(&schema, &indices, &row).unwrap()
+    }
+
+    #[test]
+    fn quasi_identifiers() {
+        let record = create_record();
+        let mut qi = record.quasi_identifiers();
+
+        assert_eq!(qi.len(), 4);
+        match qi.remove(0) {
+            Interval((Integer(32), Integer(1), Integer(100), 1)) => {}
+            _ => panic!(),
+        }
+        match qi.remove(0) {
+            Interval((Float(score), Float(_), Float(_), 2)) => assert_eq!(score, 0.25),
+            _ => panic!(),
+        }
+        match qi.remove(0) {
+            Ordinal((3, 3, 1)) => {}
+            _ => panic!(),
+        }
+        match qi.remove(0) {
+            Nominal((1, 1, 1)) => {}
+            _ => panic!(),
+        }
+        assert!(record.sensitive_value() == SensitiveAttribute::String("<=50K".to_string()))
+    }
+
+    #[test]
+    fn update_quasi_identifiers() {
+        let record = create_record();
+        let qi = vec![
+            Interval((Integer(50), Integer(1), Integer(100), 1)),
+            Interval((Float(0.5), Float(0.0), Float(1.0), 2)),
+            Ordinal((1, 3, 1)),
+            Nominal((0, 1, 1)),
+        ];
+
+        let updated = record.update_quasi_identifiers(qi);
+
+        assert_eq!(updated.get("age"), Some(&DynamicValue::Integer(50)));
+        assert_eq!(updated.get("score"), Some(&DynamicValue::Float(0.5)));
+        assert_eq!(
+            updated.get("size"),
+            Some(&DynamicValue::Text("small".to_string()))
+        );
+        assert_eq!(
+            updated.get("gender"),
+            Some(&DynamicValue::Text("male".to_string()))
+        );
+        assert_eq!(
+            updated.get("id"),
+            Some(&DynamicValue::Text("7".to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_category() {
+        let schema: DynamicSchema = serde_json::from_str(SCHEMA).unwrap();
+        let schema = Arc::new(schema);
+        let headers = StringRecord::from(vec!["id", "age", "score", "size", "gender", "class"]);
+        let row = StringRecord::from(vec!["7", "32", "0.25", "huge", "female", "<=50K"]);
+        let indices = schema.column_indices(&headers).unwrap();
+
+        assert!(DynamicRecord::from_csv(&schema, &indices, &row).is_err())
+    }
+
+    #[test]
+    fn multiple_sensitive_columns() {
+        let schema: DynamicSchema = serde_json::from_str(
+            r#"{"columns": [
+                {"name": "age", "type": "interval", "min": 1, "max": 100},
+                {"name": "diagnosis", "type": "sensitive"},
+                {"name": "medication", "type": "sensitive"},
+                {"name": "salary", "type": "sensitive", "bucket_width": 1000}
+            ]}"#,
+        )
+        .unwrap();
+        schema.validate().unwrap();
+        let schema = Arc::new(schema);
+        let headers = StringRecord::from(vec!["age", "diagnosis", "medication", "salary"]);
+        let row = StringRecord::from(vec!["32", "flu", "aspirin", "41250.5"]);
+        let indices = schema.column_indices(&headers).unwrap();
+        let record = DynamicRecord::from_csv(&schema, &indices, &row).unwrap();
+
+        assert!(record.sensitive_value() == SensitiveAttribute::String("flu".to_string()));
+        assert!(
+            record.sensitive_values()
+                == vec![
+                    SensitiveAttribute::String("flu".to_string()),
+                    SensitiveAttribute::String("aspirin".to_string()),
+                    SensitiveAttribute::Float(41999.0, 1000.0)
+                ]
+        )
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::{Display, Formatter};
+
+/// mean radius of the earth in kilometers
+pub const EARTH_RADIUS: f64 = 6371.0088;
+/// largest distance between 2 points on the earth in kilometers
+pub const HALF_CIRCUMFERENCE: f64 = std::f64::consts::PI * EARTH_RADIUS;
+
+/// Point on the earth in degrees
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// create a point, the longitude is wrapped into -180..180
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude: wrap_longitude(longitude),
+        }
+    }
+
+    /// check that the latitude is inside -90..=90 and the longitude inside -180..=180
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.latitude) && (-180.0..=180.0).contains(&self.longitude)
+    }
+
+    /// great circle distance in kilometers using the haversine formula
+    pub fn haversine(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = wrap_longitude(other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// distance in kilometers using the equirectangular approximation, which is
+    /// cheaper than the haversine formula and accurate for small distances
+    pub fn equirectangular(&self, other: &GeoPoint) -> f64 {
+        let mean_lat = ((self.latitude + other.latitude) / 2.0).to_radians();
+        let x = wrap_longitude(other.longitude - self.longitude).to_radians() * mean_lat.cos();
+        let y = (other.latitude - self.latitude).to_radians();
+
+        EARTH_RADIUS * (x * x + y * y).sqrt()
+    }
+
+    /// point reached by travelling the distance in kilometers along the bearing in radians
+    pub fn destination(&self, bearing: f64, distance: f64) -> GeoPoint {
+        let lat = self.latitude.to_radians();
+        let lon = self.longitude.to_radians();
+        let angle = distance / EARTH_RADIUS;
+
+        let dest_lat = (lat.sin() * angle.cos() + lat.cos() * angle.sin() * bearing.cos()).asin();
+        let dest_lon = lon
+            + (bearing.sin() * angle.sin() * lat.cos())
+                .atan2(angle.cos() - lat.sin() * dest_lat.sin());
+
+        GeoPoint::new(dest_lat.to_degrees(), dest_lon.to_degrees())
+    }
+
+    /// unit vector pointing from the center of the earth to the point
+    pub fn to_cartesian(self) -> (f64, f64, f64) {
+        let (lat, lon) = (self.latitude.to_radians(), self.longitude.to_radians());
+        (lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+    }
+}
+
+impl Display for GeoPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.latitude, self.longitude)
+    }
+}
+
+/// Formula used for the distance between 2 geo points
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoDistance {
+    #[default]
+    Haversine,
+    Equirectangular,
+}
+
+/// Domain of a geo point QI
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GeoDomain {
+    pub max_distance: f64, // distance in kilometers that normalizes distances to 0..1
+    pub privacy_radius: f64, // radius in kilometers inside which points are indistinguishable
+    pub formula: GeoDistance,
+}
+
+impl Default for GeoDomain {
+    fn default() -> Self {
+        Self {
+            max_distance: HALF_CIRCUMFERENCE,
+            privacy_radius: 1.0,
+            formula: GeoDistance::Haversine,
+        }
+    }
+}
+
+impl GeoDomain {
+    pub fn new(max_distance: f64) -> Self {
+        Self {
+            max_distance,
+            ..Default::default()
+        }
+    }
+
+    /// radius in kilometers in which the planar laplace noise makes points indistinguishable
+    pub fn with_privacy_radius(mut self, privacy_radius: f64) -> Self {
+        self.privacy_radius = privacy_radius;
+        self
+    }
+
+    pub fn with_formula(mut self, formula: GeoDistance) -> Self {
+        self.formula = formula;
+        self
+    }
+
+    /// distance in kilometers between 2 points using the formula of the domain
+    pub fn distance(&self, x: &GeoPoint, y: &GeoPoint) -> f64 {
+        match self.formula {
+            GeoDistance::Haversine => x.haversine(y),
+            GeoDistance::Equirectangular => x.equirectangular(y),
+        }
+    }
+
+    /// distance between 2 points relative to the max distance of the domain, capped at 1
+    pub fn normalized_distance(&self, x: &GeoPoint, y: &GeoPoint) -> f64 {
+        (self.distance(x, y) / self.max_distance).min(1.0)
+    }
+}
+
+/// wrap a longitude in degrees into -180..180
+pub fn wrap_longitude(longitude: f64) -> f64 {
+    (longitude + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// centroid of points on the sphere, the mean of their unit vectors projected back onto
+/// the sphere. Falls back to the first point when the points cancel each other out
+pub fn spherical_centroid(points: &[GeoPoint]) -> Option<GeoPoint> {
+    let first = points.first()?;
+    let sum = points
+        .iter()
+        .map(|point| point.to_cartesian())
+        .fold((0.0, 0.0, 0.0), |(x, y, z), (px, py, pz)| {
+            (x + px, y + py, z + pz)
+        });
+
+    Some(spherical_centroid_of_sum(*first, sum, points.len()))
+}
+
+/// centroid of the amount of points whose unit vectors add up to the sum,
+/// the first point when the sum is too short to have a direction
+pub fn spherical_centroid_of_sum(
+    first: GeoPoint,
+    (x, y, z): (f64, f64, f64),
+    amount: usize,
+) -> GeoPoint {
+    match (x * x + y * y + z * z).sqrt() <= f64::EPSILON * amount as f64 {
+        true => first,
+        false => GeoPoint::new(
+            z.atan2((x * x + y * y).sqrt()).to_degrees(),
+            y.atan2(x).to_degrees(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn haversine() {
+        let amsterdam = GeoPoint::new(52.3676, 4.9041);
+        let brussels = GeoPoint::new(50.8503, 4.3517);
+
+        assert!((amsterdam.haversine(&brussels) - 173.0).abs() < 0.1);
+        assert!((amsterdam.equirectangular(&brussels) - 173.0).abs() < 0.1);
+        assert_eq!(amsterdam.haversine(&amsterdam), 0.0)
+    }
+
+    #[test]
+    fn distance_across_antimeridian() {
+        let west = GeoPoint::new(0.0, 179.5);
+        let east = GeoPoint::new(0.0, -179.5);
+        let one_degree = GeoPoint::new(0.0, 0.0).haversine(&GeoPoint::new(0.0, 1.0));
+
+        assert!((west.haversine(&east) - one_degree).abs() < 1e-9);
+        assert!((west.equirectangular(&east) - one_degree).abs() < 1e-9)
+    }
+
+    #[test]
+    fn centroid_across_antimeridian() {
+        let centroid =
+            spherical_centroid(&[GeoPoint::new(10.0, 179.0), GeoPoint::new(10.0, -179.0)]).unwrap();
+
+        assert!((centroid.longitude.abs() - 180.0).abs() < 1e-9);
+        assert!((centroid.latitude - 10.0).abs() < 0.01);
+        assert_eq!(spherical_centroid(&[]), None)
+    }
+
+    #[test]
+    fn destination() {
+        let origin = GeoPoint::new(52.0, 5.0);
+        let destination = origin.destination(1.0, 25.0);
+
+        assert!((origin.haversine(&destination) - 25.0).abs() < 1e-6);
+        // travelling east from just before the antimeridian wraps around to negative longitudes
+        let wrapped = GeoPoint::new(0.0, 179.9).destination(std::f64::consts::FRAC_PI_2, 100.0);
+        assert!(wrapped.longitude < 0.0 && wrapped.is_valid())
+    }
+}
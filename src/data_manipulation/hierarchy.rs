@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+/// label of the root of every hierarchy
+pub const ROOT_LABEL: &str = "*";
+
+/// Value generalization hierarchy (taxonomy tree) of a nominal QI,
+/// e.g. country -> region -> continent.
+///
+/// Every node of the tree has an `i32` value which is used inside the QI.
+/// The leaves get the values `0..n` in the order their paths are given,
+/// the inner nodes and the root get the values after that.
+#[derive(Debug, Clone)]
+pub struct Hierarchy {
+    labels: Vec<String>,         // label of every node
+    parents: Vec<Option<usize>>, // parent of every node, the root has no parent
+    depths: Vec<usize>,          // depth of every node, the root has depth 0
+    leaf_count: usize,           // amount of leaves in the hierarchy
+    height: usize,               // depth of the deepest leaf
+    ancestor_centroid: bool, // publish the lowest common ancestor as centroid instead of the mode
+}
+
+impl Hierarchy {
+    /// create a hierarchy out of the paths from the top generalization to every leaf,
+    /// the root `*` is added implicitly
+    /// ```
+    /// # use diff_priv::data_manipulation::hierarchy::Hierarchy;
+    /// let hierarchy = Hierarchy::from_paths(vec![
+    ///     vec!["Europe", "Western-Europe", "Germany"],
+    ///     vec!["Europe", "Western-Europe", "France"],
+    ///     vec!["Europe", "Eastern-Europe", "Poland"],
+    ///     vec!["Asia", "East-Asia", "Japan"],
+    /// ]);
+    ///
+    /// let germany = hierarchy.value("Germany").unwrap();
+    /// let france = hierarchy.value("France").unwrap();
+    /// let ancestor = hierarchy.lowest_common_ancestor(germany, france);
+    /// assert_eq!(hierarchy.label(ancestor), "Western-Europe");
+    /// ```
+    pub fn from_paths(paths: Vec<Vec<&str>>) -> Self {
+        let leaf_count = paths.len();
+        let mut hierarchy = Self {
+            labels: vec![String::new(); leaf_count],
+            parents: vec![None; leaf_count],
+            depths: vec![0; leaf_count],
+            leaf_count,
+            height: 0,
+            ancestor_centroid: false,
+        };
+        let root = hierarchy.push_node(ROOT_LABEL, None);
+        // inner nodes are identified by their parent and label
+        let mut inner_nodes: HashMap<(usize, &str), usize> = HashMap::new();
+
+        paths.iter().enumerate().for_each(|(leaf, path)| {
+            let (leaf_label, generalizations) = path
+                .split_last()
+                .expect("paths of a hierarchy can't be empty");
+
+            let parent = generalizations.iter().fold(root, |parent, label| {
+                *inner_nodes
+                    .entry((parent, *label))
+                    .or_insert_with(|| hierarchy.push_node(label, Some(parent)))
+            });
+
+            if hierarchy.labels[..leaf_count].contains(&leaf_label.to_string()) {
+                panic!("duplicate leaf {} found in hierarchy", leaf_label)
+            }
+            hierarchy.labels[leaf] = leaf_label.to_string();
+            hierarchy.parents[leaf] = Some(parent);
+            hierarchy.depths[leaf] = hierarchy.depths[parent] + 1;
+            hierarchy.height = hierarchy.height.max(hierarchy.depths[leaf]);
+        });
+
+        hierarchy
+    }
+
+    fn push_node(&mut self, label: &str, parent: Option<usize>) -> usize {
+        self.labels.push(label.to_string());
+        self.parents.push(parent);
+        self.depths
+            .push(parent.map(|parent| self.depths[parent] + 1).unwrap_or(0));
+        self.labels.len() - 1
+    }
+
+    /// publish the lowest common ancestor of the values inside a cluster as centroid
+    /// instead of the most common value
+    pub fn with_ancestor_centroid(mut self) -> Self {
+        self.ancestor_centroid = true;
+        self
+    }
+
+    pub fn ancestor_centroid(&self) -> bool {
+        self.ancestor_centroid
+    }
+
+    /// value of the node with the given label, leaves are searched first
+    pub fn value(&self, label: &str) -> Option<i32> {
+        self.labels
+            .iter()
+            .position(|node| node == label)
+            .map(|node| node as i32)
+    }
+
+    /// label of the node with the given value
+    pub fn label(&self, value: i32) -> &str {
+        &self.labels[value as usize]
+    }
+
+    /// amount of leaves, the leaves have the values `0..leaf_count`
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// amount of nodes, every value of the hierarchy is in `0..node_count`
+    pub fn node_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// value of the root of the hierarchy
+    pub fn root(&self) -> i32 {
+        self.leaf_count as i32
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// find the most specific generalization containing both values
+    pub fn lowest_common_ancestor(&self, x: i32, y: i32) -> i32 {
+        let (mut x, mut y) = (x as usize, y as usize);
+        while self.depths[x] > self.depths[y] {
+            x = self.parents[x].unwrap();
+        }
+        while self.depths[y] > self.depths[x] {
+            y = self.parents[y].unwrap();
+        }
+        while x != y {
+            x = self.parents[x].unwrap();
+            y = self.parents[y].unwrap();
+        }
+
+        x as i32
+    }
+
+    /// normalized distance between 2 values, based on the height of their lowest
+    /// common ancestor in the tree. Equal values have distance 0 and values whose only
+    /// common ancestor is the root have distance 1
+    pub fn distance(&self, x: i32, y: i32) -> f64 {
+        if x == y || self.height == 0 {
+            return 0.0;
+        }
+
+        let ancestor = self.lowest_common_ancestor(x, y);
+        (self.height - self.depths[ancestor as usize]) as f64 / self.height as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_hierarchy() -> Hierarchy {
+        Hierarchy::from_paths(vec![
+            vec!["Europe", "Western-Europe", "Germany"],
+            vec!["Europe", "Western-Europe", "France"],
+            vec!["Europe", "Eastern-Europe", "Poland"],
+            vec!["Asia", "East-Asia", "Japan"],
+        ])
+    }
+
+    #[test]
+    fn leaf_values() {
+        let hierarchy = create_hierarchy();
+
+        assert_eq!(hierarchy.value("Germany"), Some(0));
+        assert_eq!(hierarchy.value("Japan"), Some(3));
+        assert_eq!(hierarchy.root(), 4);
+        assert_eq!(hierarchy.label(4), ROOT_LABEL);
+        assert_eq!(hierarchy.height(), 3)
+    }
+
+    #[test]
+    fn lowest_common_ancestor() {
+        let hierarchy = create_hierarchy();
+        let poland = hierarchy.value("Poland").unwrap();
+        let france = hierarchy.value("France").unwrap();
+        let japan = hierarchy.value("Japan").unwrap();
+
+        let ancestor = hierarchy.lowest_common_ancestor(poland, france);
+        assert_eq!(hierarchy.label(ancestor), "Europe");
+        assert_eq!(
+            hierarchy.lowest_common_ancestor(poland, japan),
+            hierarchy.root()
+        );
+        assert_eq!(hierarchy.lowest_common_ancestor(ancestor, france), ancestor)
+    }
+
+    #[test]
+    fn distance() {
+        let hierarchy = create_hierarchy();
+        let germany = hierarchy.value("Germany").unwrap();
+        let france = hierarchy.value("France").unwrap();
+        let poland = hierarchy.value("Poland").unwrap();
+        let japan = hierarchy.value("Japan").unwrap();
+
+        assert_eq!(hierarchy.distance(germany, germany), 0.0);
+        assert!((hierarchy.distance(germany, france) - 1.0 / 3.0).abs() <= f64::EPSILON);
+        assert!((hierarchy.distance(germany, poland) - 2.0 / 3.0).abs() <= f64::EPSILON);
+        assert_eq!(hierarchy.distance(germany, japan), 1.0)
+    }
+}
@@ -1,3 +1,8 @@
 pub mod aggregation;
 pub mod anonymizable;
+pub mod distance;
+pub mod dynamic_record;
+pub mod geo;
+pub mod hierarchy;
 pub mod mueller;
+pub mod temporal;
@@ -4,10 +4,11 @@ use strm_privacy_driver::StrmPrivacyValue;
 use uuid::Uuid;
 
 use crate::data_manipulation::anonymizable::QuasiIdentifierType::Integer;
-use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::{Interval, Nominal};
+use crate::data_manipulation::anonymizable::QuasiIdentifierTypes::{Interval, Missing, Nominal};
 use crate::data_manipulation::anonymizable::{
     Anonymizable, QuasiIdentifierTypes, SensitiveAttribute,
 };
+use crate::error::DiffPrivError;
 
 impl StrmPrivacyValue for MuellerStream {
     const STRM_SCHEMA_REF: &'static str = "diff_priv/mueller-steps/1.0.0";
@@ -63,7 +64,8 @@ pub struct MuellerStream {
     pub walk_ratio: Option<f32>,
     pub start: Option<String>,
     pub end: Option<String>,
-    #[serde(skip_deserializing, default = "default_time")]
+    #[serde(default = "default_time")]
+    // not part of the avro schema, the arrival time when decoded
     pub time_generated: SystemTime,
 }
 
@@ -96,35 +98,77 @@ impl Default for MuellerStream {
 
 impl Anonymizable for MuellerStream {
     fn quasi_identifiers(&self) -> Vec<QuasiIdentifierTypes> {
-        let age = Interval((Integer(self.age.unwrap()), Integer(33), Integer(85), 1));
-        let gender_string = self.gender.as_ref().unwrap();
-        let gender = match gender_string.as_str() {
-            "male" => (0, 1, 1),
-            "female" => (1, 1, 1),
-            _ => panic!("Not all categories covered"),
+        self.try_quasi_identifiers()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_quasi_identifiers(&self) -> Result<Vec<QuasiIdentifierTypes>, DiffPrivError> {
+        // every field of the stream is nullable, absent values become missing QI's
+        let age = match self.age {
+            Some(age) => Interval((Integer(age), Integer(33), Integer(85), 1)),
+            None => Missing(Box::new(Interval((
+                Integer(33),
+                Integer(33),
+                Integer(85),
+                1,
+            )))),
+        };
+        let gender = match self.gender.as_deref() {
+            Some("male") => Nominal((0, 1, 1)),
+            Some("female") => Nominal((1, 1, 1)),
+            None => Missing(Box::new(Nominal((0, 1, 1)))),
+            Some(other) => {
+                return Err(DiffPrivError::UnknownCategory(format!(
+                    "`{}` of `gender`",
+                    other
+                )))
+            }
         };
 
-        let gender = Nominal(gender);
+        Ok(vec![age, gender])
+    }
 
-        vec![age, gender]
+    fn update_quasi_identifiers(&self, qi: Vec<QuasiIdentifierTypes>) -> Self {
+        self.try_update_quasi_identifiers(qi)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
-    fn update_quasi_identifiers(&self, mut qi: Vec<QuasiIdentifierTypes>) -> Self {
+    fn try_update_quasi_identifiers(
+        &self,
+        mut qi: Vec<QuasiIdentifierTypes>,
+    ) -> Result<Self, DiffPrivError> {
         let mut update = self.clone();
-        let gender_qi = qi.pop().unwrap().extract_value();
-        let age_qi = qi.pop().unwrap().extract_value();
+        if qi.len() != 2 {
+            return Err(DiffPrivError::InvalidRecord(format!(
+                "expected 2 QI's, found {}",
+                qi.len()
+            )));
+        }
+        let gender_qi = qi.pop().unwrap().try_extract_value();
+        let age_qi = qi.pop().unwrap().try_extract_value();
 
         match gender_qi {
-            Integer(0) => update.gender = Some(String::from("male")),
-            Integer(1) => update.gender = Some(String::from("female")),
-            _ => panic!("Not all categories covered"),
+            Some(Integer(0)) => update.gender = Some(String::from("male")),
+            Some(Integer(1)) => update.gender = Some(String::from("female")),
+            None => update.gender = None,
+            _ => {
+                return Err(DiffPrivError::TypeMismatch(
+                    "gender needs a nominal QI of 0 or 1".to_string(),
+                ))
+            }
         }
 
-        if let Integer(age) = age_qi {
-            update.age = Some(age)
+        match age_qi {
+            Some(Integer(age)) => update.age = Some(age),
+            None => update.age = None,
+            _ => {
+                return Err(DiffPrivError::TypeMismatch(
+                    "age needs an integer QI".to_string(),
+                ))
+            }
         }
 
-        update
+        Ok(update)
     }
 
     fn sensitive_value(&self) -> SensitiveAttribute {
@@ -138,4 +182,12 @@ impl Anonymizable for MuellerStream {
     fn get_timestamp(&self) -> SystemTime {
         self.time_generated
     }
+
+    /// the STRM timestamp in milliseconds, the arrival time when it is missing
+    fn event_time(&self) -> SystemTime {
+        match self.strm_meta.timestamp {
+            Some(timestamp) => UNIX_EPOCH + Duration::from_millis(timestamp.max(0) as u64),
+            None => self.time_generated,
+        }
+    }
 }
@@ -0,0 +1,275 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::DiffPrivError;
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+/// the unix epoch is a thursday, weeks start 4 days later on monday
+const FIRST_MONDAY: i64 = 4 * DAY;
+
+/// Granularity a timestamp is published with, from fine to coarse.
+/// A timestamp is generalized by truncating it to the start of its unit
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+/// every granularity ordered from fine to coarse
+pub const GRANULARITY_LADDER: [Granularity; 5] = [
+    Granularity::Second,
+    Granularity::Minute,
+    Granularity::Hour,
+    Granularity::Day,
+    Granularity::Week,
+];
+
+impl Granularity {
+    /// length of a unit in seconds
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Granularity::Second => 1,
+            Granularity::Minute => MINUTE,
+            Granularity::Hour => HOUR,
+            Granularity::Day => DAY,
+            Granularity::Week => WEEK,
+        }
+    }
+
+    /// start of the unit containing the timestamp, weeks start on monday
+    pub fn truncate(&self, timestamp: i64) -> i64 {
+        match self {
+            Granularity::Week => timestamp - (timestamp - FIRST_MONDAY).rem_euclid(WEEK),
+            _ => timestamp - timestamp.rem_euclid(self.seconds()),
+        }
+    }
+
+    /// climb the ladder from this granularity to the first one whose unit
+    /// covers the given span of seconds, or the coarsest one if none does
+    pub fn generalize(&self, span: i64) -> Granularity {
+        GRANULARITY_LADDER
+            .into_iter()
+            .filter(|granularity| granularity >= self)
+            .find(|granularity| granularity.seconds() >= span)
+            .unwrap_or(Granularity::Week)
+    }
+}
+
+/// Domain of a temporal QI, timestamps are seconds since the unix epoch
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TemporalDomain {
+    pub min: i64,                 // earliest timestamp of the domain
+    pub max: i64,                 // latest timestamp of the domain
+    pub granularity: Granularity, // finest granularity timestamps are published with
+    median_centroid: bool,        // publish the median of a cluster as centroid instead of the mean
+}
+
+impl TemporalDomain {
+    pub fn new(min: i64, max: i64, granularity: Granularity) -> Self {
+        Self {
+            min,
+            max,
+            granularity,
+            median_centroid: false,
+        }
+    }
+
+    /// publish the median timestamp of a cluster as centroid instead of the mean
+    pub fn with_median_centroid(mut self) -> Self {
+        self.median_centroid = true;
+        self
+    }
+
+    pub fn median_centroid(&self) -> bool {
+        self.median_centroid
+    }
+
+    /// amount of seconds inside the domain
+    pub fn span(&self) -> i64 {
+        self.max - self.min
+    }
+}
+
+/// parse a `YYYY-MM-DD HH:MM:SS` UTC datetime into seconds since the unix epoch.
+/// The time, or fractions of a second after it, can be left out and `T` can separate date and time
+/// ```
+/// # use diff_priv::data_manipulation::temporal::parse_datetime;
+/// assert_eq!(parse_datetime("1970-01-02 00:01:00.25"), Ok(86460));
+/// assert_eq!(parse_datetime("2022-06-20"), Ok(1655683200));
+/// ```
+pub fn parse_datetime(text: &str) -> Result<i64, DiffPrivError> {
+    let error = || DiffPrivError::TypeMismatch(format!("`{}` is not a datetime", text));
+    let number = |part: Option<&str>| -> Result<i64, DiffPrivError> {
+        part.ok_or_else(error)?.parse::<i64>().map_err(|_| error())
+    };
+
+    let text = text.trim();
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+
+    let mut date = date.split('-');
+    let (year, month, day) = (
+        number(date.next())?,
+        number(date.next())?,
+        number(date.next())?,
+    );
+    if date.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(error());
+    }
+
+    let seconds = match time {
+        None => 0,
+        Some(time) => {
+            let time = time.split('.').next().unwrap_or_default();
+            let mut time = time.split(':');
+            let (hour, minute, second) = (
+                number(time.next())?,
+                number(time.next())?,
+                number(time.next())?,
+            );
+            if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+                return Err(error());
+            }
+            hour * HOUR + minute * MINUTE + second
+        }
+    };
+
+    Ok(days_from_civil(year, month, day) * DAY + seconds)
+}
+
+/// format seconds since the unix epoch as a `YYYY-MM-DD HH:MM:SS` UTC datetime
+pub fn format_datetime(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(DAY));
+    let seconds = timestamp.rem_euclid(DAY);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / HOUR,
+        seconds % HOUR / MINUTE,
+        seconds % MINUTE
+    )
+}
+
+/// seconds since the unix epoch of a `SystemTime`
+pub fn from_system_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(before_epoch) => -(before_epoch.duration().as_secs() as i64),
+    }
+}
+
+/// `SystemTime` of seconds since the unix epoch
+pub fn to_system_time(timestamp: i64) -> SystemTime {
+    match timestamp >= 0 {
+        true => UNIX_EPOCH + Duration::from_secs(timestamp as u64),
+        false => UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs()),
+    }
+}
+
+/// days since the unix epoch of a date in the proleptic gregorian calendar
+/// From: H. Hinnant, "chrono-Compatible Low-Level Date Algorithms"
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = match month <= 2 {
+        true => year - 1,
+        false => year,
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// date in the proleptic gregorian calendar of days since the unix epoch
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = match month_index < 10 {
+        true => month_index + 3,
+        false => month_index - 9,
+    };
+    let year = year_of_era + era * 400;
+
+    match month <= 2 {
+        true => (year + 1, month, day),
+        false => (year, month, day),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate() {
+        let timestamp = parse_datetime("2022-06-22 12:39:34").unwrap();
+
+        assert_eq!(
+            format_datetime(Granularity::Minute.truncate(timestamp)),
+            "2022-06-22 12:39:00"
+        );
+        assert_eq!(
+            format_datetime(Granularity::Day.truncate(timestamp)),
+            "2022-06-22 00:00:00"
+        );
+        // the 20th of june 2022 is a monday
+        assert_eq!(
+            format_datetime(Granularity::Week.truncate(timestamp)),
+            "2022-06-20 00:00:00"
+        );
+        assert_eq!(Granularity::Hour.truncate(-1), -HOUR)
+    }
+
+    #[test]
+    fn generalize() {
+        assert_eq!(Granularity::Second.generalize(0), Granularity::Second);
+        assert_eq!(Granularity::Second.generalize(90), Granularity::Hour);
+        assert_eq!(Granularity::Day.generalize(90), Granularity::Day);
+        assert_eq!(
+            Granularity::Second.generalize(100 * WEEK),
+            Granularity::Week
+        )
+    }
+
+    #[test]
+    fn datetime_round_trip() {
+        [
+            "1969-12-31 23:59:59",
+            "2000-02-29 13:05:09",
+            "2022-06-20 12:39:34",
+        ]
+        .into_iter()
+        .for_each(|datetime| {
+            assert_eq!(format_datetime(parse_datetime(datetime).unwrap()), datetime)
+        });
+        let timestamp = parse_datetime("2022-06-20T12:39:34.432215").unwrap();
+        assert_eq!(from_system_time(to_system_time(timestamp)), timestamp);
+        assert_eq!(from_system_time(to_system_time(-10)), -10)
+    }
+
+    #[test]
+    fn parse_invalid_datetime() {
+        assert!(parse_datetime("yesterday").is_err());
+        assert!(parse_datetime("2022-13-01").is_err());
+        assert!(parse_datetime("2022-06-20 25:00:00").is_err())
+    }
+}
@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors caused by a single bad record or invalid anonymizer parameters, they are raised
+/// instead of panicking so a long running stream can survive bad input
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffPrivError {
+    /// QI's of different data categories or value types are combined
+    TypeMismatch(String),
+    /// a categorical value that is not part of the domain of the QI
+    UnknownCategory(String),
+    /// the QI's of a record don't match the QI's of the records anonymized before
+    InvalidRecord(String),
+    /// the record couldn't be decoded from its input format
+    Decode(String),
+    /// the parameters of the anonymizer or noiser can't be combined
+    InvalidParameter(String),
+    /// the state of the anonymizer couldn't be written to or read from a snapshot
+    Snapshot(String),
+    /// a shard of a `ShardedAnonymizer` stopped before it was finished
+    Shard(String),
+    /// a record couldn't be sent to its output
+    Publish(String),
+    /// messages couldn't be fetched from or committed to the input
+    Consume(String),
+}
+
+impl fmt::Display for DiffPrivError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffPrivError::TypeMismatch(message) => write!(f, "type mismatch: {}", message),
+            DiffPrivError::UnknownCategory(message) => write!(f, "unknown category: {}", message),
+            DiffPrivError::InvalidRecord(message) => write!(f, "invalid record: {}", message),
+            DiffPrivError::Decode(message) => write!(f, "couldn't decode record: {}", message),
+            DiffPrivError::InvalidParameter(message) => write!(f, "invalid parameter: {}", message),
+            DiffPrivError::Snapshot(message) => write!(f, "snapshot failed: {}", message),
+            DiffPrivError::Shard(message) => write!(f, "shard stopped: {}", message),
+            DiffPrivError::Publish(message) => write!(f, "publishing failed: {}", message),
+            DiffPrivError::Consume(message) => write!(f, "consuming failed: {}", message),
+        }
+    }
+}
+
+impl Error for DiffPrivError {}
+
+/// What to do with a record that can't be anonymized
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordErrorPolicy {
+    /// log the error and continue with the next record
+    Skip,
+    /// keep the record together with its error aside and continue with the next record
+    DeadLetter,
+    /// stop and return the error to the caller
+    #[default]
+    Abort,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let error = DiffPrivError::UnknownCategory("`unknown` of `gender`".to_string());
+        assert_eq!(error.to_string(), "unknown category: `unknown` of `gender`")
+    }
+}
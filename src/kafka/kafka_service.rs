@@ -1,47 +1,167 @@
+use crate::anonymization::clock::TimeMode;
+use crate::anonymization::eviction::EvictionPolicy;
 use crate::anonymization::microagg_anonymizer::MicroaggAnonymizer;
+use crate::anonymization::suppression::SuppressionStrategy;
 use crate::config::Config;
+use crate::data_manipulation::distance::DistanceMetricType;
 use crate::data_manipulation::mueller::MuellerStream;
+use crate::error::{DiffPrivError, RecordErrorPolicy};
 use crate::noise::laplace::laplace_noiser::LaplaceNoiser;
+use crate::publishing::generalized::OutputMode;
 use crate::publishing::kafka_publisher::KafkaPublisher;
 use avro_rs::from_value;
 use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+use std::path::Path;
+use std::time::{Duration, Instant};
 use strm_privacy_driver::StrmPrivacyValue;
 
+/// length of the confluent wire format header in front of every message
+const CONFLUENT_HEADER: usize = 5;
+
 pub struct KafkaService {
     consumer: Consumer,
+    record_error_policy: RecordErrorPolicy,
+    distance_metric: DistanceMetricType,
+    output_mode: OutputMode,
+    suppression_strategy: SuppressionStrategy,
+    time_mode: TimeMode,
+    seed: Option<u64>,
+    checkpoint_path: Option<String>,
+    checkpoint_interval: Duration,
+    flush_interval: Duration,
+    max_clusters: Option<usize>,
+    memory_budget: Option<usize>,
+    eviction_policy: EvictionPolicy,
+    merge_evicted: bool,
 }
 
 impl KafkaService {
-    pub fn consume(&mut self) {
+    /// anonymize the messages of the input topic, only returns when a bad
+    /// record is found with `RecordErrorPolicy::Abort`, a checkpoint fails or the
+    /// broker can't be polled or committed to.
+    /// With a checkpoint path the offsets are only committed after the state of the
+    /// anonymizer is written, after a restart the uncommitted messages are anonymized again.
+    /// Expired clusters are flushed between polls, so they are published at most
+    /// `flush_interval` plus the duration of one poll after they expire
+    pub fn consume(&mut self) -> Result<(), DiffPrivError> {
         let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
         let publisher = KafkaPublisher::default();
+        let mut builder = MicroaggAnonymizer::builder(publisher, noiser)
+            .k(3)
+            .k_max(20)
+            .l(2)
+            .c(7)
+            .diff_thres(0.1)
+            .delta(300)
+            .buffer_size(5)
+            .record_error_policy(self.record_error_policy)
+            .distance_metric(self.distance_metric.build())
+            .output_mode(self.output_mode)
+            .suppression_strategy(self.suppression_strategy)
+            .time_mode(self.time_mode)
+            .eviction_policy(self.eviction_policy)
+            .merge_evicted(self.merge_evicted);
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(max_clusters) = self.max_clusters {
+            builder = builder.max_clusters(max_clusters);
+        }
+        if let Some(memory_budget) = self.memory_budget {
+            builder = builder.memory_budget(memory_budget);
+        }
         let mut microagg: MicroaggAnonymizer<LaplaceNoiser, MuellerStream, KafkaPublisher> =
-            MicroaggAnonymizer::new(3, 20, 2, 7, 0.1, 300, 5, publisher, noiser);
+            builder.build()?;
+        if let Some(path) = self
+            .checkpoint_path
+            .as_ref()
+            .filter(|p| Path::new(p).exists())
+        {
+            microagg.restore(path)?;
+            info!(
+                "restored {} clusters from {}",
+                microagg.cluster_set.len(),
+                path
+            )
+        }
+        let mut last_checkpoint = Instant::now();
+        let mut last_flush = Instant::now();
         loop {
-            for ms in self.consumer.poll().unwrap().iter() {
+            let message_sets = self
+                .consumer
+                .poll()
+                .map_err(|e| DiffPrivError::Consume(e.to_string()))?;
+            for ms in message_sets.iter() {
                 for m in ms.messages() {
-                    let mut b = &m.value[5..];
-                    let confluent_bytes = &m.value[..5];
-                    microagg.publisher.confluent_bytes = confluent_bytes.to_vec();
-                    let mueller_value = avro_rs::from_avro_datum(
-                        &MuellerStream::get_schema(MuellerStream::STRM_SCHEMA),
-                        &mut b,
-                        None,
-                    )
-                    .expect("couldn't convert message to mueller");
-                    let mueller = from_value::<MuellerStream>(&mueller_value)
-                        .expect("couldn't convert from value");
-                    microagg.anonymize(mueller)
+                    match decode(m.value) {
+                        Ok(mueller) => {
+                            microagg.publisher.confluent_bytes =
+                                m.value[..CONFLUENT_HEADER].to_vec();
+                            microagg.try_anonymize(mueller)?;
+                            // the raw message is sent instead of the decoded record
+                            if microagg.dead_letters.drain(..).count() > 0 {
+                                microagg.publisher.publish_dead_letter(m.value)?
+                            }
+                        }
+                        Err(e) => match self.record_error_policy {
+                            RecordErrorPolicy::Skip => warn!("skipping message: {}", e),
+                            RecordErrorPolicy::DeadLetter => {
+                                warn!("dead lettering message: {}", e);
+                                microagg.publisher.publish_dead_letter(m.value)?
+                            }
+                            RecordErrorPolicy::Abort => return Err(e),
+                        },
+                    }
                 }
                 self.consumer
                     .consume_messageset(ms)
-                    .expect("couldn't consume message");
+                    .map_err(|e| DiffPrivError::Consume(e.to_string()))?;
+            }
+            // the poll also returns on a quiet stream after the fetch max wait time,
+            // so clusters without new tuples are flushed once they expire
+            if last_flush.elapsed() >= self.flush_interval {
+                microagg.flush_expired();
+                last_flush = Instant::now();
+            }
+            match &self.checkpoint_path {
+                None => self
+                    .consumer
+                    .commit_consumed()
+                    .map_err(|e| DiffPrivError::Consume(e.to_string()))?,
+                Some(path) if last_checkpoint.elapsed() >= self.checkpoint_interval => {
+                    microagg.snapshot(path)?;
+                    self.consumer
+                        .commit_consumed()
+                        .map_err(|e| DiffPrivError::Consume(e.to_string()))?;
+                    last_checkpoint = Instant::now();
+                    debug!("checkpoint written to {}", path)
+                }
+                Some(_) => {}
             }
-            self.consumer.commit_consumed().unwrap();
         }
     }
 }
 
+/// decode a confluent avro message into a `MuellerStream`
+fn decode(message: &[u8]) -> Result<MuellerStream, DiffPrivError> {
+    if message.len() < CONFLUENT_HEADER {
+        return Err(DiffPrivError::Decode(format!(
+            "message of {} bytes is shorter than the confluent header",
+            message.len()
+        )));
+    }
+
+    let mut b = &message[CONFLUENT_HEADER..];
+    let mueller_value = avro_rs::from_avro_datum(
+        &MuellerStream::get_schema(MuellerStream::STRM_SCHEMA),
+        &mut b,
+        None,
+    )
+    .map_err(|e| DiffPrivError::Decode(e.to_string()))?;
+
+    from_value::<MuellerStream>(&mueller_value).map_err(|e| DiffPrivError::Decode(e.to_string()))
+}
+
 impl Default for KafkaService {
     fn default() -> Self {
         let config = Config::new(&"application.conf".to_string());
@@ -54,6 +174,35 @@ impl Default for KafkaService {
             .create()
             .expect("Consumer couldn't connect to bootstrap");
 
-        Self { consumer }
+        Self {
+            consumer,
+            record_error_policy: config.record_error_policy,
+            distance_metric: config.distance_metric,
+            output_mode: config.output_mode,
+            suppression_strategy: config.suppression_strategy,
+            time_mode: config.time_mode,
+            seed: config.seed,
+            checkpoint_path: config.checkpoint_path,
+            checkpoint_interval: Duration::from_secs(config.checkpoint_interval),
+            flush_interval: Duration::from_secs(config.flush_interval),
+            max_clusters: config.max_clusters,
+            memory_budget: config.memory_budget,
+            eviction_policy: config.eviction_policy,
+            merge_evicted: config.merge_evicted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_bad_message() {
+        assert!(matches!(decode(&[0, 1]), Err(DiffPrivError::Decode(_))));
+        assert!(matches!(
+            decode(&[0, 0, 0, 0, 0, 255, 255]),
+            Err(DiffPrivError::Decode(_))
+        ));
     }
 }
@@ -18,10 +18,19 @@
 //! let noiser = LaplaceNoiser::new(0.1, 3, 0.1);
 //! // we initialize a publisher that implements the `Publisher` trait
 //! let publisher = DummyPublisher::default();
-//! // we create the anonymizer with the desired parameters
-//! // k: 2 | k_max: 10 | c: 2 | l: 7 | diff_thres: 0.1 | delta: 10 | buff_size: 5
+//! // we create the anonymizer with the desired parameters, `build` returns an error
+//! // for parameters that can't be combined
 //! let mut anonymizer: MicroaggAnonymizer<LaplaceNoiser, Adult, DummyPublisher> =
-//!     MicroaggAnonymizer::new(2, 10, 2, 7, 0.1, 10, 5, publisher, noiser);
+//!     MicroaggAnonymizer::builder(publisher, noiser)
+//!         .k(2)
+//!         .k_max(10)
+//!         .l(2)
+//!         .c(7)
+//!         .diff_thres(0.1)
+//!         .delta(10)
+//!         .buffer_size(5)
+//!         .build()
+//!         .unwrap();
 //!
 //! // load CSV file representing an Adult
 //! let mut file = Reader::from_path("datasets/Adult_1_numeric_only_class_50K.csv").unwrap();
@@ -42,9 +51,17 @@
 //!     .cluster_set
 //!     .into_iter()
 //!     .for_each(|(_, mut cluster)| {
-//!         cluster.publish_all(&mut anonymizer.publisher, &mut anonymizer.analysers)
+//!         cluster.release_all(
+//!             &mut anonymizer.publisher,
+//!             &mut anonymizer.analysers,
+//!             &anonymizer.sensitive_freq,
+//!         )
 //! });
 //! ```
+//! Parameters that are not set use the defaults of the `MicroaggAnonymizerBuilder`, `k_max` defaults to 4k and
+//! `buffer_size` to 3k. `build` returns `DiffPrivError::InvalidParameter` when for example `k > k_max`,
+//! `buffer_size < k`, `c <= 0` or the `eps` of the noiser isn't positive.
+//!
 //! ## Implementing `Anonymizable` trait to anonymize new data
 //! By implementing the `Anonymizable` trait on any type of datastructure, DiffPriv will know how to anonymize it.
 //! The following QIs types are implemented
@@ -209,12 +226,397 @@
 //! }
 //! ```
 //!
+//! ## Deriving `Anonymizable`
+//! Instead of implementing the trait by hand it can be derived by annotating the fields of the struct.
+//! The QIs are extracted and updated in the order the fields are declared, so the order of popping
+//! the QIs can't be mixed up.
+//! ```
+//! use std::time::SystemTime;
+//! use serde::{Serialize, Deserialize};
+//!
+//! use diff_priv::data_manipulation::anonymizable::Anonymizable;
+//!
+//! #[derive(Debug, Serialize, Clone, Deserialize, Anonymizable)]
+//! pub struct Patient {
+//!     // interval QI, i32 fields become integer intervals and f64 fields float intervals
+//!     #[qi(interval, min = 1, max = 100, weight = 1)]
+//!     age: i32,
+//!     // ordinal QI, the rank of a category is its position in the list starting at 1
+//!     #[qi(ordinal, categories = ["low", "medium", "high"])]
+//!     income: String,
+//!     // nominal QI, the value of a category is its position in the list starting at 0
+//!     #[qi(nominal, categories = ["male", "female"])]
+//!     gender: String,
+//!     #[sensitive]
+//!     diagnosis: String,
+//!     #[arrival_time]
+//!     #[serde(skip_deserializing, default = "SystemTime::now")]
+//!     time_generated: SystemTime,
+//! }
+//!
+//! impl Default for Patient {
+//!     fn default() -> Self {
+//!         Self {
+//!             age: 0,
+//!             income: "low".to_string(),
+//!             gender: "male".to_string(),
+//!             diagnosis: "".to_string(),
+//!             time_generated: SystemTime::now(),
+//!         }
+//!     }
+//! }
+//!
+//! // the generated code only refers to `diff_priv`, which re-exports the `uuid` crate
+//! let values = Patient::default().extract_string_values(diff_priv::uuid::Uuid::nil(), 0.5);
+//! assert_eq!(values[..3], ["00000000-0000-0000-0000-000000000000", "0.5", "0"]);
+//! ```
+//!
+//! A struct can have several `#[sensitive]` fields, e.g. a diagnosis and a medication. Clusters enforce
+//! (c,l)-diversity for each of them and a tuple is only published through the centroid when every sensitive
+//! attribute is diverse enough. Hand written implementations return them with `sensitive_values()`.
+//!
+//! ## Numeric sensitive attributes and t-closeness
+//! A numeric sensitive attribute like a salary would give every tuple its own class, so `SensitiveAttribute::Float`
+//! groups values into buckets of a given width, e.g. `#[sensitive(bucket_width = 1000)]` when deriving or
+//! `bucket_width = 1000` on a sensitive column of a `DynamicSchema`. Values in the same bucket are equal for the
+//! diversity checks.
+//!
+//! On top of (c,l)-diversity, `MicroaggAnonymizer::with_t_closeness(t)` only publishes the centroid of a cluster when
+//! the distribution of every sensitive attribute inside the cluster is close to its running global distribution.
+//! The distance is the Earth Mover's Distance, using the ordered distance for integers and float buckets and the
+//! equal distance for strings. When the distance is larger than `t` the tuple is suppressed.
+//!
+//! ## Generalization hierarchies
+//! Nominal QIs can be given a value generalization [Hierarchy](data_manipulation::hierarchy::Hierarchy)
+//! (e.g. country -> region -> continent) with `#[qi(nominal, hierarchy = COUNTRIES)]`. The distance between
+//! 2 values is then based on the height of their lowest common ancestor in the hierarchy instead of being 0 or 1.
+//! By default the centroid of a cluster contains the most common value, calling `with_ancestor_centroid()`
+//! on the hierarchy publishes the lowest common ancestor of the values in the cluster instead.
+//! ```
+//! use std::sync::Arc;
+//! use diff_priv::data_manipulation::hierarchy::Hierarchy;
+//!
+//! lazy_static::lazy_static! {
+//!     static ref COUNTRIES: Arc<Hierarchy> = Arc::new(
+//!         Hierarchy::from_paths(vec![
+//!             vec!["Europe", "Western-Europe", "Germany"],
+//!             vec!["Europe", "Eastern-Europe", "Poland"],
+//!             vec!["Asia", "East-Asia", "Japan"],
+//!         ])
+//!         .with_ancestor_centroid()
+//!     );
+//! }
+//! ```
+//!
+//! ## Temporal quasi identifiers
+//! A timestamp QI like the start of an activity is a `QuasiIdentifierTypes::Temporal` containing the timestamp in
+//! seconds since the unix epoch, the [Granularity](data_manipulation::temporal::Granularity) it is published with,
+//! its [TemporalDomain](data_manipulation::temporal::TemporalDomain) and its weight. The distance between 2 timestamps
+//! is their difference relative to the span of the domain. The centroid of a cluster is the mean, or the median when
+//! the domain uses `with_median_centroid()`, truncated to the first granularity of the ladder
+//! second -> minute -> hour -> day -> week covering the timestamps in the cluster. A cluster of tuples that started
+//! within the same hour is for example published with the hour they started in.
+//!
+//! When deriving, `String` datetimes (`YYYY-MM-DD HH:MM:SS`), `SystemTime` and `i64` fields can be temporal QIs:
+//! ```
+//! use std::time::SystemTime;
+//! use serde::Serialize;
+//!
+//! use diff_priv::data_manipulation::anonymizable::Anonymizable;
+//!
+//! #[derive(Debug, Serialize, Clone, Anonymizable)]
+//! pub struct Activity {
+//!     // published with at least minute granularity, using the median of a cluster
+//!     #[qi(temporal, min = "2022-06-20", max = "2022-06-27", granularity = "minute", median)]
+//!     start: String,
+//!     // boundaries in seconds since the unix epoch
+//!     #[qi(temporal, min = 1655683200, max = 1656288000)]
+//!     end: i64,
+//!     #[sensitive]
+//!     activity: String,
+//!     #[arrival_time]
+//!     time_generated: SystemTime,
+//! }
+//!
+//! impl Default for Activity {
+//!     fn default() -> Self {
+//!         Self {
+//!             start: "2022-06-20 00:00:00".to_string(),
+//!             end: 1655683200,
+//!             activity: "".to_string(),
+//!             time_generated: SystemTime::now(),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ## Geo point quasi identifiers
+//! GPS coordinates are a `QuasiIdentifierTypes::GeoPoint` instead of 2 independent interval QIs, which would get
+//! the distance and the centroid wrong near the antimeridian. It contains a [GeoPoint](data_manipulation::geo::GeoPoint)
+//! in degrees, a [GeoDomain](data_manipulation::geo::GeoDomain) and its weight. The distance between 2 points is the
+//! haversine distance, or the cheaper equirectangular approximation, relative to the `max_distance` of the domain.
+//! The centroid of a cluster is the spherical centroid of its points (`AggregateType::SphericalCentroid`).
+//!
+//! Noise is added with the planar laplace mechanism of geo-indistinguishability: a point is moved in a random direction
+//! over a random distance, so points inside the `privacy_radius` of the domain are indistinguishable.
+//! When deriving, a `GeoPoint` field is annotated with `#[qi(geo)]`:
+//! ```
+//! use std::time::SystemTime;
+//! use serde::Serialize;
+//!
+//! use diff_priv::data_manipulation::anonymizable::Anonymizable;
+//! use diff_priv::data_manipulation::geo::GeoPoint;
+//!
+//! #[derive(Debug, Serialize, Clone, Anonymizable)]
+//! pub struct Trip {
+//!     // distances are normalized by 50 km, points within 500 m are indistinguishable
+//!     #[qi(geo, max_distance = 50, privacy_radius = 0.5)]
+//!     pickup: GeoPoint,
+//!     // approximate distances with the equirectangular projection
+//!     #[qi(geo, equirectangular)]
+//!     dropoff: GeoPoint,
+//!     #[sensitive]
+//!     fare: i32,
+//!     #[arrival_time]
+//!     time_generated: SystemTime,
+//! }
+//!
+//! impl Default for Trip {
+//!     fn default() -> Self {
+//!         Self {
+//!             pickup: GeoPoint::new(52.37, 4.9),
+//!             dropoff: GeoPoint::new(52.31, 4.76),
+//!             fare: 0,
+//!             time_generated: SystemTime::now(),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ## Centroid aggregation
+//! By default the centroid of a cluster uses the mean of interval and temporal QI's, the mode of categorical QI's
+//! and the spherical centroid of geo points. `aggregators()` can choose another `Aggregator` for every QI,
+//! in the order of `quasi_identifiers()`:
+//!
+//! | Aggregator | QI's | Centroid |
+//! |---|---|---|
+//! | `Mean` | interval, temporal | mean of the values |
+//! | `Median` | interval, temporal, ordinal | middle value, the lower one for an even amount of values |
+//! | `TrimmedMean(p)` | interval, temporal | mean without the proportion `p` of the lowest and highest values |
+//! | `WeightedMean` | interval, temporal | mean weighted by the `record_weight()` of the tuples |
+//! | `Mode` | ordinal, nominal, hierarchical | most frequent value |
+//!
+//! Aggregated integer values are truncated unless `rounding()` returns another `Rounding`.
+//! When deriving, the aggregator is set on the field and the rounding on the struct:
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use serde::Serialize;
+//!
+//! use diff_priv::data_manipulation::anonymizable::Anonymizable;
+//!
+//! #[derive(Debug, Serialize, Clone, Anonymizable)]
+//! #[rounding(nearest)]
+//! struct Adult {
+//!     // skewed values get a more sensible centroid from the median
+//!     #[qi(interval, min = 0, max = 100000, median)]
+//!     capital_gain: i32,
+//!     #[qi(interval, min = 17, max = 90, trimmed_mean = 0.1)]
+//!     age: i32,
+//!     #[qi(interval, min = 1, max = 99, weighted_mean)]
+//!     hours_per_week: i32,
+//!     #[record_weight]
+//!     fnlwgt: i32,
+//!     #[sensitive]
+//!     occupation: String,
+//!     #[arrival_time]
+//!     time_generated: SystemTime,
+//! }
+//!
+//! impl Default for Adult {
+//!     fn default() -> Self {
+//!         Self {
+//!             capital_gain: 0,
+//!             age: 17,
+//!             hours_per_week: 40,
+//!             fnlwgt: 1,
+//!             occupation: "Sales".to_string(),
+//!             time_generated: SystemTime::now(),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Centroids are kept up to date with running aggregates of the buffered tuples: sums for the means, frequencies for the
+//! mode, the two halves of the values for the median and the trimmed ends of the values for the trimmed mean, so adding
+//! a tuple doesn't aggregate the whole buffer again. With `MissingValuePolicy::Impute` the missing values are replaced by
+//! the centroid, which changes with every tuple, so these centroids still aggregate the whole buffer for every tuple.
+//!
+//! ## Missing values
+//! A QI whose value is absent is represented by `QuasiIdentifierTypes::Missing`, which contains a template
+//! of the QI with its domain and weight. The derive macro extracts `None` of an `Option` field as a missing QI.
+//! How missing values are handled is decided by the `MissingValuePolicy` returned by `missing_value_policy()`
+//! (or set with `#[missing_values(...)]` when deriving):
+//!
+//! | Policy | Distance | Centroid | Concept drift |
+//! |---|---|---|---|
+//! | `Ignore` (default) | the QI is left out | left out of the aggregation | left out of the KS test |
+//! | `Impute` | distance 0 to the centroid | replaced by the value of the centroid | replaced by the value of the centroid |
+//! | `OwnCategory` | maximum distance to present values | counts as a category for the mode, left out of the mean | left out of the KS test |
+//!
+//! Missing values are never noised, a centroid only contains a missing value when no value is present.
+//!
+//! ## Distance metrics
+//! The distance between a tuple and a cluster centroid decides which cluster the tuple joins, and is also used
+//! for concept drift and by the analysers. Every QI contributes a weighted difference normalized to its domain,
+//! which the `DistanceMetric` set with `with_distance_metric` combines into a single distance:
+//!
+//! | Metric | Distance |
+//! |---|---|
+//! | `Gower` (default) | weighted mean of the QI differences |
+//! | `Manhattan` | sum of the weighted QI differences |
+//! | `NormalizedEuclidean` | root of the weighted mean of the squared QI differences |
+//! | `Mahalanobis` | scales the numerical QI's by an online estimate of their covariance |
+//!
+//! `Mahalanobis` keeps learning the covariance from every tuple that is anonymized, so correlated or
+//! differently spread QI's don't dominate the clustering. The covariance is inverted for each of the first 64 tuples
+//! and then once every 64 tuples. Custom metrics can implement the `DistanceMetric` trait.
+//!
+//! The nearest cluster is looked up in a k-d tree over the QI's of the centroids except geo points, which
+//! skips the clusters that are certainly further away than `diff_thres`. It needs a lower bound of the distance from
+//! `DistanceMetric::lower_bound`, which `Gower`, `Manhattan` and `NormalizedEuclidean` give and announce with
+//! `supports_lower_bound`. Metrics without one, like `Mahalanobis`, compare every cluster with the tuple.
+//! `cargo bench` compares both on the `AdultLarge` dataset.
+//!
+//! The QI's of a tuple are extracted only once when it enters the anonymizer, into a row of numbers that shares its
+//! domains and weights with the other tuples. Finding the cluster, updating the centroid and the analysers all work on
+//! that row, so `quasi_identifiers` isn't called for every comparison. `QiColumns::extract` gives the same row for a tuple.
+//!
+//! ## Handling bad records
+//! Instead of panicking on a record that can't be anonymized, e.g. a category that isn't part of the QI's domain
+//! or QI's that don't match the records before, `try_anonymize` returns a `DiffPrivError`. What happens with such a
+//! record is decided by the `RecordErrorPolicy` set with `with_record_error_policy`:
+//!
+//! | Policy | Behaviour |
+//! |---|---|
+//! | `Skip` | the record is logged and dropped |
+//! | `DeadLetter` | the record and its error are kept in `dead_letters` |
+//! | `Abort` (default) | the error is returned, `anonymize` panics with it |
+//!
+//! Implementations of `Anonymizable` can report bad input by overriding `try_quasi_identifiers` and
+//! `try_update_quasi_identifiers`, the derive macro does this automatically.
+//! The Kafka service uses the `record_error_policy` of the `application.conf` for records it can't decode as well,
+//! and sends dead letters to the `dead_letter_topic`. When a dead letter can't be sent the service stops with
+//! `DiffPrivError::Publish` before the offset of the record is committed.
+//!
+//! ## Generalized output
+//! Besides publishing every tuple with the noisy centroid of its cluster, the anonymizer can publish classic
+//! k-anonymous generalizations with `with_output_mode`. In `OutputMode::Generalization` every tuple is published as a
+//! `GeneralizedRecord` through `Publisher::publish_generalized`, where each QI is replaced by the range of its values
+//! inside the cluster, or the set of values for nominal QI's. QI's of clusters that don't satisfy l-diversity or
+//! t-closeness are `Suppressed`. `OutputMode::Both` publishes the microaggregated tuple as well.
+//! The ranges are not noised, so these records are k-anonymous but not ε-differentially private.
+//! The Kafka service sends the generalized records as JSON to the `generalized_topic`.
+//!
+//! ## Suppression strategies
+//! Tuples of a cluster that doesn't satisfy l-diversity or t-closeness can't be published with the centroid.
+//! What happens with them is set with `with_suppression_strategy`:
+//!
+//! | Strategy | Behaviour |
+//! |---|---|
+//! | `Drop` | the tuple is not published |
+//! | `Midpoint` | every QI is replaced by the midpoint of its domain |
+//! | `Wildcard` | every QI is replaced by a missing value, the midpoint for fields that can't be missing |
+//! | `FullDomain` | generalized records contain the whole domain of every QI, microaggregated tuples the midpoint |
+//! | `UniformSample` (default) | every QI is sampled uniformly from its domain, independent of the original value |
+//! | `Hold` | the tuple stays in the cluster until it is diverse, it is dropped when the cluster is removed |
+//!
+//! Clusters removed at the end of a stream should be emptied with `release_all` instead of `publish_all`, so tuples
+//! that are still held are counted as dropped. The `SuppressionAnalyser` counts the published, suppressed and held tuples.
+//!
+//! ## Event time
+//! Clusters without new tuples for `delta` seconds are published and replaced, and the `PublishingDelayAnalyser` measures
+//! how long tuples wait. By default both use the wall clock, so replaying historical data faster than it happened never
+//! exceeds `delta`. With `with_time_mode(TimeMode::EventTime)` they use `Anonymizable::event_time` instead, the latest
+//! event time seen is the current time of the anonymizer. Replays are then deterministic and behave like the original
+//! stream. The derive macro reads the event time from the field annotated with `#[event_time]`, without it the arrival
+//! time is used.
+//!
+//! Clusters are checked for their life time when a tuple is added to them, so a cluster that stops receiving tuples would
+//! keep them forever. `flush_expired` publishes or suppresses the tuples of every expired cluster and removes it, `tick(now)`
+//! first moves the event time clock to `now` for quiet streams. The Kafka service flushes between polls every
+//! `flush_interval` seconds, so a cluster is flushed at most `flush_interval` plus the duration of one poll after it expires.
+//!
+//! ## Reproducible runs
+//! The noise, the values of suppressed tuples and the cluster uuids are random. Every new cluster gets its own seed from
+//! the random number generator of the anonymizer, by default a `ChaCha12Rng` CSPRNG seeded from the OS. With `with_seed` or the
+//! builder's `seed` two runs over the same tuples in the same order publish the same output, a custom generator is set with
+//! `with_rng`. Clusters expire on the wall clock in processing time, so replays are only byte-identical when no cluster
+//! reaches `delta` or with `TimeMode::EventTime`. `LaplaceNoiser::with_rng` and `with_seed` draw the noise of a single
+//! noiser from another generator, custom noisers receive the seed of their cluster in `Noiser::reseed`.
+//!
+//! ## Checkpoints
+//! `snapshot` writes the clusters with their buffers, the sensitive frequencies, the history windows of the noiser and the
+//! clock of the anonymizer to a file with bincode, `restore` reads them back into an anonymizer created with the same
+//! parameters. Restarts then keep the unpublished tuples and the calibration of the noise. The publisher, the distance
+//! metric and the analysers are not part of a snapshot, the analysers start counting again. The Kafka service restores
+//! the `checkpoint_path` on startup and writes a checkpoint every `checkpoint_interval` seconds, the consumer offsets are
+//! only committed after a checkpoint so messages anonymized after the last one are anonymized again after a crash.
+//!
+//! ## Bounded memory
+//! Every distinct group of tuples gets its own cluster, a stream with many small groups keeps growing the cluster set.
+//! `with_max_clusters` and `with_memory_budget`, or the builder's `max_clusters` and `memory_budget`, bound the number of
+//! clusters and their estimated size in bytes. Above a bound a cluster is evicted by the `EvictionPolicy`: the
+//! `LeastRecentlyUsed` cluster by its last arrival, the `Smallest` or the `Oldest` cluster. An evicted cluster with at least
+//! k tuples publishes its remaining tuples, the tuples of a smaller cluster are suppressed or with `with_merge_evicted` added
+//! to the nearest cluster. The evictions are counted as `evicted` in the cluster report.
+//!
+//! ## Batches
+//! Backfills can pass all their tuples at once to `anonymize_batch`, or `try_anonymize_batch` which returns the error of a
+//! bad record like `try_anonymize`. The tuples of a batch are validated and their nearest clusters looked up in parallel,
+//! then they are added to their clusters one by one in their order. Every tuple is also compared with the clusters that
+//! changed since the lookup, or looked up again when its nearest cluster changed, so the clusters and published tuples are
+//! the same as with `anonymize` for every tuple. Metrics that learn from the stream, like `Mahalanobis`, look up the clusters one by one.
+//! `cargo bench --bench batch` compares the throughput of both on the `AdultLarge` dataset.
+//!
+//! ## Sharding
+//! A single `MicroaggAnonymizer` runs on a single thread. A `ShardedAnonymizer` divides the stream over a thread for every
+//! shard, each running its own anonymizer that a closure creates for the number of the shard. `Partition::by_key` sends
+//! the tuples with the same key, e.g. the id of a user, to the same shard and `Partition::Grid` divides the domain of every
+//! QI into cells and sends the tuples of a cell to the same shard, so similar tuples are still clustered together. Share a
+//! `SharedPublisher` between the anonymizers to publish every tuple to the same publisher. `finish` waits until the shards
+//! are done, publishes or suppresses the tuples left in their clusters with `flush_all` and returns the reports of their
+//! analysers merged with `Analyser::merge`.
+//!
+//! The guarantees of the shards compose as follows:
+//! - k-anonymity and l-diversity are properties of a single cluster and a cluster only contains tuples of one shard, so
+//!   every published cluster has at least k tuples and l different sensitive values, like with a single anonymizer.
+//!   Clusters of different shards with the same published QI's only make the group of tuples larger.
+//! - A shard doesn't see the tuples of the other shards, a group of similar tuples divided over shards can be suppressed or
+//!   wait longer where a single anonymizer would have published it. The grid keeps similar tuples on the same shard.
+//! - t-closeness compares a cluster with the distribution of the sensitive attributes of its own shard instead of the
+//!   whole stream.
+//! - Every tuple is anonymized by exactly one shard, so the noise of the shards covers disjoint tuples and the ε of the
+//!   shards doesn't add up.
+//! - The tuples of a shard are anonymized in their order, the tuples of different shards are published in any order.
+//!
 //! # The `Publisher` trait
 //! To publish an anonymized struct to a desired backend we use the `Publisher` trait.
 //! DiffPriv also support exporting to an [Apache Kafka topic](publishing::kafka_publisher::KafkaPublisher). This can be seen in `publishing` directory.
 //! An example publisher for CSVs can be seen here: [CsvPublisher](publishing::csv_publisher::CsvPublisher).
 //! To implement a custom publishing backend one can use the [Publisher](publishing::publisher::Publisher) trait.
 //!
+//! # The `Analyser` trait
+//! The anonymizer measures itself with analysers, by default the MSE, SSE, publishing delay, cluster counts,
+//! disclosure risk and suppression counts are kept. Own measurements are added by implementing the
+//! [Analyser](analysis::analyser::Analyser) trait and registering it with `with_analyser`. Its hooks are called when a tuple
+//! is published or suppressed, tuples are held, clusters are created or removed and when concept drift is detected.
+//! `on_publish` receives a `PublishContext` with the cluster and the distance metric, the `DisclosureRiskAnalyser`
+//! sets the disclosure risk that is published together with the tuple. `MicroaggAnonymizer::report` collects the
+//! `report` of every analyser by its `name` as JSON.
+//! An analyser that returns its measurements from `state` is merged over the shards of a `ShardedAnonymizer` with
+//! `merge`, the reports of other analysers are listed for every shard.
+//!
 //! # The `Noiser` trait
 //! DiffPriv support [Laplace noise](noise::laplace::laplace_noiser::LaplaceNoiser) for ε-differential privacy.
 //! The noiser supports 2 different kind of noise: one for [numerical values](noise::laplace::numerical_noiser::NumericalNoiser) and one for [categorical](noise::laplace::categorical_noiser::CategoricalNoiser).
@@ -229,13 +631,19 @@ extern crate lazy_static;
 
 extern crate core;
 extern crate pretty_env_logger;
+// makes the paths generated by the `Anonymizable` derive macro resolve inside this crate
+extern crate self as diff_priv;
 
 pub mod analysis;
 pub mod anonymization;
 pub mod config;
 pub mod data_manipulation;
+pub mod error;
 pub mod kafka;
 pub mod noise;
 pub mod publishing;
 pub mod test;
 pub mod vec_set;
+
+// used by the code generated by the `Anonymizable` derive macro, so deriving crates don't need it
+pub use uuid;
@@ -1,11 +1,11 @@
 use crate::noise::laplace::laplace_noiser::CategoricalTypes;
 use crate::vec_set::VecSet;
 use rand::distributions::Distribution;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use rand_distr::Normal;
 
 /// Noiser for categorical QI types
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CategoricalNoiser {
     observed_values: VecSet<i32>, // hashset containing all observed values of the QI
     noise_thr: f64,               // categorical noise threshold
@@ -26,20 +26,24 @@ impl CategoricalNoiser {
         match *categorical {
             CategoricalTypes::Ordinal((rank, _, _)) => rank,
             CategoricalTypes::Nominal((value, _, _)) => value,
+            CategoricalTypes::Hierarchical((value, _, _)) => value,
         }
     }
 
     /// generate noise for categorical QI types. Return the noise to be used
     /// instead of the original
-    pub fn generate_noise(&mut self, categorical: CategoricalTypes) -> i32 {
+    pub fn generate_noise<R: Rng + ?Sized>(
+        &mut self,
+        categorical: CategoricalTypes,
+        random: &mut R,
+    ) -> i32 {
         let value = Self::extract_value(&categorical);
         self.observed_values.insert(value);
 
-        let mut random = thread_rng();
         let normal: Normal<f64> = Normal::new(0.0, 1.0).unwrap();
-        let e = normal.sample(&mut random);
+        let e = normal.sample(random);
 
-        match self.observed_values.len() > 1 && e < self.noise_thr * self.stream_weight as f64 {
+        match self.observed_values.len() > 1 && e < self.noise_thr * self.stream_weight {
             true => {
                 let mut index = random.gen_range(0..self.observed_values.len());
                 while *self.observed_values.get(index).unwrap() == value {
@@ -1,19 +1,28 @@
+use std::collections::HashMap;
+
 use crate::data_manipulation::aggregation::truncate_to_domain;
 use crate::data_manipulation::anonymizable::{
-    Anonymizable, IntervalType, NominalType, OrdinalType, QuasiIdentifierType, QuasiIdentifierTypes,
+    Anonymizable, GeoPointType, HierarchicalType, IntervalType, NominalType, OrdinalType,
+    QuasiIdentifierType, QuasiIdentifierTypes, TemporalType,
 };
+use crate::error::DiffPrivError;
 use crate::noise::laplace::categorical_noiser::CategoricalNoiser;
 use crate::noise::laplace::numerical_noiser::NumericalNoiser;
+use crate::noise::laplace::planar_noiser::PlanarNoiser;
 use crate::noise::noiser::Noiser;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 
 /// location of laplace distribution (mu)
 pub const LOC: f64 = 0.0;
 
 /// possible noiser categories for the laplace noiser
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 enum NoiserCategories {
     NumericalNoiser(NumericalNoiser),
     CategoricalNoiser(CategoricalNoiser),
+    PlanarNoiser(PlanarNoiser),
 }
 
 /// QI types that support categorical noise
@@ -21,16 +30,18 @@ enum NoiserCategories {
 pub enum CategoricalTypes {
     Nominal(NominalType),
     Ordinal(OrdinalType),
+    Hierarchical(HierarchicalType),
 }
 
 /// The laplace noice noiser used for introducing random noise to make the QI's
-/// differentially private
-#[derive(Default, Clone)]
-pub struct LaplaceNoiser {
-    eps: f64,                          // differential privacy parameter
-    k: usize,                          // k anonymity level
-    noise_thr: f64,                    // categorical noise threshold
-    qi_noisers: Vec<NoiserCategories>, // vector containing all the different noisers for the QI's
+/// differentially private. The noise is drawn from `R`, by default the `ChaCha12Rng` CSPRNG seeded from the OS
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LaplaceNoiser<R = ChaCha12Rng> {
+    eps: f64,                                     // differential privacy parameter
+    k: usize,                                     // k anonymity level
+    noise_thr: f64,                               // categorical noise threshold
+    qi_noisers: HashMap<usize, NoiserCategories>, // the different noisers for the QI's by their index
+    rng: R,                                       // source of all the noise
 }
 
 impl LaplaceNoiser {
@@ -42,86 +53,227 @@ impl LaplaceNoiser {
             ..Default::default()
         }
     }
+}
 
-    /// generate and add noise to an interval QI type
-    fn generate_noise_interval(
+impl<R> LaplaceNoiser<R>
+where
+    R: RngCore + SeedableRng + Clone + Sync,
+{
+    /// draw the noise from the given random number generator
+    pub fn with_rng<S>(self, rng: S) -> LaplaceNoiser<S>
+    where
+        S: RngCore + SeedableRng + Clone + Sync,
+    {
+        LaplaceNoiser {
+            eps: self.eps,
+            k: self.k,
+            noise_thr: self.noise_thr,
+            qi_noisers: self.qi_noisers,
+            rng,
+        }
+    }
+
+    /// seed the random number generator to make the noise reproducible
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.reseed(seed);
+        self
+    }
+
+    /// generate noise for a numerical value with the numerical noiser of the QI
+    fn generate_numerical_noise(
         &mut self,
-        interval: IntervalType,
+        interval: &IntervalType,
         qi_len: usize,
         index: usize,
-    ) -> QuasiIdentifierTypes {
-        match self.qi_noisers.get_mut(index) {
+    ) -> Result<f64, DiffPrivError> {
+        match self.qi_noisers.get_mut(&index) {
             None => {
                 let mut noiser =
-                    NumericalNoiser::initialize(self.eps, self.k, qi_len as f64, &interval);
-                let noise = noiser.generate_noise(&interval);
+                    NumericalNoiser::initialize(self.eps, self.k, qi_len as f64, interval);
+                let noise = noiser.generate_noise(interval, &mut self.rng);
                 self.qi_noisers
-                    .push(NoiserCategories::NumericalNoiser(noiser));
-                QuasiIdentifierTypes::Interval(self.add_noise_interval(noise, interval))
+                    .insert(index, NoiserCategories::NumericalNoiser(noiser));
+                Ok(noise)
             }
             Some(category) => match category {
                 NoiserCategories::NumericalNoiser(noiser) => {
-                    let noise = noiser.generate_noise(&interval);
-                    QuasiIdentifierTypes::Interval(self.add_noise_interval(noise, interval))
+                    Ok(noiser.generate_noise(interval, &mut self.rng))
                 }
-                _ => panic!("wrong noiser type detected"),
+                _ => Err(wrong_noiser(index)),
             },
         }
     }
 
+    /// generate and add noise to an interval QI type
+    fn generate_noise_interval(
+        &mut self,
+        interval: IntervalType,
+        qi_len: usize,
+        index: usize,
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        let noise = self.generate_numerical_noise(&interval, qi_len, index)?;
+        Ok(QuasiIdentifierTypes::Interval(
+            self.add_noise_interval(noise, interval)?,
+        ))
+    }
+
+    /// generate and add noise to a temporal QI type, the noised timestamp
+    /// is truncated to its domain and granularity
+    fn generate_noise_temporal(
+        &mut self,
+        temporal: TemporalType,
+        qi_len: usize,
+        index: usize,
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        let (timestamp, granularity, domain, weight) = temporal;
+        let interval = (
+            QuasiIdentifierType::Float(timestamp as f64),
+            QuasiIdentifierType::Float(domain.min as f64),
+            QuasiIdentifierType::Float(domain.max as f64),
+            weight,
+        );
+        let noise = self.generate_numerical_noise(&interval, qi_len, index)?;
+        let noised = truncate_to_domain(timestamp + noise.round() as i64, domain.min, domain.max);
+
+        Ok(QuasiIdentifierTypes::Temporal((
+            granularity.truncate(noised),
+            granularity,
+            domain,
+            weight,
+        )))
+    }
+
+    /// generate and add planar laplace noise to a geo point QI type
+    fn generate_noise_geo_point(
+        &mut self,
+        geo_point: GeoPointType,
+        qi_len: usize,
+        index: usize,
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        let (point, domain, weight) = geo_point;
+        let noiser = self.qi_noisers.entry(index).or_insert_with(|| {
+            NoiserCategories::PlanarNoiser(PlanarNoiser::initialize(
+                self.eps,
+                self.k,
+                qi_len as f64,
+                &domain,
+            ))
+        });
+
+        match noiser {
+            NoiserCategories::PlanarNoiser(noiser) => Ok(QuasiIdentifierTypes::GeoPoint((
+                noiser.generate_noise(&point, &mut self.rng),
+                domain,
+                weight,
+            ))),
+            _ => Err(wrong_noiser(index)),
+        }
+    }
+
     /// generate and add noise to an ordinal QI type
     fn generate_noise_ordinal(
         &mut self,
         ordinal: OrdinalType,
         stream_weight: usize,
         index: usize,
-    ) -> QuasiIdentifierTypes {
-        match self.qi_noisers.get_mut(index) {
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        match self.qi_noisers.get_mut(&index) {
             None => {
                 let mut noiser = CategoricalNoiser::initialize(self.noise_thr, stream_weight);
-                let noise = noiser.generate_noise(CategoricalTypes::Ordinal(ordinal));
+                let noise =
+                    noiser.generate_noise(CategoricalTypes::Ordinal(ordinal), &mut self.rng);
                 self.qi_noisers
-                    .push(NoiserCategories::CategoricalNoiser(noiser));
-                QuasiIdentifierTypes::Ordinal(self.add_noise_ordinal(noise, ordinal))
+                    .insert(index, NoiserCategories::CategoricalNoiser(noiser));
+                Ok(QuasiIdentifierTypes::Ordinal(
+                    self.add_noise_ordinal(noise, ordinal),
+                ))
             }
             Some(category) => match category {
                 NoiserCategories::CategoricalNoiser(noiser) => {
-                    let noise = noiser.generate_noise(CategoricalTypes::Ordinal(ordinal));
-                    QuasiIdentifierTypes::Ordinal(self.add_noise_ordinal(noise, ordinal))
+                    let noise =
+                        noiser.generate_noise(CategoricalTypes::Ordinal(ordinal), &mut self.rng);
+                    Ok(QuasiIdentifierTypes::Ordinal(
+                        self.add_noise_ordinal(noise, ordinal),
+                    ))
                 }
-                _ => panic!("wrong noiser type detected"),
+                _ => Err(wrong_noiser(index)),
             },
         }
     }
 
-    /// generate and add noise to an ordinal QI type
+    /// generate and add noise to a nominal QI type
     fn generate_noise_nominal(
         &mut self,
         nominal: NominalType,
         stream_weight: usize,
         index: usize,
-    ) -> QuasiIdentifierTypes {
-        match self.qi_noisers.get_mut(index) {
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        match self.qi_noisers.get_mut(&index) {
             None => {
                 let mut noiser = CategoricalNoiser::initialize(self.noise_thr, stream_weight);
-                let noise = noiser.generate_noise(CategoricalTypes::Nominal(nominal));
+                let noise =
+                    noiser.generate_noise(CategoricalTypes::Nominal(nominal), &mut self.rng);
                 self.qi_noisers
-                    .push(NoiserCategories::CategoricalNoiser(noiser));
-                QuasiIdentifierTypes::Nominal(self.add_noise_nominal(noise, nominal))
+                    .insert(index, NoiserCategories::CategoricalNoiser(noiser));
+                Ok(QuasiIdentifierTypes::Nominal(
+                    self.add_noise_nominal(noise, nominal),
+                ))
             }
             Some(categorical) => match categorical {
                 NoiserCategories::CategoricalNoiser(noiser) => {
-                    let noise = noiser.generate_noise(CategoricalTypes::Nominal(nominal));
-                    QuasiIdentifierTypes::Nominal(self.add_noise_nominal(noise, nominal))
+                    let noise =
+                        noiser.generate_noise(CategoricalTypes::Nominal(nominal), &mut self.rng);
+                    Ok(QuasiIdentifierTypes::Nominal(
+                        self.add_noise_nominal(noise, nominal),
+                    ))
                 }
-                _ => panic!("wrong noiser type detected"),
+                _ => Err(wrong_noiser(index)),
+            },
+        }
+    }
+
+    /// generate and add noise to a hierarchical QI type
+    fn generate_noise_hierarchical(
+        &mut self,
+        hierarchical: HierarchicalType,
+        stream_weight: usize,
+        index: usize,
+    ) -> Result<QuasiIdentifierTypes, DiffPrivError> {
+        match self.qi_noisers.get_mut(&index) {
+            None => {
+                let mut noiser = CategoricalNoiser::initialize(self.noise_thr, stream_weight);
+                let noise = noiser.generate_noise(
+                    CategoricalTypes::Hierarchical(hierarchical.clone()),
+                    &mut self.rng,
+                );
+                self.qi_noisers
+                    .insert(index, NoiserCategories::CategoricalNoiser(noiser));
+                Ok(QuasiIdentifierTypes::Hierarchical(
+                    self.add_noise_hierarchical(noise, hierarchical),
+                ))
+            }
+            Some(categorical) => match categorical {
+                NoiserCategories::CategoricalNoiser(noiser) => {
+                    let noise = noiser.generate_noise(
+                        CategoricalTypes::Hierarchical(hierarchical.clone()),
+                        &mut self.rng,
+                    );
+                    Ok(QuasiIdentifierTypes::Hierarchical(
+                        self.add_noise_hierarchical(noise, hierarchical),
+                    ))
+                }
+                _ => Err(wrong_noiser(index)),
             },
         }
     }
 
     /// add noise to a interval QI type value
-    pub fn add_noise_interval(&self, noise: f64, interval: IntervalType) -> IntervalType {
-        match interval {
+    pub fn add_noise_interval(
+        &self,
+        noise: f64,
+        interval: IntervalType,
+    ) -> Result<IntervalType, DiffPrivError> {
+        let noised = match interval {
             (
                 QuasiIdentifierType::Float(value),
                 QuasiIdentifierType::Float(min_value),
@@ -149,9 +301,13 @@ impl LaplaceNoiser {
                 weight,
             ),
             _ => {
-                panic!("Wrong typing combination when adding noise to interval value")
+                return Err(DiffPrivError::TypeMismatch(
+                    "interval QI mixes integer and float values".to_string(),
+                ))
             }
-        }
+        };
+
+        Ok(noised)
     }
 
     /// add generated noise to a nominal QI value
@@ -166,21 +322,71 @@ impl LaplaceNoiser {
         (noise, max_rank, weight)
     }
 
+    /// add generated noise to a hierarchical QI value
+    fn add_noise_hierarchical(
+        &self,
+        noise: i32,
+        hierarchical: HierarchicalType,
+    ) -> HierarchicalType {
+        let (_, hierarchy, weight) = hierarchical;
+        (noise, hierarchy, weight)
+    }
+
     /// calculate the full weight of all the QI's
     fn calculate_stream_weight(&self, qi: &[QuasiIdentifierTypes]) -> usize {
-        qi.iter()
-            .map(|x| match x {
-                QuasiIdentifierTypes::Interval((_, _, _, weight)) => weight,
-                QuasiIdentifierTypes::Ordinal((_, _, weight)) => weight,
-                QuasiIdentifierTypes::Nominal((_, _, weight)) => weight,
-            })
-            .sum()
+        qi.iter().map(QuasiIdentifierTypes::weight).sum()
     }
 }
 
-impl Noiser for LaplaceNoiser {
+impl<R> Default for LaplaceNoiser<R>
+where
+    R: SeedableRng,
+{
+    fn default() -> Self {
+        Self {
+            eps: 0.0,
+            k: 0,
+            noise_thr: 0.0,
+            qi_noisers: HashMap::new(),
+            rng: R::from_entropy(),
+        }
+    }
+}
+
+impl<R> Noiser for LaplaceNoiser<R>
+where
+    R: RngCore + SeedableRng + Clone + Sync,
+{
+    fn reseed(&mut self, seed: u64) {
+        self.rng = R::seed_from_u64(seed)
+    }
+
+    fn validate(&self) -> Result<(), DiffPrivError> {
+        if self.eps.is_nan() || self.eps <= 0.0 {
+            return Err(DiffPrivError::InvalidParameter(format!(
+                "eps must be positive, got {}",
+                self.eps
+            )));
+        }
+        if self.noise_thr.is_nan() || self.noise_thr < 0.0 {
+            return Err(DiffPrivError::InvalidParameter(format!(
+                "noise_thr can't be negative, got {}",
+                self.noise_thr
+            )));
+        }
+        Ok(())
+    }
+
     fn add_noise<M: Anonymizable>(&mut self, value: &M) -> Vec<QuasiIdentifierTypes> {
-        let qi = value.quasi_identifiers();
+        self.try_add_noise(value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_add_noise<M: Anonymizable>(
+        &mut self,
+        value: &M,
+    ) -> Result<Vec<QuasiIdentifierTypes>, DiffPrivError> {
+        let qi = value.try_quasi_identifiers()?;
         let qi_len = qi.len();
         let stream_weight = match self.qi_noisers.is_empty() {
             true => self.calculate_stream_weight(&qi),
@@ -199,7 +405,28 @@ impl Noiser for LaplaceNoiser {
                 QuasiIdentifierTypes::Nominal(nominal) => {
                     self.generate_noise_nominal(nominal, stream_weight, index)
                 }
+                QuasiIdentifierTypes::Hierarchical(hierarchical) => {
+                    self.generate_noise_hierarchical(hierarchical, stream_weight, index)
+                }
+                QuasiIdentifierTypes::Temporal(temporal) => {
+                    self.generate_noise_temporal(temporal, qi_len, index)
+                }
+                QuasiIdentifierTypes::GeoPoint(geo_point) => {
+                    self.generate_noise_geo_point(geo_point, qi_len, index)
+                }
+                // there is no value to protect, a missing value stays missing
+                QuasiIdentifierTypes::Missing(template) => {
+                    Ok(QuasiIdentifierTypes::Missing(template))
+                }
             })
-            .collect::<Vec<QuasiIdentifierTypes>>()
+            .collect::<Result<Vec<QuasiIdentifierTypes>, DiffPrivError>>()
     }
 }
+
+/// error for a QI whose data category changed since its noiser was created
+fn wrong_noiser(index: usize) -> DiffPrivError {
+    DiffPrivError::TypeMismatch(format!(
+        "QI {} doesn't have the same data category as the QI's noised before",
+        index
+    ))
+}
@@ -1,3 +1,4 @@
 pub mod categorical_noiser;
 pub mod laplace_noiser;
 pub mod numerical_noiser;
+pub mod planar_noiser;
@@ -0,0 +1,60 @@
+use crate::data_manipulation::geo::{GeoDomain, GeoPoint};
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use rand_distr::Gamma;
+use std::f64::consts::TAU;
+
+/// Noiser for geo point QI types using the planar laplace mechanism
+/// From: M. Andrés et. al., "Geo-Indistinguishability: Differential Privacy for Location-Based Systems", 2013
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlanarNoiser {
+    eps: f64, // privacy parameter per kilometer
+}
+
+impl PlanarNoiser {
+    /// create a new planar noiser, points inside the privacy radius of the domain are
+    /// eps-indistinguishable. Like the numerical noiser the budget is shared by the QI's and
+    /// a published centroid only moves 1/k of the distance a single tuple moves
+    pub fn initialize(eps: f64, k: usize, qi_amount: f64, domain: &GeoDomain) -> Self {
+        Self {
+            eps: k as f64 * eps / (qi_amount * domain.privacy_radius),
+        }
+    }
+
+    /// move the point in a uniformly random direction over a distance following
+    /// the radial distribution of the planar laplace, which is gamma(2, 1/eps)
+    pub fn generate_noise<R: Rng + ?Sized>(&self, point: &GeoPoint, rng: &mut R) -> GeoPoint {
+        let bearing = Uniform::<f64>::from(0.0..TAU).sample(rng);
+        let gamma: Gamma<f64> = Gamma::new(2.0, 1.0 / self.eps).unwrap();
+        let distance = gamma.sample(rng);
+
+        point.destination(bearing, distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_manipulation::geo::{GeoDomain, GeoPoint};
+    use crate::noise::laplace::planar_noiser::PlanarNoiser;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SAMPLE_SIZE: usize = 50000;
+
+    #[test]
+    fn generate_noise() {
+        let domain = GeoDomain::default().with_privacy_radius(2.0);
+        // eps of 0.5 per kilometer
+        let noiser = PlanarNoiser::initialize(1.0, 1, 1.0, &domain);
+        let origin = GeoPoint::new(52.0, 5.0);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let mean_distance = (0..SAMPLE_SIZE)
+            .map(|_| origin.haversine(&noiser.generate_noise(&origin, &mut rng)))
+            .sum::<f64>()
+            / SAMPLE_SIZE as f64;
+
+        // the expected distance of the planar laplace is 2 / eps
+        assert!((mean_distance - 4.0).abs() < 0.2)
+    }
+}
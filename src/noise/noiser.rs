@@ -1,8 +1,26 @@
 use crate::data_manipulation::anonymizable::{Anonymizable, QuasiIdentifierTypes};
+use crate::error::DiffPrivError;
 
 /// This trait lets you implement a custom noising function to add ε-differential privacy to
 /// a struct that implements `Anonymizable`
 /// DiffPriv already supports Laplace noise as a possible noiser
 pub trait Noiser: Default + Clone + Sync {
     fn add_noise<M: Anonymizable>(&mut self, value: &M) -> Vec<QuasiIdentifierTypes>;
+
+    /// add noise to the QI's, return an error instead of panicking on QI's that can't be noised
+    fn try_add_noise<M: Anonymizable>(
+        &mut self,
+        value: &M,
+    ) -> Result<Vec<QuasiIdentifierTypes>, DiffPrivError> {
+        Ok(self.add_noise(value))
+    }
+
+    /// reseed the random number generator of the noiser, the anonymizer gives the
+    /// noiser of every cluster its own seed. Noisers without randomness can ignore it
+    fn reseed(&mut self, _seed: u64) {}
+
+    /// check the privacy parameters of the noiser before it is used by an anonymizer
+    fn validate(&self) -> Result<(), DiffPrivError> {
+        Ok(())
+    }
 }
@@ -0,0 +1,183 @@
+use crate::data_manipulation::anonymizable::{
+    QuasiIdentifierType, QuasiIdentifierTypes, SensitiveAttribute,
+};
+use crate::data_manipulation::geo::GeoPoint;
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+/// What is published for every tuple
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// the tuple with the noisy centroid of its cluster as QI's
+    #[default]
+    Microaggregation,
+    /// a `GeneralizedRecord` with the range of every QI inside the cluster
+    Generalization,
+    /// both the microaggregated tuple and the `GeneralizedRecord`
+    Both,
+}
+
+impl OutputMode {
+    pub fn microaggregation(&self) -> bool {
+        matches!(self, OutputMode::Microaggregation | OutputMode::Both)
+    }
+
+    pub fn generalization(&self) -> bool {
+        matches!(self, OutputMode::Generalization | OutputMode::Both)
+    }
+}
+
+/// Generalization of a single QI to the values of the tuples in a cluster
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GeneralizedQi {
+    /// interval QI's and the ranks of ordinal QI's
+    IntegerRange {
+        min: i32,
+        max: i32,
+    },
+    FloatRange {
+        min: f64,
+        max: f64,
+    },
+    /// seconds since the unix epoch
+    TimeRange {
+        min: i64,
+        max: i64,
+    },
+    /// corners of the box containing the geo points
+    BoundingBox {
+        min: GeoPoint,
+        max: GeoPoint,
+    },
+    /// the values of nominal and hierarchical QI's
+    Categories {
+        values: Vec<i32>,
+    },
+    /// all the values in the cluster are missing
+    Missing,
+    /// the cluster doesn't satisfy l-diversity or t-closeness
+    Suppressed,
+}
+
+impl GeneralizedQi {
+    /// generalization of a QI to its whole domain
+    pub fn full_domain(qi: &QuasiIdentifierTypes) -> Self {
+        match qi {
+            QuasiIdentifierTypes::Interval((_, min, max, _)) => match (min, max) {
+                (QuasiIdentifierType::Integer(min), QuasiIdentifierType::Integer(max)) => {
+                    GeneralizedQi::IntegerRange {
+                        min: *min,
+                        max: *max,
+                    }
+                }
+                (QuasiIdentifierType::Float(min), QuasiIdentifierType::Float(max)) => {
+                    GeneralizedQi::FloatRange {
+                        min: *min,
+                        max: *max,
+                    }
+                }
+                _ => panic!("Wrong combination of type found in domain of interval"),
+            },
+            QuasiIdentifierTypes::Ordinal((_, max_rank, _)) => GeneralizedQi::IntegerRange {
+                min: 1,
+                max: *max_rank,
+            },
+            QuasiIdentifierTypes::Nominal((_, max_value, _)) => GeneralizedQi::Categories {
+                values: (0..=*max_value).collect(),
+            },
+            QuasiIdentifierTypes::Hierarchical((_, hierarchy, _)) => GeneralizedQi::Categories {
+                values: (0..hierarchy.leaf_count() as i32).collect(),
+            },
+            QuasiIdentifierTypes::Temporal((_, _, domain, _)) => GeneralizedQi::TimeRange {
+                min: domain.min,
+                max: domain.max,
+            },
+            QuasiIdentifierTypes::GeoPoint(_) => GeneralizedQi::BoundingBox {
+                min: GeoPoint {
+                    latitude: -90.0,
+                    longitude: -180.0,
+                },
+                max: GeoPoint {
+                    latitude: 90.0,
+                    longitude: 180.0,
+                },
+            },
+            QuasiIdentifierTypes::Missing(template) => GeneralizedQi::full_domain(template),
+        }
+    }
+}
+
+impl Display for GeneralizedQi {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneralizedQi::IntegerRange { min, max } => write!(f, "[{},{}]", min, max),
+            GeneralizedQi::FloatRange { min, max } => write!(f, "[{},{}]", min, max),
+            GeneralizedQi::TimeRange { min, max } => write!(f, "[{},{}]", min, max),
+            GeneralizedQi::BoundingBox { min, max } => write!(f, "[{};{}]", min, max),
+            GeneralizedQi::Categories { values } => write!(f, "{{{}}}", values.iter().join(",")),
+            GeneralizedQi::Missing => write!(f, ""),
+            GeneralizedQi::Suppressed => write!(f, "*"),
+        }
+    }
+}
+
+/// Tuple published as a classic k-anonymous generalization, every QI is replaced by
+/// the range of the values in its cluster. The ranges are not noised
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneralizedRecord {
+    pub cluster: Uuid,
+    pub quasi_identifiers: Vec<GeneralizedQi>, // in the order of `quasi_identifiers()`
+    pub sensitive_values: Vec<SensitiveAttribute>,
+    pub disclosure_risk: f64,
+}
+
+impl GeneralizedRecord {
+    /// all the values in string format to be used for creating CSV
+    pub fn extract_string_values(&self) -> Vec<String> {
+        [self.cluster.to_string(), self.disclosure_risk.to_string()]
+            .into_iter()
+            .chain(self.quasi_identifiers.iter().map(|qi| qi.to_string()))
+            .chain(self.sensitive_values.iter().map(|value| value.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_generalized_record() {
+        let record = GeneralizedRecord {
+            cluster: Uuid::nil(),
+            quasi_identifiers: vec![
+                GeneralizedQi::IntegerRange { min: 30, max: 40 },
+                GeneralizedQi::Categories { values: vec![0, 2] },
+                GeneralizedQi::Missing,
+            ],
+            sensitive_values: vec![SensitiveAttribute::String("flu".to_string())],
+            disclosure_risk: 0.5,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            serde_json::json!({
+                "cluster": Uuid::nil().to_string(),
+                "quasi_identifiers": [
+                    { "type": "integer_range", "min": 30, "max": 40 },
+                    { "type": "categories", "values": [0, 2] },
+                    { "type": "missing" }
+                ],
+                "sensitive_values": ["flu"],
+                "disclosure_risk": 0.5
+            })
+        );
+        assert_eq!(
+            record.extract_string_values()[2..],
+            ["[30,40]", "{0,2}", "", "flu"]
+        )
+    }
+}
@@ -1,6 +1,8 @@
 use crate::config::Config;
 use crate::data_manipulation::anonymizable::Anonymizable;
 use crate::data_manipulation::mueller::MuellerStream;
+use crate::error::DiffPrivError;
+use crate::publishing::generalized::GeneralizedRecord;
 use crate::publishing::publisher::Publisher;
 use avro_rs::{to_avro_datum, to_value};
 use kafka::producer::{Producer, Record, RequiredAcks};
@@ -12,6 +14,8 @@ pub struct KafkaPublisher {
     producer: Producer,
     pub confluent_bytes: Vec<u8>,
     topic_out: String,
+    dead_letter_topic: Option<String>,
+    generalized_topic: Option<String>,
     published: i32,
 }
 
@@ -22,6 +26,25 @@ impl KafkaPublisher {
             ..Default::default()
         }
     }
+
+    /// send a raw record that couldn't be anonymized to the dead letter topic,
+    /// returns an error when it couldn't be sent so its offset isn't committed
+    pub fn publish_dead_letter(&mut self, payload: &[u8]) -> Result<(), DiffPrivError> {
+        let topic = match &self.dead_letter_topic {
+            Some(topic) => topic,
+            None => {
+                warn!("no dead_letter_topic configured, dropping record");
+                return Ok(());
+            }
+        };
+
+        self.producer
+            .send(&Record::from_value(topic.as_str(), payload))
+            .map_err(|e| {
+                error!("couldn't send dead letter: {:?}", e);
+                DiffPrivError::Publish(format!("dead letter to `{}`: {:?}", topic, e))
+            })
+    }
 }
 
 impl Default for KafkaPublisher {
@@ -39,13 +62,15 @@ impl Default for KafkaPublisher {
             confluent_bytes,
             producer,
             topic_out: config.topic_out,
+            dead_letter_topic: config.dead_letter_topic,
+            generalized_topic: config.generalized_topic,
             published: 0,
         }
     }
 }
 
 impl Publisher for KafkaPublisher {
-    fn publish<M: Anonymizable>(&mut self, value: M, uuid: Uuid, dr: f64) {
+    fn publish<M: Anonymizable>(&mut self, value: M, _uuid: Uuid, _dr: f64) {
         let converted_value = to_value(value).unwrap();
         let mut datum = to_avro_datum(
             &MuellerStream::get_schema(MuellerStream::STRM_SCHEMA),
@@ -72,4 +97,23 @@ impl Publisher for KafkaPublisher {
             }
         }
     }
+
+    /// send the generalized record as JSON to the generalized topic
+    fn publish_generalized(&mut self, record: GeneralizedRecord) {
+        let topic = match &self.generalized_topic {
+            Some(topic) => topic,
+            None => {
+                warn!("no generalized_topic configured, dropping generalized record");
+                return;
+            }
+        };
+
+        let payload = serde_json::to_vec(&record).unwrap();
+        if let Err(e) = self
+            .producer
+            .send(&Record::from_value(topic.as_str(), payload))
+        {
+            error!("couldn't send generalized record: {:?}", e)
+        }
+    }
 }
@@ -1,3 +1,5 @@
 pub mod csv_publisher;
+pub mod generalized;
 pub mod kafka_publisher;
 pub mod publisher;
+pub mod shared_publisher;
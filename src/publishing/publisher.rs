@@ -1,4 +1,5 @@
 use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::publishing::generalized::GeneralizedRecord;
 use uuid::Uuid;
 
 /// Generic trait for publishing the anonymized data
@@ -6,4 +7,13 @@ use uuid::Uuid;
 /// convert a value for specific publishers
 pub trait Publisher {
     fn publish<M: Anonymizable>(&mut self, value: M, uuid: Uuid, dr: f64);
+
+    /// publish a tuple generalized to the ranges of its cluster, used with
+    /// `OutputMode::Generalization` and `OutputMode::Both`
+    fn publish_generalized(&mut self, record: GeneralizedRecord) {
+        warn!(
+            "publisher doesn't support generalized records, dropping record of cluster {}",
+            record.cluster
+        )
+    }
 }
@@ -0,0 +1,42 @@
+use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::publishing::generalized::GeneralizedRecord;
+use crate::publishing::publisher::Publisher;
+use std::sync::{Arc, Mutex, MutexGuard};
+use uuid::Uuid;
+
+/// Publisher shared by the shards of a `ShardedAnonymizer`, every clone
+/// publishes to the same publisher one tuple at a time
+pub struct SharedPublisher<P: Publisher> {
+    publisher: Arc<Mutex<P>>,
+}
+
+impl<P: Publisher> SharedPublisher<P> {
+    pub fn new(publisher: P) -> Self {
+        Self {
+            publisher: Arc::new(Mutex::new(publisher)),
+        }
+    }
+
+    /// the shared publisher, e.g. to read what was published
+    pub fn lock(&self) -> MutexGuard<'_, P> {
+        self.publisher.lock().unwrap()
+    }
+}
+
+impl<P: Publisher> Clone for SharedPublisher<P> {
+    fn clone(&self) -> Self {
+        Self {
+            publisher: Arc::clone(&self.publisher),
+        }
+    }
+}
+
+impl<P: Publisher> Publisher for SharedPublisher<P> {
+    fn publish<M: Anonymizable>(&mut self, value: M, uuid: Uuid, dr: f64) {
+        self.lock().publish(value, uuid, dr)
+    }
+
+    fn publish_generalized(&mut self, record: GeneralizedRecord) {
+        self.lock().publish_generalized(record)
+    }
+}
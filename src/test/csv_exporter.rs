@@ -70,10 +70,15 @@ impl CsvExporter {
         println!("Exporting to {}", self.path);
         let mut writer = Writer::from_path(&self.path)?;
         // write header
-        match self.dataset {
-            Datasets::Adult(_) => writer.write_record(&ADULT_HEADERS)?,
-            Datasets::AdultLarge(_) => writer.write_record(&ADULT_LARGE_HEADERS)?,
-            Datasets::Mueller(_) => writer.write_record(&MUELLER_HEADERS)?,
+        match &self.dataset {
+            Datasets::Adult(_) => writer.write_record(ADULT_HEADERS)?,
+            Datasets::AdultLarge(_) => writer.write_record(ADULT_LARGE_HEADERS)?,
+            Datasets::Mueller(_) => writer.write_record(MUELLER_HEADERS)?,
+            Datasets::Dynamic(dataset) => {
+                let mut headers = vec!["uuid".to_string(), "disclosure_risk".to_string()];
+                headers.extend(dataset.schema.headers());
+                writer.write_record(headers)?
+            }
         }
 
         for record in &self.data {
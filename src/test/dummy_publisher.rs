@@ -1,4 +1,5 @@
 use crate::data_manipulation::anonymizable::Anonymizable;
+use crate::publishing::generalized::GeneralizedRecord;
 use crate::publishing::publisher::Publisher;
 use uuid::Uuid;
 
@@ -6,5 +7,7 @@ use uuid::Uuid;
 pub struct DummyPublisher {}
 
 impl Publisher for DummyPublisher {
-    fn publish<M: Anonymizable>(&mut self, _value: M, uuid: Uuid, dr: f64) {}
+    fn publish<M: Anonymizable>(&mut self, _value: M, _uuid: Uuid, _dr: f64) {}
+
+    fn publish_generalized(&mut self, _record: GeneralizedRecord) {}
 }
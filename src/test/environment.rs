@@ -1,3 +1,7 @@
+use std::sync::Arc;
+
+use crate::data_manipulation::dynamic_record::DynamicSchema;
+
 pub struct Environment {
     pub k: usize,
     pub k_max: usize,
@@ -10,21 +14,33 @@ pub struct Environment {
     pub noise_thr: f64,
     pub dataset: Datasets,
     pub publish_remaining_tuples: bool,
+    pub seed: Option<u64>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum Datasets {
     Adult(Dataset),
     AdultLarge(Dataset),
     Mueller(Dataset),
+    Dynamic(DynamicDataset),
 }
 
 impl Datasets {
-    pub fn extract(&self) -> Dataset {
-        match *self {
-            Datasets::Adult(dataset) => dataset,
-            Datasets::AdultLarge(dataset) => dataset,
-            Datasets::Mueller(dataset) => dataset,
+    pub fn path(&self) -> &str {
+        match self {
+            Datasets::Adult(dataset) => dataset.path,
+            Datasets::AdultLarge(dataset) => dataset.path,
+            Datasets::Mueller(dataset) => dataset.path,
+            Datasets::Dynamic(dataset) => &dataset.path,
+        }
+    }
+
+    pub fn export(&self) -> &str {
+        match self {
+            Datasets::Adult(dataset) => dataset.export,
+            Datasets::AdultLarge(dataset) => dataset.export,
+            Datasets::Mueller(dataset) => dataset.export,
+            Datasets::Dynamic(dataset) => &dataset.export,
         }
     }
 }
@@ -35,6 +51,15 @@ pub struct Dataset {
     pub export: &'static str,
 }
 
+/// dataset configured in the `application.conf` whose
+/// columns are described by a schema
+#[derive(Clone)]
+pub struct DynamicDataset {
+    pub path: String,
+    pub export: String,
+    pub schema: Arc<DynamicSchema>,
+}
+
 impl Environment {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -48,6 +73,7 @@ impl Environment {
         noise_thr: f64,
         dataset: Datasets,
         publish_remaining_tuples: bool,
+        seed: Option<u64>,
     ) -> Self {
         Self {
             k,
@@ -61,6 +87,7 @@ impl Environment {
             noise_thr,
             dataset,
             publish_remaining_tuples,
+            seed,
         }
     }
 }
@@ -1,10 +1,6 @@
 #[derive(Default, Serialize, Debug)]
 pub struct Metrics {
-    pub mse: f64,
-    pub sse: f64,
-    pub publishing_delay: u128,
     pub execution_time: u128,
-    pub clusters_created: i32,
-    pub clusters_deleted: i32,
-    pub disclosure_risk: f64,
+    #[serde(flatten)]
+    pub analysers: serde_json::Map<String, serde_json::Value>, // reports of the analysers by their name
 }
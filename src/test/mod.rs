@@ -1,5 +1,5 @@
 pub mod adult;
-mod adult_large;
+pub mod adult_large;
 pub mod csv_exporter;
 mod csv_importer;
 pub mod dummy_publisher;
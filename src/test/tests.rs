@@ -2,25 +2,32 @@ use crate::data_manipulation::anonymizable::Anonymizable;
 use crate::test::adult::Adult;
 use crate::test::adult_large::AdultLarge;
 use crate::test::csv_importer::{CsvImporter, EnrichedRow};
-use crate::test::environment::{Dataset, Datasets, Environment};
+use crate::test::environment::{Dataset, Datasets, DynamicDataset, Environment};
 
 use crate::config::Config;
+use crate::data_manipulation::dynamic_record::DynamicSchema;
 use csv::Reader;
 use serde::de::DeserializeOwned;
+use std::sync::Arc;
 
 /**
     This file contains the main test architecture to run all the different possible
     sets of parameters defined in the `application.conf`
 **/
 
+#[allow(clippy::empty_line_after_doc_comments)]
 pub fn start_tests(conf_file: &String) {
     let config = Config::new(conf_file);
-    let environments = prepare_environments(&config);
-    environments.into_iter().for_each(|env| match env.dataset {
-        Datasets::Adult(dataset) => create_test::<Adult>(env, dataset),
-        Datasets::AdultLarge(dataset) => create_test::<AdultLarge>(env, dataset),
-        Datasets::Mueller(dataset) => create_test::<EnrichedRow>(env, dataset),
-    });
+    let datasets = prepare_datasets(&config);
+    let environments = prepare_environments(&config, &datasets);
+    environments
+        .into_iter()
+        .for_each(|env| match env.dataset.clone() {
+            Datasets::Adult(dataset) => create_test::<Adult>(env, dataset),
+            Datasets::AdultLarge(dataset) => create_test::<AdultLarge>(env, dataset),
+            Datasets::Mueller(dataset) => create_test::<EnrichedRow>(env, dataset),
+            Datasets::Dynamic(dataset) => create_dynamic_test(env, dataset),
+        });
 }
 
 fn create_test<A: Anonymizable + DeserializeOwned>(env: Environment, dataset: Dataset) {
@@ -35,6 +42,39 @@ fn create_test<A: Anonymizable + DeserializeOwned>(env: Environment, dataset: Da
     }
 }
 
+fn create_dynamic_test(env: Environment, dataset: DynamicDataset) {
+    println!("Reading file {}", dataset.path);
+    let file = Reader::from_path(&dataset.path).unwrap();
+    let mut csv_importer = CsvImporter::new(file);
+    match csv_importer.convert_dynamic(env, dataset.schema) {
+        Ok(_) => {}
+        Err(e) => {
+            println!("{}", e)
+        }
+    }
+}
+
+/// use the datasets configured in the `application.conf`, fall back
+/// on the built-in datasets if none are configured
+fn prepare_datasets(config: &Config) -> Vec<Datasets> {
+    match config.datasets.is_empty() {
+        true => DATASETS.to_vec(),
+        false => config
+            .datasets
+            .iter()
+            .map(|dataset| {
+                let schema = DynamicSchema::from_file(&dataset.schema)
+                    .unwrap_or_else(|e| panic!("couldn't load schema {}: {}", dataset.schema, e));
+                Datasets::Dynamic(DynamicDataset {
+                    path: dataset.path.to_owned(),
+                    export: dataset.export.to_owned(),
+                    schema: Arc::new(schema),
+                })
+            })
+            .collect(),
+    }
+}
+
 const DATASETS: [Datasets; 3] = [
     Datasets::Adult(Dataset {
         path: "datasets/Adult_1_numeric_only_class_50K.csv",
@@ -50,9 +90,9 @@ const DATASETS: [Datasets; 3] = [
     }),
 ];
 
-fn prepare_environments(config: &Config) -> Vec<Environment> {
+fn prepare_environments(config: &Config, datasets: &[Datasets]) -> Vec<Environment> {
     let mut environments: Vec<Environment> = vec![];
-    DATASETS.iter().copied().for_each(|dataset| {
+    datasets.iter().for_each(|dataset| {
         config.k.iter().copied().for_each(|k| {
             config.l.iter().copied().for_each(|l| {
                 config.c.iter().copied().for_each(|c| {
@@ -69,8 +109,9 @@ fn prepare_environments(config: &Config) -> Vec<Environment> {
                                         eps,
                                         delta as u128,
                                         noise_thr,
-                                        dataset,
+                                        dataset.clone(),
                                         config.publish_remaining_tuples,
+                                        config.seed,
                                     ))
                                 })
                             })
@@ -1,8 +1,13 @@
 /// based on https://stackoverflow.com/questions/53755017/can-i-randomly-sample-from-a-hashset-efficiently
 /// used to efficiently retrieve observed values in the Laplace noiser
 use std::collections::HashSet;
+use std::hash::Hash;
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: serde::Serialize + Eq + Hash",
+    deserialize = "T: serde::Deserialize<'de> + Eq + Hash"
+))]
 pub struct VecSet<T> {
     set: HashSet<T>,
     vec: Vec<T>,
@@ -16,6 +21,10 @@ where
         self.set.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
     pub fn insert(&mut self, elem: T) {
         assert_eq!(self.set.len(), self.vec.len());
         let was_new = self.set.insert(elem.clone());